@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE telegram_accounts;
+DROP TABLE users;
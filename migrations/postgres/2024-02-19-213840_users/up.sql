@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE users(
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    start VARCHAR NOT NULL,
+    role VARCHAR NOT NULL,
+    UNIQUE(name),
+    UNIQUE(start)
+);
+
+CREATE TABLE telegram_accounts(
+  id BIGINT NOT NULL PRIMARY KEY,
+  user_id BIGINT NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users (id)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE incidents(
+    id VARCHAR NOT NULL PRIMARY KEY,
+    created_at BIGINT NOT NULL,
+    update_id BIGINT NOT NULL,
+    chat_id BIGINT,
+    command VARCHAR,
+    dialogue_state VARCHAR,
+    error VARCHAR NOT NULL
+);
+
+CREATE INDEX incidents_created_at ON incidents (created_at);
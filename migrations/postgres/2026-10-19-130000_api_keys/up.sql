@@ -0,0 +1,8 @@
+CREATE TABLE api_keys(
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    key_hash VARCHAR NOT NULL,
+    created_at BIGINT NOT NULL,
+    UNIQUE(name),
+    UNIQUE(key_hash)
+);
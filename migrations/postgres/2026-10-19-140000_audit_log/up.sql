@@ -0,0 +1,21 @@
+CREATE TABLE audit_log(
+    id BIGSERIAL PRIMARY KEY,
+    created_at BIGINT NOT NULL,
+    actor VARCHAR NOT NULL,
+    action VARCHAR NOT NULL,
+    target VARCHAR,
+    detail VARCHAR
+);
+
+CREATE INDEX audit_log_created_at ON audit_log (created_at);
+
+CREATE TABLE broadcasts(
+    id BIGSERIAL PRIMARY KEY,
+    created_at BIGINT NOT NULL,
+    sender VARCHAR NOT NULL,
+    message VARCHAR NOT NULL,
+    recipients BIGINT NOT NULL,
+    delivered BIGINT NOT NULL
+);
+
+CREATE INDEX broadcasts_created_at ON broadcasts (created_at);
@@ -0,0 +1,14 @@
+CREATE TABLE event_outbox(
+    id BIGSERIAL PRIMARY KEY,
+    created_at BIGINT NOT NULL,
+    event_id VARCHAR NOT NULL,
+    event_type VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    payload VARCHAR NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    next_attempt_at BIGINT,
+    delivered_at BIGINT,
+    last_error VARCHAR
+);
+
+CREATE INDEX event_outbox_next_attempt_at ON event_outbox (next_attempt_at);
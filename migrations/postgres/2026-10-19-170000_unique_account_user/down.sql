@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX telegram_accounts_user_id;
@@ -0,0 +1,9 @@
+CREATE TABLE `orders`(
+    `id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    `created_at` BIGINT NOT NULL,
+    `telegram_id` BIGINT NOT NULL,
+    `full_name` VARCHAR NOT NULL,
+    `product` VARCHAR NOT NULL
+);
+
+CREATE INDEX `orders_telegram_id` ON `orders` (`telegram_id`);
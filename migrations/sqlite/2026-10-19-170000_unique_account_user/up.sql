@@ -0,0 +1,4 @@
+-- A user is linked to at most one telegram account, also with concurrent registrations.
+-- Users linked to several accounts keep the account with the lowest id.
+DELETE FROM `telegram_accounts` WHERE `id` NOT IN (SELECT MIN(`id`) FROM `telegram_accounts` GROUP BY `user_id`);
+CREATE UNIQUE INDEX `telegram_accounts_user_id` ON `telegram_accounts` (`user_id`);
@@ -0,0 +1,50 @@
+use crate::bot::core::admin_api::auth::{hash_api_key, new_api_key};
+use crate::bot::core::audit::{audit, Actor, AuditAction};
+use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
+use crate::bot::core::db::client::DatabaseClient;
+use crate::MyResult;
+
+/// Manage the api keys of the admin api.
+#[derive(clap::Parser)]
+pub struct ApiKeyCli {
+    #[command(subcommand)]
+    pub(crate) task: ApiKeyTaskCli,
+}
+
+#[derive(clap::Subcommand)]
+pub enum ApiKeyTaskCli {
+    /// Create an api key, it is only shown once
+    Create { name: String },
+    /// List the names of all api keys
+    List,
+    /// Delete an api key, requests with it are rejected immediately
+    Delete { name: String },
+}
+
+impl ApiKeyCli {
+    pub(crate) async fn default_handling(&self, database_client: &DatabaseClient) -> MyResult {
+        match &self.task {
+            ApiKeyTaskCli::Create { name } => {
+                let api_key = new_api_key();
+                database_client.create_api_key(name, &hash_api_key(&api_key)).await?;
+                audit(database_client, Actor::Cli, AuditAction::ApiKeyCreated, name, None).await;
+                println!("Created api key '{}', it is not shown again:", name);
+                println!("{}", api_key);
+            }
+            ApiKeyTaskCli::List => {
+                for api_key in database_client.list_api_keys().await? {
+                    let created_at = chrono::DateTime::from_timestamp(api_key.created_at, 0)
+                        .map(|created_at| created_at.to_rfc3339())
+                        .unwrap_or_else(|| api_key.created_at.to_string());
+                    println!("id={}: name={} created_at={}", api_key.id, api_key.name, created_at);
+                }
+            }
+            ApiKeyTaskCli::Delete { name } => {
+                let api_key = database_client.delete_api_key(name).await?;
+                audit(database_client, Actor::Cli, AuditAction::ApiKeyDeleted, &api_key.name, None).await;
+                println!("Deleted api key '{}'", api_key.name);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+
+use crate::bot::core::bot_config::storage::{BotStorageConfig, DatabaseBackend};
+#[cfg(feature = "sqlite")]
+use crate::bot::core::db::backup::{backup_database, open_read_only, restore_database, timestamped_backup_path, verify_backup};
+#[cfg(feature = "sqlite")]
+use crate::bot::core::db::check::{applied_migration_count, check_database};
+#[cfg(feature = "sqlite")]
+use crate::bot::core::db::connection::AnyConnection;
+use crate::bot::core::db::connection::MyDatabaseConnection;
+use crate::bot::core::db::migration;
+use crate::MyResult;
+
+/// Maintain the bot database.
+#[derive(clap::Parser)]
+pub struct DbCli {
+    #[command(subcommand)]
+    pub(crate) task: DbTaskCli,
+}
+
+#[derive(clap::Subcommand)]
+pub enum DbTaskCli {
+    /// Write a consistent copy of the database to a new file, safe while the bot is running
+    Backup { path: PathBuf },
+    /// Replace the database with the content of a backup file, stop the bot before restoring
+    Restore { path: PathBuf },
+    /// Run integrity and foreign key checks and list pending migrations, the database is not modified
+    Check,
+    /// Apply pending migrations, the database is backed up first
+    Migrate,
+    /// List applied and pending migrations
+    Status,
+    /// Revert the most recently applied migration, the database is backed up first
+    Rollback,
+}
+
+impl DbCli {
+    pub(crate) async fn default_handling(&self) -> MyResult {
+        match &self.task {
+            DbTaskCli::Backup { .. } | DbTaskCli::Restore { .. } | DbTaskCli::Check => {
+                let storage_config = BotStorageConfig::new()?;
+                if storage_config.database_backend().ne(&DatabaseBackend::Sqlite) {
+                    return Err(anyhow!("Backup, restore and check only support sqlite. Use the postgres tools (pg_dump, pg_restore) instead."));
+                }
+                self.sqlite_maintenance(&storage_config).await?;
+            }
+            DbTaskCli::Migrate => {
+                let database_connection = MyDatabaseConnection::open().await?;
+                let connection = &mut database_connection.get().await?;
+                let migrated = migration::run_migrations(connection, &database_connection.database_path)?;
+                if migrated.is_empty() {
+                    println!("Database schema is up to date.");
+                }
+                for version in migrated {
+                    println!("Applied migration {}", version);
+                }
+            }
+            DbTaskCli::Status => {
+                let database_connection = MyDatabaseConnection::open().await?;
+                let connection = &mut database_connection.get().await?;
+                let status = migration::migration_status(connection)?;
+                print!("{}", status);
+                if status.is_schema_newer() {
+                    println!("Database schema is newer than this binary.");
+                }
+            }
+            DbTaskCli::Rollback => {
+                let database_connection = MyDatabaseConnection::open().await?;
+                let connection = &mut database_connection.get().await?;
+                let version = migration::revert_last_migration(connection, &database_connection.database_path)?;
+                println!("Reverted migration {}", version);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    async fn sqlite_maintenance(&self, storage_config: &BotStorageConfig) -> MyResult {
+        let database_path = storage_config.database_path();
+        match &self.task {
+            DbTaskCli::Backup { path } => {
+                backup_database(&database_path, path)?;
+                println!("Created backup {:?} of database {:?}", path, database_path);
+            }
+            DbTaskCli::Restore { path } => {
+                verify_backup(path)?;
+
+                if database_path.exists() {
+                    let data_directory = PathBuf::from(&storage_config.data_directory);
+                    let safety_backup_path = timestamped_backup_path(&data_directory, "db-before-restore-");
+                    backup_database(&database_path, &safety_backup_path)?;
+                    println!("Saved current database to {:?}", safety_backup_path);
+                }
+                restore_database(path, &database_path)?;
+                println!("Restored database {:?} from backup {:?}", database_path, path);
+            }
+            DbTaskCli::Check => {
+                let mut connection = open_read_only(&database_path)?;
+                let report = check_database(&mut connection)?;
+                print!("{}", report);
+                if !report.is_ok() {
+                    return Err(anyhow!("Database check failed."));
+                }
+                let status = if applied_migration_count(&mut connection)? == 0 {
+                    None
+                } else {
+                    Some(migration::migration_status(&mut AnyConnection::Sqlite(connection))?)
+                };
+                match status {
+                    None => println!("migrations: none applied, the bot migrates the database on its next start"),
+                    Some(status) if !status.pending.is_empty() => println!("migrations: pending {}", status.pending.join(", ")),
+                    Some(status) if status.is_schema_newer() => println!("migrations: database schema is newer than this binary"),
+                    Some(_) => println!("migrations: up to date"),
+                }
+                println!("Database is healthy.");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    async fn sqlite_maintenance(&self, _storage_config: &BotStorageConfig) -> MyResult {
+        Err(anyhow!("This build does not support sqlite, enable the cargo feature 'sqlite'."))
+    }
+}
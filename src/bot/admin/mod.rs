@@ -1,9 +1,26 @@
+use anyhow::anyhow;
+use reqwest::Url;
+
+use crate::bot::admin::api_key::ApiKeyCli;
+use crate::bot::admin::db::DbCli;
+use crate::bot::core::audit::{audit, Actor, AuditAction};
+use crate::bot::core::bot_config::{BotConfig, TELEGRAM_BOT_ENDPOINT_DASHBOARD};
+use crate::bot::core::bot_config::dashboard::BotDashboardConfig;
+use crate::bot::core::bot_config::events::BotEventsConfig;
+use crate::bot::core::bot_config::webhook::BotConfigWebHook;
+use crate::bot::core::dashboard::login::signed_login_query;
 use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
 use crate::bot::core::db::client::DatabaseClient;
 use crate::bot::core::db::connection::MyDatabaseConnection;
 use crate::bot::core::db::user_representation::UserRole;
+use crate::bot::core::events::{BotEvent, EventBus};
+use crate::bot::core::redaction::TelegramId;
+use crate::bot::core::repository::{AccountRepository, IncidentRepository, UserRepository};
 use crate::MyResult;
 
+pub(crate) mod api_key;
+pub(crate) mod db;
+
 /// Manage the bot.
 #[derive(clap::Parser)]
 pub struct AdminCli {
@@ -21,6 +38,22 @@ pub enum TaskCli {
     Delete { user_name: String },
     /// Link telegram id to user account
     AddTelegram { start_token: String, telegram_id: i64 },
+    /// List the most recent incidents of failed updates
+    Incidents {
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Print a dashboard login link signed with the bot token, to log in without the login widget
+    DashboardLogin {
+        telegram_id: i64,
+        /// Defaults to the dashboard listener or the public url of the webhook
+        #[arg(long)]
+        base_url: Option<Url>,
+    },
+    /// Database maintenance
+    Db(DbCli),
+    /// Api keys of the admin api
+    ApiKey(ApiKeyCli),
 }
 
 const PRINT_BARRIER: &str = "----------------------------------------";
@@ -34,10 +67,25 @@ fn print_header(msg: &str, with_newline: bool) {
     println!("{}", PRINT_BARRIER);
 }
 
+/// The separate dashboard listener or the webhook listener.
+fn dashboard_base_url() -> Result<Url, anyhow::Error> {
+    if let Some(socket_address) = BotDashboardConfig::new()?.socket_address {
+        return Ok(Url::parse(&format!("http://{}", socket_address))?);
+    }
+    BotConfigWebHook::new()
+        .map(|webhook_config| webhook_config.public_url)
+        .map_err(|error| anyhow!("Could not find the dashboard url, pass --base-url. {}", error))
+}
+
 impl AdminCli {
     pub(crate) async fn default_handling(&self) -> MyResult {
+        if let TaskCli::Db(db_cli) = &self.task {
+            // maintenance tasks manage their own database access
+            return db_cli.default_handling().await;
+        }
+        let bot_config = BotConfig::new()?;
         let database_connection = MyDatabaseConnection::new().await?;
-        let mut database_client = DatabaseClient::load(database_connection.clone()).await?;
+        let mut database_client = DatabaseClient::load(database_connection.clone(), &bot_config.bot_name).await?;
 
         match &self.task {
             TaskCli::Show => {
@@ -55,16 +103,62 @@ impl AdminCli {
             }
             TaskCli::Add { user_name, role } => {
                 let user = database_client.create_user(user_name, role).await?;
+                audit(&database_client, Actor::Cli, AuditAction::UserCreated, &user.name, Some(format!("role={}", user.role))).await;
                 println!("Created user {}", user);
             }
             TaskCli::Delete { user_name } => {
                 let user = database_client.delete_user(user_name).await?;
+                audit(&database_client, Actor::Cli, AuditAction::UserDeleted, &user.name, None).await;
+                // delivered by the running bot
+                EventBus::new(BotEventsConfig::new()?)
+                    .publish(&database_client, BotEvent::UserDeleted { user_name: user.name.clone(), telegram_id: user.telegram_id })
+                    .await;
                 println!("Deleted user {}", user);
             }
             TaskCli::AddTelegram { start_token, telegram_id } => {
                 let result = database_client.register_telegram_account_of_user(start_token, *telegram_id).await?;
+                audit(&database_client, Actor::Cli, AuditAction::TelegramLinked, &result.name, None).await;
+                EventBus::new(BotEventsConfig::new()?)
+                    .publish(&database_client, BotEvent::UserRegistered { user_name: result.name.clone(), telegram_id: *telegram_id })
+                    .await;
                 println!("{:?}", result);
             }
+            TaskCli::Incidents { limit } => {
+                print_header("List recent incidents:", false);
+                for incident in database_client.list_incidents(*limit).await? {
+                    let created_at = chrono::DateTime::from_timestamp(incident.created_at, 0)
+                        .map(|created_at| created_at.to_rfc3339())
+                        .unwrap_or_else(|| incident.created_at.to_string());
+                    let chat = incident.chat_id
+                        .map(|chat_id| format!("{} ({})", chat_id, TelegramId(chat_id)))
+                        .unwrap_or_else(|| "-".to_string());
+                    println!("{} at {}: update={} chat={} command={} dialogue_state={}\n    {}",
+                             incident.id, created_at, incident.update_id, chat,
+                             incident.command.as_deref().unwrap_or("-"),
+                             incident.dialogue_state.as_deref().unwrap_or("-"),
+                             incident.error);
+                }
+            }
+            TaskCli::DashboardLogin { telegram_id, base_url } => {
+                let base_url = match base_url {
+                    Some(base_url) => base_url.clone(),
+                    None => dashboard_base_url()?,
+                };
+                let first_name = database_client.known_user(*telegram_id).await?
+                    .map(|user| user.name)
+                    .unwrap_or_else(|| "unknown".to_string());
+                let query = signed_login_query(*telegram_id, &first_name, &bot_config.bot_token, chrono::Utc::now().timestamp());
+                let mut url = base_url.join(&format!("{}/login/telegram", TELEGRAM_BOT_ENDPOINT_DASHBOARD))?;
+                url.set_query(Some(&query));
+                println!("Login link for {}, valid for an hour:", first_name);
+                println!("{}", url);
+            }
+            TaskCli::ApiKey(api_key_cli) => {
+                api_key_cli.default_handling(&database_client).await?;
+            }
+            TaskCli::Db(_) => {
+                unreachable!("Database maintenance is handled above.")
+            }
         }
         Ok(())
     }
@@ -0,0 +1,96 @@
+use crate::bot::core::bot_config::BotConfig;
+use crate::bot::core::bot_config::admin_api::BotAdminApiConfig;
+use crate::bot::core::bot_config::dashboard::BotDashboardConfig;
+use crate::bot::core::bot_config::events::BotEventsConfig;
+use crate::bot::core::bot_config::healthcheck::{BotHealthcheckConfig, WebhookInfoLimits};
+use crate::bot::core::bot_config::logging::{BotLoggingConfig, BotRedactionConfig};
+use crate::bot::core::bot_config::source::ConfigSource;
+use crate::bot::core::bot_config::telemetry::BotTelemetryConfig;
+use crate::bot::core::bot_config::webapp::BotWebAppConfig;
+use crate::bot::core::bot_config::webhook::BotConfigWebHook;
+use crate::bot::core::healthcheck::registry::HealthcheckRegistry;
+use crate::bot::core::redaction::hash_telegram_id;
+use crate::MyResult;
+
+/// Inspect the configuration.
+#[derive(clap::Parser)]
+pub struct ConfigCli {
+    #[command(subcommand)]
+    pub(crate) task: TaskCli,
+}
+
+#[derive(clap::Subcommand)]
+pub enum TaskCli {
+    /// Validate the configuration and report all problems
+    Check,
+    /// Print the effective configuration, secrets are redacted
+    Show,
+    /// Print the hash of a telegram id as it appears in the logs
+    HashId {
+        telegram_id: i64,
+    },
+}
+
+impl ConfigCli {
+    pub(crate) async fn default_handling(&self) -> MyResult {
+        let source = ConfigSource::global();
+        match &self.task {
+            TaskCli::Check => {
+                let mut errors = vec![];
+                if let Err(error) = BotConfig::new() {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = BotHealthcheckConfig::new(&HealthcheckRegistry::new().names()) {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = WebhookInfoLimits::new() {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = BotLoggingConfig::new() {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = BotTelemetryConfig::new() {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = BotAdminApiConfig::new() {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = BotDashboardConfig::new() {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = BotWebAppConfig::new() {
+                    errors.push(error.to_string());
+                }
+                if let Err(error) = BotEventsConfig::new() {
+                    errors.push(error.to_string());
+                }
+                // webhook settings are only required for the bot subcommand
+                if BotConfigWebHook::is_configured() {
+                    if let Err(error) = BotConfigWebHook::new() {
+                        errors.push(error.to_string());
+                    }
+                }
+                if !errors.is_empty() {
+                    return Err(anyhow::anyhow!(errors.join("\n")));
+                }
+                println!("Configuration is valid.");
+            }
+            TaskCli::Show => {
+                match &source.file_path {
+                    Some(file_path) => println!("# Configuration file: {:?}", file_path),
+                    None => println!("# No configuration file"),
+                }
+                for line in source.describe() {
+                    println!("{}", line);
+                }
+            }
+            TaskCli::HashId { telegram_id } => {
+                let redaction_config = BotRedactionConfig::new()?;
+                let salt = redaction_config.salt
+                    .ok_or_else(|| anyhow::anyhow!("No redaction salt configured, the hashes in the logs use a random salt per run."))?;
+                println!("{}", hash_telegram_id(salt.expose(), *telegram_id));
+            }
+        }
+        Ok(())
+    }
+}
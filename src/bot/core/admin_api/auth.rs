@@ -0,0 +1,51 @@
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+
+use crate::bot::core::admin_api::{AdminApiState, ApiError};
+use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
+use crate::bot::core::util::random_start_token;
+
+/// Tells api keys apart from other credentials, e.g. in secret scanners
+const API_KEY_PREFIX: &str = "tgb_";
+
+/// Random key, shown once and stored as [`hash_api_key`].
+pub(crate) fn new_api_key() -> String {
+    format!("{}{}", API_KEY_PREFIX, random_start_token())
+}
+
+/// Keys are random, an unsalted hash is enough to look them up without storing them.
+pub(crate) fn hash_api_key(api_key: &str) -> String {
+    let digest = Sha256::digest(api_key.as_bytes());
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Name of the api key of the request, the actor in the audit log
+#[derive(Clone)]
+pub(super) struct ApiKeyName(pub String);
+
+/// Requires `Authorization: Bearer <api key>` with a key of the database, requests are logged with the name of the key.
+pub(super) async fn require_api_key(State(state): State<AdminApiState>, mut request: Request, next: Next) -> Response {
+    let api_key = request.headers()
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "));
+    let Some(api_key) = api_key else {
+        return ApiError::new(StatusCode::UNAUTHORIZED, "Missing api key").into_response();
+    };
+    match state.database_client.find_api_key(&hash_api_key(api_key)).await {
+        Ok(Some(api_key)) => {
+            tracing::info!("Admin api {} {} with key '{}'", request.method(), request.uri().path(), api_key.name);
+            request.extensions_mut().insert(ApiKeyName(api_key.name));
+            next.run(request).await
+        }
+        Ok(None) => {
+            tracing::warn!("Rejected admin api request with unknown key to {}", request.uri().path());
+            ApiError::new(StatusCode::UNAUTHORIZED, "Unknown api key").into_response()
+        }
+        Err(error) => ApiError::from(error).into_response(),
+    }
+}
@@ -0,0 +1,265 @@
+use std::net::SocketAddr;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::Json;
+use serde::Serialize;
+use teloxide::Bot;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::server::Server;
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_ADMIN_API;
+use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::events::EventBus;
+use crate::bot::core::shutdown::{shutdown_requested, spawn_background_task};
+
+pub(crate) mod auth;
+mod routes;
+
+/// Name of the security scheme in the OpenAPI description
+const SECURITY_SCHEME: &str = "api_key";
+
+#[derive(Clone)]
+pub(crate) struct AdminApiState {
+    database_client: DatabaseClient,
+    bot: Bot,
+    events: EventBus,
+}
+
+/// User management for internal tools below [`TELEGRAM_BOT_ENDPOINT_ADMIN_API`], all routes except the OpenAPI description require an api key.
+pub(crate) fn admin_api_router(database_client: DatabaseClient, bot: Bot, events: EventBus) -> axum::Router {
+    let state = AdminApiState { database_client, bot, events };
+    let api = axum::Router::new()
+        .route("/users", get(routes::list_users).post(routes::create_user))
+        .route("/users/:user_name", delete(routes::delete_user))
+        .route("/users/:user_name/start-token", post(routes::rotate_start_token))
+        .route("/users/:user_name/telegram", put(routes::link_telegram_account))
+        .route("/broadcasts", post(routes::create_broadcast))
+        // only applies to the routes above
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), auth::require_api_key))
+        .route("/openapi.json", get(openapi))
+        .with_state(state);
+    axum::Router::new().nest(TELEGRAM_BOT_ENDPOINT_ADMIN_API, api)
+}
+
+/// Serve the admin api on its own address, e.g. only reachable from the internal network.
+/// Stops accepting requests on shutdown, the shutdown waits for the running ones.
+pub(crate) async fn spawn_admin_api_listener(address: SocketAddr, router: axum::Router) -> Result<(), anyhow::Error> {
+    let tcp_listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|error| anyhow::anyhow!("Could not bind admin api listener to {}: {}", address, error))?;
+    tracing::info!("Serving admin api on http://{}{}", address, TELEGRAM_BOT_ENDPOINT_ADMIN_API);
+    spawn_background_task(async move {
+        if let Err(error) = axum::serve(tcp_listener, router).with_graceful_shutdown(shutdown_requested()).await {
+            tracing::error!("Admin api listener failed: {}", error);
+        }
+    });
+    Ok(())
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Telegram bot admin api", description = "Manage the users of the bot. Create an api key with `admin api-key create <name>`."),
+    paths(
+        routes::list_users,
+        routes::create_user,
+        routes::delete_user,
+        routes::rotate_start_token,
+        routes::link_telegram_account,
+        routes::create_broadcast,
+    ),
+    modifiers(&ApiKeySecurity),
+)]
+struct AdminApiDoc;
+
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.servers = Some(vec![Server::new(TELEGRAM_BOT_ENDPOINT_ADMIN_API)]);
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(SECURITY_SCHEME, SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+    }
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(AdminApiDoc::openapi())
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorResponse {
+    error: String,
+}
+
+/// Error of an admin api or mini app request, returned as [`ErrorResponse`].
+#[derive(Debug)]
+pub(crate) struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+}
+
+/// Status of a failed request, shared by the admin api, the mini app and the dashboard.
+pub(crate) fn status_of(error: &DatabaseError) -> StatusCode {
+    match error {
+        DatabaseError::UnknownUser(_) => StatusCode::NOT_FOUND,
+        DatabaseError::CreateError(_) => StatusCode::CONFLICT,
+        DatabaseError::Connection(_) => StatusCode::SERVICE_UNAVAILABLE,
+        DatabaseError::DeleteError(_) | DatabaseError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl From<DatabaseError> for ApiError {
+    fn from(error: DatabaseError) -> Self {
+        Self::new(status_of(&error), error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() {
+            tracing::error!("Api request failed: {}", self.message);
+        }
+        (self.status, Json(ErrorResponse { error: self.message })).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+    use reqwest::header::AUTHORIZATION;
+    use reqwest::Method;
+    use serde_json::{json, Value};
+    use teloxide::Bot;
+
+    use crate::bot::core::admin_api::auth::{hash_api_key, new_api_key};
+    use crate::bot::core::admin_api::{admin_api_router, status_of};
+    use crate::bot::core::bot_config::events::BotEventsConfig;
+    use crate::bot::core::bot_config::storage::DatabaseBackend;
+    use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_ADMIN_API;
+    use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
+    use crate::bot::core::db::client::DatabaseClient;
+    use crate::bot::core::db::test_database::TestDatabase;
+    use crate::bot::core::db::DatabaseError;
+    use crate::bot::core::events::EventBus;
+    use crate::bot::core::mock_api::{serve_local, MockApi, ALICE};
+    use crate::bot::core::repository::AuditRepository;
+    use crate::bot::core::shutdown::join_background_tasks;
+
+    /// Admin api of a database with one api key, broadcasts are sent to a mock Bot API.
+    struct TestAdminApi {
+        url: String,
+        api_key: String,
+        bot_api: Arc<MockApi>,
+        database_client: DatabaseClient,
+    }
+
+    impl TestAdminApi {
+        async fn serve(database: &TestDatabase) -> Self {
+            let database_client = DatabaseClient::load(database.connection.clone(), "testbot").await.unwrap();
+            let api_key = new_api_key();
+            database_client.create_api_key("ci", &hash_api_key(&api_key)).await.unwrap();
+            let bot_api = MockApi::new("testbot");
+            let bot = Bot::new("123456:test-token").set_api_url(bot_api.serve().await);
+            let events = EventBus::new(BotEventsConfig { urls: vec![], secret: None, max_attempts: 1, retry_delay_seconds: 1 });
+
+            let router = admin_api_router(database_client.clone(), bot, events);
+            let url = serve_local(router).await.join(TELEGRAM_BOT_ENDPOINT_ADMIN_API).unwrap().to_string();
+            Self { url, api_key, bot_api, database_client }
+        }
+
+        /// Send a request with the api key, returns the status and the json body.
+        async fn call(&self, method: Method, path: &str, body: Option<Value>) -> (u16, Value) {
+            let mut request = reqwest::Client::builder().no_proxy().build().unwrap()
+                .request(method, format!("{}{}", self.url, path))
+                .header(AUTHORIZATION, format!("Bearer {}", self.api_key));
+            if let Some(body) = body {
+                request = request.json(&body);
+            }
+            let response = request.send().await.unwrap();
+            (response.status().as_u16(), response.json().await.unwrap_or(Value::Null))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn routes_require_api_key() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let api = TestAdminApi::serve(&database).await;
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let users_url = format!("{}/users", api.url);
+
+        let missing = client.get(&users_url).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(missing.json::<Value>().await.unwrap(), json!({"error": "Missing api key"}));
+        let unknown = client.get(&users_url).header(AUTHORIZATION, format!("Bearer {}", new_api_key())).send().await.unwrap();
+        assert_eq!(unknown.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(unknown.json::<Value>().await.unwrap(), json!({"error": "Unknown api key"}));
+        let basic = client.get(&users_url).header(AUTHORIZATION, format!("Basic {}", api.api_key)).send().await.unwrap();
+        assert_eq!(basic.status(), StatusCode::UNAUTHORIZED);
+
+        assert_eq!(api.call(Method::GET, "/users", None).await, (200, json!([])));
+        // the description of the api is public
+        assert_eq!(client.get(format!("{}/openapi.json", api.url)).send().await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn manages_users() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let api = TestAdminApi::serve(&database).await;
+
+        let (status, created) = api.call(Method::POST, "/users", Some(json!({"name": "alice"}))).await;
+        assert_eq!((status, created["role"].as_str(), created["registered"].as_bool()), (201, Some("user"), Some(false)));
+        assert_eq!(api.call(Method::POST, "/users", Some(json!({"name": "alice"}))).await.0, 409);
+        assert_eq!(api.call(Method::POST, "/users", Some(json!({"name": " "}))).await.0, 422);
+
+        let (status, rotated) = api.call(Method::POST, "/users/alice/start-token", None).await;
+        assert_eq!(status, 200);
+        assert_ne!(rotated["start_token"], created["start_token"]);
+        let (status, linked) = api.call(Method::PUT, "/users/alice/telegram", Some(json!({"telegram_id": ALICE.id}))).await;
+        assert_eq!((status, linked["telegram_id"].as_i64()), (200, Some(ALICE.id)));
+        assert_eq!(api.call(Method::PUT, "/users/nobody/telegram", Some(json!({"telegram_id": 7}))).await.0, 404);
+
+        assert_eq!(api.call(Method::DELETE, "/users/alice", None).await.0, 200);
+        assert_eq!(api.call(Method::DELETE, "/users/alice", None).await.0, 404);
+        let audit_events = api.database_client.list_audit_events(10).await.unwrap().into_iter().rev()
+            .map(|event| format!("{} {}", event.actor, event.action))
+            .collect::<Vec<_>>();
+        assert_eq!(audit_events, vec!["api:ci user_created", "api:ci start_token_rotated", "api:ci telegram_linked", "api:ci user_deleted"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sends_broadcast_to_registered_users() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let api = TestAdminApi::serve(&database).await;
+        api.call(Method::POST, "/users", Some(json!({"name": "alice"}))).await;
+        api.call(Method::PUT, "/users/alice/telegram", Some(json!({"telegram_id": ALICE.id}))).await;
+
+        assert_eq!(api.call(Method::POST, "/broadcasts", Some(json!({"text": ""}))).await.0, 422);
+        let response = api.call(Method::POST, "/broadcasts", Some(json!({"text": "Hello"}))).await;
+        assert_eq!(response, (202, json!({"recipients": 1})));
+        // the broadcast continues in the background, the shutdown waits for it
+        tokio::time::timeout(Duration::from_secs(5), join_background_tasks()).await.unwrap();
+        let broadcasts = api.database_client.list_broadcasts(10).await.unwrap();
+        assert_eq!(broadcasts.iter().map(|broadcast| (broadcast.recipients, broadcast.delivered)).collect::<Vec<_>>(), vec![(1, 1)]);
+        let sent = api.bot_api.sent_messages();
+        assert_eq!(sent.iter().map(|message| (message.chat_id, message.text.as_str())).collect::<Vec<_>>(), vec![(ALICE.id, "Hello")]);
+    }
+
+    #[test]
+    fn unreachable_database_is_unavailable() {
+        assert_eq!(status_of(&DatabaseError::Connection("timed out".to_string())), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status_of(&DatabaseError::UnknownUser("alice".to_string())), StatusCode::NOT_FOUND);
+        assert_eq!(status_of(&DatabaseError::CreateError("exists".to_string())), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,151 @@
+use axum::extract::{Path, State};
+use axum::Extension;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::bot::core::admin_api::{AdminApiState, ApiError, ErrorResponse};
+use crate::bot::core::admin_api::auth::ApiKeyName;
+use crate::bot::core::audit::{audit, Actor, AuditAction};
+use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
+use crate::bot::core::db::user_representation::{UserRepresentation, UserRole};
+use crate::bot::core::events::BotEvent;
+use crate::bot::core::repository::UserRepository;
+use crate::bot::core::shutdown::spawn_background_task;
+use crate::bot::handlers::broadcast::send_broadcast;
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct UserResponse {
+    id: i64,
+    name: String,
+    role: UserRole,
+    /// Token of the start link, a user is registered by opening it
+    start_token: String,
+    start_url: String,
+    telegram_id: Option<i64>,
+    registered: bool,
+}
+
+impl From<UserRepresentation> for UserResponse {
+    fn from(user: UserRepresentation) -> Self {
+        Self {
+            id: user.id,
+            name: user.name,
+            role: user.role,
+            start_token: user.start_token,
+            start_url: user.bot_start_url,
+            registered: user.telegram_id.is_some(),
+            telegram_id: user.telegram_id,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct CreateUserRequest {
+    name: String,
+    #[serde(default = "default_role")]
+    #[schema(default = "user")]
+    role: UserRole,
+}
+
+fn default_role() -> UserRole {
+    UserRole::User
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct LinkTelegramRequest {
+    telegram_id: i64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct BroadcastRequest {
+    text: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct BroadcastResponse {
+    /// Number of registered users the broadcast is sent to
+    recipients: usize,
+}
+
+/// List all users
+#[utoipa::path(get, path = "/users", tag = "users",
+    responses((status = 200, body = [UserResponse]), (status = 401, body = ErrorResponse)),
+    security(("api_key" = [])))]
+pub(super) async fn list_users(State(state): State<AdminApiState>) -> Result<Json<Vec<UserResponse>>, ApiError> {
+    let users = state.database_client.list_users().await?;
+    Ok(Json(users.into_iter().map(UserResponse::from).collect()))
+}
+
+/// Create a user, the user registers with the returned start link
+#[utoipa::path(post, path = "/users", tag = "users", request_body = CreateUserRequest,
+    responses((status = 201, body = UserResponse), (status = 401, body = ErrorResponse), (status = 409, description = "User exists", body = ErrorResponse)),
+    security(("api_key" = [])))]
+pub(super) async fn create_user(State(state): State<AdminApiState>, Extension(ApiKeyName(key_name)): Extension<ApiKeyName>, Json(request): Json<CreateUserRequest>) -> Result<(StatusCode, Json<UserResponse>), ApiError> {
+    if request.name.trim().is_empty() {
+        return Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "The user name must not be empty"));
+    }
+    let user = state.database_client.create_user(&request.name, &request.role).await?;
+    audit(&state.database_client, Actor::ApiKey(&key_name), AuditAction::UserCreated, &user.name, Some(format!("role={}", user.role))).await;
+    Ok((StatusCode::CREATED, Json(user.into())))
+}
+
+/// Delete a user and the linked telegram account
+#[utoipa::path(delete, path = "/users/{user_name}", tag = "users",
+    params(("user_name" = String, Path)),
+    responses((status = 200, body = UserResponse), (status = 401, body = ErrorResponse), (status = 404, body = ErrorResponse)),
+    security(("api_key" = [])))]
+pub(super) async fn delete_user(State(state): State<AdminApiState>, Extension(ApiKeyName(key_name)): Extension<ApiKeyName>, Path(user_name): Path<String>) -> Result<Json<UserResponse>, ApiError> {
+    let user = state.database_client.delete_user(&user_name).await?;
+    audit(&state.database_client, Actor::ApiKey(&key_name), AuditAction::UserDeleted, &user.name, None).await;
+    state.events.publish(&state.database_client, BotEvent::UserDeleted { user_name: user.name.clone(), telegram_id: user.telegram_id }).await;
+    Ok(Json(user.into()))
+}
+
+/// Replace the start token, the previous start link stops working
+#[utoipa::path(post, path = "/users/{user_name}/start-token", tag = "users",
+    params(("user_name" = String, Path)),
+    responses((status = 200, body = UserResponse), (status = 401, body = ErrorResponse), (status = 404, body = ErrorResponse)),
+    security(("api_key" = [])))]
+pub(super) async fn rotate_start_token(State(state): State<AdminApiState>, Extension(ApiKeyName(key_name)): Extension<ApiKeyName>, Path(user_name): Path<String>) -> Result<Json<UserResponse>, ApiError> {
+    let user = state.database_client.rotate_start_token(&user_name).await?;
+    audit(&state.database_client, Actor::ApiKey(&key_name), AuditAction::StartTokenRotated, &user.name, None).await;
+    Ok(Json(user.into()))
+}
+
+/// Link a telegram account to a user without the start link
+#[utoipa::path(put, path = "/users/{user_name}/telegram", tag = "users", request_body = LinkTelegramRequest,
+    params(("user_name" = String, Path)),
+    responses((status = 200, body = UserResponse), (status = 401, body = ErrorResponse), (status = 404, body = ErrorResponse),
+        (status = 409, description = "User or telegram account is already linked", body = ErrorResponse)),
+    security(("api_key" = [])))]
+pub(super) async fn link_telegram_account(State(state): State<AdminApiState>, Extension(ApiKeyName(key_name)): Extension<ApiKeyName>, Path(user_name): Path<String>, Json(request): Json<LinkTelegramRequest>) -> Result<Json<UserResponse>, ApiError> {
+    let mut database_client = state.database_client.clone();
+    let user = database_client.link_telegram_account(&user_name, request.telegram_id).await?;
+    audit(&database_client, Actor::ApiKey(&key_name), AuditAction::TelegramLinked, &user.name, None).await;
+    state.events.publish(&database_client, BotEvent::UserRegistered { user_name: user.name.clone(), telegram_id: request.telegram_id }).await;
+    Ok(Json(user.into()))
+}
+
+/// Send a message to all registered users, the broadcast continues in the background
+#[utoipa::path(post, path = "/broadcasts", tag = "broadcasts", request_body = BroadcastRequest,
+    responses((status = 202, body = BroadcastResponse), (status = 401, body = ErrorResponse)),
+    security(("api_key" = [])))]
+pub(super) async fn create_broadcast(State(state): State<AdminApiState>, Extension(ApiKeyName(key_name)): Extension<ApiKeyName>, Json(request): Json<BroadcastRequest>) -> Result<(StatusCode, Json<BroadcastResponse>), ApiError> {
+    if request.text.trim().is_empty() {
+        return Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "The broadcast text must not be empty"));
+    }
+    let users = state.database_client.list_registered_users().await?;
+    let recipients = users.len();
+    spawn_background_task(async move {
+        let outcome = send_broadcast(&state.bot, &state.database_client, &state.events, Actor::ApiKey(&key_name), &users, &request.text).await;
+        if outcome.failed.is_empty() && outcome.not_sent.is_empty() {
+            tracing::info!("Admin api broadcast sent to {} users", outcome.delivered);
+        } else {
+            tracing::warn!("Admin api broadcast sent to {} of {} users, failed for {} and stopped by shutdown before {}",
+                outcome.delivered, users.len(), outcome.failed.len(), outcome.not_sent.len());
+        }
+    });
+    Ok((StatusCode::ACCEPTED, Json(BroadcastResponse { recipients })))
+}
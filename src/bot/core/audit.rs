@@ -0,0 +1,80 @@
+use std::fmt::{Display, Formatter};
+
+use crate::bot::core::db::model::{NewAuditEvent, NewBroadcast};
+use crate::bot::core::repository::AuditRepository;
+
+/// Who changed something, stored as text in the audit log and the broadcast history.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Actor<'a> {
+    Cli,
+    /// Name of the bot user
+    Telegram(&'a str),
+    /// Name of the api key
+    ApiKey(&'a str),
+    /// Name of the admin logged into the dashboard
+    Dashboard(&'a str),
+}
+
+impl Display for Actor<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Actor::Cli => f.write_str("cli"),
+            Actor::Telegram(name) => write!(f, "telegram:{}", name),
+            Actor::ApiKey(name) => write!(f, "api:{}", name),
+            Actor::Dashboard(name) => write!(f, "dashboard:{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AuditAction {
+    UserCreated,
+    UserDeleted,
+    StartTokenRotated,
+    TelegramLinked,
+    ApiKeyCreated,
+    ApiKeyDeleted,
+    DashboardLogin,
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AuditAction::UserCreated => "user_created",
+            AuditAction::UserDeleted => "user_deleted",
+            AuditAction::StartTokenRotated => "start_token_rotated",
+            AuditAction::TelegramLinked => "telegram_linked",
+            AuditAction::ApiKeyCreated => "api_key_created",
+            AuditAction::ApiKeyDeleted => "api_key_deleted",
+            AuditAction::DashboardLogin => "dashboard_login",
+        })
+    }
+}
+
+/// The action already happened, a failure to record it is logged but not returned.
+pub(crate) async fn audit<R: AuditRepository>(repository: &R, actor: Actor<'_>, action: AuditAction, target: &str, detail: Option<String>) {
+    let event = NewAuditEvent {
+        created_at: chrono::Utc::now().timestamp(),
+        actor: actor.to_string(),
+        action: action.to_string(),
+        target: Some(target.to_string()),
+        detail,
+    };
+    if let Err(error) = repository.record_audit_event(event).await {
+        tracing::error!("Could not record audit event {} by {}: {}", action, actor, error);
+    }
+}
+
+/// Keep the broadcast history, also for broadcasts stopped by a failed delivery or a shutdown.
+pub(crate) async fn record_broadcast<R: AuditRepository>(repository: &R, sender: Actor<'_>, message: &str, recipients: usize, delivered: usize) {
+    let broadcast = NewBroadcast {
+        created_at: chrono::Utc::now().timestamp(),
+        sender: sender.to_string(),
+        message: message.to_string(),
+        recipients: recipients as i64,
+        delivered: delivered as i64,
+    };
+    if let Err(error) = repository.record_broadcast(broadcast).await {
+        tracing::error!("Could not record broadcast by {}: {}", sender, error);
+    }
+}
@@ -0,0 +1,25 @@
+use std::net::SocketAddr;
+
+use crate::bot::core::bot_config::source::ConfigReader;
+use crate::bot::core::bot_config::{TELOXIDE_ADMIN_API_BIND_ADDRESS_KEY, TELOXIDE_ADMIN_API_ENABLED_KEY};
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotAdminApiConfig {
+    pub enabled: bool,
+    /// Separate listener for the admin api, otherwise it is served by the webhook listener
+    pub socket_address: Option<SocketAddr>,
+}
+
+impl BotAdminApiConfig {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let enabled = reader.parse::<bool>(TELOXIDE_ADMIN_API_ENABLED_KEY);
+        let socket_address = reader.parse::<SocketAddr>(TELOXIDE_ADMIN_API_BIND_ADDRESS_KEY);
+        reader.finish()?;
+
+        Ok(Self {
+            enabled: enabled.unwrap_or_default(),
+            socket_address,
+        })
+    }
+}
@@ -0,0 +1,25 @@
+use std::net::SocketAddr;
+
+use crate::bot::core::bot_config::source::ConfigReader;
+use crate::bot::core::bot_config::{TELOXIDE_DASHBOARD_BIND_ADDRESS_KEY, TELOXIDE_DASHBOARD_ENABLED_KEY};
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotDashboardConfig {
+    pub enabled: bool,
+    /// Separate listener for the dashboard, otherwise it is served by the webhook listener
+    pub socket_address: Option<SocketAddr>,
+}
+
+impl BotDashboardConfig {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let enabled = reader.parse::<bool>(TELOXIDE_DASHBOARD_ENABLED_KEY);
+        let socket_address = reader.parse::<SocketAddr>(TELOXIDE_DASHBOARD_BIND_ADDRESS_KEY);
+        reader.finish()?;
+
+        Ok(Self {
+            enabled: enabled.unwrap_or_default(),
+            socket_address,
+        })
+    }
+}
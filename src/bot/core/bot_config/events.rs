@@ -0,0 +1,61 @@
+use reqwest::Url;
+
+use crate::bot::core::bot_config::source::{ConfigReader, Secret};
+use crate::bot::core::bot_config::{TELOXIDE_EVENTS_MAX_ATTEMPTS_KEY, TELOXIDE_EVENTS_RETRY_DELAY_SECONDS_KEY, TELOXIDE_EVENTS_SECRET_KEY, TELOXIDE_EVENTS_URLS_KEY};
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotEventsConfig {
+    /// Receivers of every event, events are not recorded without
+    pub urls: Vec<Url>,
+    /// Key of the payload signature, present if there are urls
+    pub secret: Option<Secret>,
+    /// Deliveries are given up after this many failed attempts
+    pub max_attempts: i64,
+    /// Delay of the first retry, doubled for every further retry
+    pub retry_delay_seconds: i64,
+}
+
+impl BotEventsConfig {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let urls = reader.optional(TELOXIDE_EVENTS_URLS_KEY)
+            .map(|urls| urls.split(',')
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+                .collect::<Vec<_>>())
+            .unwrap_or_default();
+        let urls = urls.iter()
+            .filter_map(|url| match Url::parse(url) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Some(url),
+                Ok(_) => {
+                    reader.error(TELOXIDE_EVENTS_URLS_KEY, &format!("'{}' is no http or https url", url));
+                    None
+                }
+                Err(error) => {
+                    reader.error(TELOXIDE_EVENTS_URLS_KEY, &format!("unable to parse url '{}': {}", url, error));
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let secret = reader.optional(TELOXIDE_EVENTS_SECRET_KEY);
+        if !urls.is_empty() && secret.is_none() {
+            reader.required(TELOXIDE_EVENTS_SECRET_KEY);
+        }
+        let max_attempts = reader.parse::<i64>(TELOXIDE_EVENTS_MAX_ATTEMPTS_KEY);
+        if max_attempts.is_some_and(|max_attempts| max_attempts < 1) {
+            reader.error(TELOXIDE_EVENTS_MAX_ATTEMPTS_KEY, "at least one attempt is required");
+        }
+        let retry_delay_seconds = reader.parse::<i64>(TELOXIDE_EVENTS_RETRY_DELAY_SECONDS_KEY);
+        if retry_delay_seconds.is_some_and(|retry_delay_seconds| retry_delay_seconds < 1) {
+            reader.error(TELOXIDE_EVENTS_RETRY_DELAY_SECONDS_KEY, "retry delay must be at least one second");
+        }
+        reader.finish()?;
+
+        Ok(Self {
+            urls,
+            secret: secret.map(Secret::new),
+            max_attempts: max_attempts.unwrap_or_default(),
+            retry_delay_seconds: retry_delay_seconds.unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use crate::bot::core::bot_config::source::ConfigReader;
+use crate::bot::core::bot_config::{TELOXIDE_HEALTHCHECK_ERROR_WINDOW_MINUTES_KEY, TELOXIDE_HEALTHCHECK_MAX_PENDING_UPDATES_KEY, TELOXIDE_HEALTHCHECK_TASKS_KEY, TELOXIDE_HEALTHCHECK_TIMEOUT_SECONDS_KEY, TELOXIDE_HEALTHCHECK_WARN_WINDOW_MINUTES_KEY};
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotHealthcheckConfig {
+    /// Enabled tasks, None runs all tasks that apply to the configured update mode
+    pub tasks: Option<Vec<String>>,
+    /// Limit for each task, tasks run concurrently
+    pub timeout: Duration,
+}
+
+impl BotHealthcheckConfig {
+    /// Task names are validated against the names known to the registry.
+    pub fn new(known_tasks: &[&str]) -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let tasks = reader.optional(TELOXIDE_HEALTHCHECK_TASKS_KEY)
+            .map(|tasks| tasks.split(',')
+                .map(|task| task.trim().to_string())
+                .filter(|task| !task.is_empty())
+                .collect::<Vec<_>>());
+        for task in tasks.iter().flatten() {
+            if !known_tasks.iter().any(|known| known.eq_ignore_ascii_case(task)) {
+                reader.error(TELOXIDE_HEALTHCHECK_TASKS_KEY, &format!("unknown task '{}', known tasks are {}", task, known_tasks.join(", ")));
+            }
+        }
+        let timeout_seconds = reader.parse::<u64>(TELOXIDE_HEALTHCHECK_TIMEOUT_SECONDS_KEY);
+        if timeout_seconds == Some(0) {
+            reader.error(TELOXIDE_HEALTHCHECK_TIMEOUT_SECONDS_KEY, "timeout must be at least one second");
+        }
+        reader.finish()?;
+
+        Ok(Self {
+            tasks,
+            timeout: Duration::from_secs(timeout_seconds.unwrap_or_default()),
+        })
+    }
+
+    pub fn is_enabled(&self, task: &str) -> Option<bool> {
+        self.tasks.as_ref()
+            .map(|tasks| tasks.iter().any(|enabled| enabled.eq_ignore_ascii_case(task)))
+    }
+}
+
+/// Limits for the evaluation of getWebhookInfo.
+#[derive(Debug, Clone)]
+pub(crate) struct WebhookInfoLimits {
+    /// More pending updates fail the check
+    pub max_pending_updates: u64,
+    /// Delivery errors within this window fail the check, older ones are warnings
+    pub error_window: Duration,
+    /// Delivery errors older than this window are ignored, telegram keeps reporting the last one
+    pub warn_window: Duration,
+}
+
+impl WebhookInfoLimits {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let max_pending_updates = reader.parse::<u64>(TELOXIDE_HEALTHCHECK_MAX_PENDING_UPDATES_KEY);
+        let error_window_minutes = reader.parse::<u64>(TELOXIDE_HEALTHCHECK_ERROR_WINDOW_MINUTES_KEY).unwrap_or_default();
+        let warn_window_minutes = reader.parse::<u64>(TELOXIDE_HEALTHCHECK_WARN_WINDOW_MINUTES_KEY).unwrap_or_default();
+        if warn_window_minutes < error_window_minutes {
+            reader.error(TELOXIDE_HEALTHCHECK_WARN_WINDOW_MINUTES_KEY, "warn window must not be shorter than the error window");
+        }
+        reader.finish()?;
+
+        Ok(Self {
+            max_pending_updates: max_pending_updates.unwrap_or_default(),
+            error_window: Duration::from_secs(error_window_minutes * 60),
+            warn_window: Duration::from_secs(warn_window_minutes * 60),
+        })
+    }
+}
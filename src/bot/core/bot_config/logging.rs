@@ -0,0 +1,143 @@
+use std::env;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use tracing_subscriber::EnvFilter;
+
+use reqwest::Url;
+
+use crate::bot::core::bot_config::source::{ConfigReader, Secret, SETTINGS};
+use crate::bot::core::bot_config::{TELOXIDE_LOG_FILE_FILTER_KEY, TELOXIDE_LOG_FORMAT_KEY, TELOXIDE_LOG_MAX_FILES_KEY, TELOXIDE_LOG_MESSAGE_TEXT_KEY, TELOXIDE_LOG_REDACTION_SALT_KEY, TELOXIDE_LOG_ROTATION_KEY, TELOXIDE_LOG_STDOUT_FILTER_KEY};
+
+/// Used for both outputs if neither the setting nor `RUST_LOG` is given, hyper is very verbose on debug
+pub(crate) const DEFAULT_LOG_FILTER: &str = "debug,hyper=off";
+const RUST_LOG_KEY: &str = "RUST_LOG";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LogFormat {
+    Compact,
+    Pretty,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "compact" => Ok(LogFormat::Compact),
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow!("expected compact, pretty or json")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl FromStr for LogRotation {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            "never" => Ok(LogRotation::Never),
+            _ => Err(anyhow!("expected hourly, daily or never")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotLoggingConfig {
+    pub format: LogFormat,
+    /// `EnvFilter` directives, e.g. `info,rust_telegram_alias_bot=debug`
+    pub stdout_filter: String,
+    pub file_filter: String,
+    pub rotation: LogRotation,
+    /// Number of rotated log files to keep
+    pub max_files: usize,
+    pub redaction: BotRedactionConfig,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotRedactionConfig {
+    /// Telegram ids are logged as hash with this salt, without it hashes differ between restarts
+    pub salt: Option<Secret>,
+    /// Log the text of user messages, only for debugging
+    pub log_message_text: bool,
+    /// Values of all secret settings, masked wherever they appear in log lines and exported spans
+    pub secrets: Vec<Secret>,
+}
+
+impl BotLoggingConfig {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let format = reader.parse::<LogFormat>(TELOXIDE_LOG_FORMAT_KEY);
+        let stdout_filter = Self::read_filter(&mut reader, TELOXIDE_LOG_STDOUT_FILTER_KEY);
+        let file_filter = Self::read_filter(&mut reader, TELOXIDE_LOG_FILE_FILTER_KEY);
+        let rotation = reader.parse::<LogRotation>(TELOXIDE_LOG_ROTATION_KEY);
+        let max_files = reader.parse::<usize>(TELOXIDE_LOG_MAX_FILES_KEY);
+        if max_files == Some(0) {
+            reader.error(TELOXIDE_LOG_MAX_FILES_KEY, "at least one log file must be kept");
+        }
+        let redaction = BotRedactionConfig::read(&mut reader);
+        reader.finish()?;
+
+        Ok(Self {
+            format: format.unwrap_or(LogFormat::Compact),
+            stdout_filter,
+            file_filter,
+            rotation: rotation.unwrap_or(LogRotation::Daily),
+            max_files: max_files.unwrap_or_default(),
+            redaction,
+        })
+    }
+
+    /// The setting takes precedence over `RUST_LOG`, which applies to both outputs.
+    fn read_filter(reader: &mut ConfigReader, env_key: &str) -> String {
+        let filter = reader.optional(env_key)
+            .or_else(|| env::var(RUST_LOG_KEY).ok())
+            .unwrap_or_else(|| DEFAULT_LOG_FILTER.to_string());
+        if let Err(error) = EnvFilter::try_new(&filter) {
+            reader.error(env_key, &format!("invalid filter '{}': {}", filter, error));
+        }
+        filter
+    }
+}
+
+impl BotRedactionConfig {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let redaction_config = Self::read(&mut reader);
+        reader.finish()?;
+        Ok(redaction_config)
+    }
+
+    fn read(reader: &mut ConfigReader) -> Self {
+        let salt = reader.optional(TELOXIDE_LOG_REDACTION_SALT_KEY).map(Secret::new);
+        let log_message_text = reader.parse::<bool>(TELOXIDE_LOG_MESSAGE_TEXT_KEY);
+        let mut secrets = vec![];
+        for setting in SETTINGS.iter().filter(|setting| setting.secret) {
+            let Some(value) = reader.optional(setting.env_key) else {
+                continue;
+            };
+            // the password of a database url also shows up on its own, e.g. in connection errors
+            if let Some(password) = Url::parse(&value).ok().and_then(|url| url.password().map(str::to_string)) {
+                secrets.push(Secret::new(password));
+            }
+            secrets.push(Secret::new(value));
+        }
+
+        Self {
+            salt,
+            log_message_text: log_message_text.unwrap_or_default(),
+            secrets,
+        }
+    }
+}
@@ -1,37 +1,146 @@
-use std::env;
+use std::net::SocketAddr;
+use std::time::Duration;
 
+use reqwest::Url;
 use serde::Deserialize;
 
+use crate::bot::core::bot_config::source::ConfigReader;
 use crate::bot::core::bot_config::storage::BotStorageConfig;
 
+pub(crate) mod admin_api;
+pub(crate) mod dashboard;
+pub(crate) mod events;
+pub(crate) mod healthcheck;
+pub(crate) mod logging;
+pub(crate) mod source;
 pub(crate) mod storage;
+pub(crate) mod telemetry;
+pub(crate) mod webapp;
 pub(crate) mod webhook;
 
 const TELOXIDE_TOKEN_KEY: &str = "TELOXIDE_TOKEN";
+const TELOXIDE_API_URL_KEY: &str = "TELOXIDE_API_URL";
 const TELOXIDE_LOG_DIR_KEY: &str = "TELOXIDE_LOG_DIR";
+const TELOXIDE_LOG_FORMAT_KEY: &str = "TELOXIDE_LOG_FORMAT";
+const TELOXIDE_LOG_STDOUT_FILTER_KEY: &str = "TELOXIDE_LOG_STDOUT_FILTER";
+const TELOXIDE_LOG_FILE_FILTER_KEY: &str = "TELOXIDE_LOG_FILE_FILTER";
+const TELOXIDE_LOG_ROTATION_KEY: &str = "TELOXIDE_LOG_ROTATION";
+const TELOXIDE_LOG_MAX_FILES_KEY: &str = "TELOXIDE_LOG_MAX_FILES";
+const TELOXIDE_LOG_REDACTION_SALT_KEY: &str = "TELOXIDE_LOG_REDACTION_SALT";
+const TELOXIDE_LOG_MESSAGE_TEXT_KEY: &str = "TELOXIDE_LOG_MESSAGE_TEXT";
 const TELOXIDE_DATA_DIR_KEY: &str = "TELOXIDE_DATA_DIR";
 const TELOXIDE_BIND_PORT_KEY: &str = "TELOXIDE_BIND_PORT";
 const TELOXIDE_BIND_ADDRESS_KEY: &str = "TELOXIDE_BIND_ADDRESS";
 const TELOXIDE_PUBLIC_URL_KEY: &str = "TELOXIDE_PUBLIC_URL";
+const TELOXIDE_METRICS_BIND_ADDRESS_KEY: &str = "TELOXIDE_METRICS_BIND_ADDRESS";
+const TELOXIDE_WEBHOOK_SECRET_KEY: &str = "TELOXIDE_WEBHOOK_SECRET";
+const DATABASE_URL_KEY: &str = "DATABASE_URL";
+const TELOXIDE_BACKUP_DIR_KEY: &str = "TELOXIDE_BACKUP_DIR";
+const TELOXIDE_BACKUP_INTERVAL_HOURS_KEY: &str = "TELOXIDE_BACKUP_INTERVAL_HOURS";
+const TELOXIDE_BACKUP_RETENTION_KEY: &str = "TELOXIDE_BACKUP_RETENTION";
+const TELOXIDE_HEALTHCHECK_TASKS_KEY: &str = "TELOXIDE_HEALTHCHECK_TASKS";
+const TELOXIDE_HEALTHCHECK_TIMEOUT_SECONDS_KEY: &str = "TELOXIDE_HEALTHCHECK_TIMEOUT_SECONDS";
+const TELOXIDE_HEALTHCHECK_MAX_PENDING_UPDATES_KEY: &str = "TELOXIDE_HEALTHCHECK_MAX_PENDING_UPDATES";
+const TELOXIDE_HEALTHCHECK_ERROR_WINDOW_MINUTES_KEY: &str = "TELOXIDE_HEALTHCHECK_ERROR_WINDOW_MINUTES";
+const TELOXIDE_HEALTHCHECK_WARN_WINDOW_MINUTES_KEY: &str = "TELOXIDE_HEALTHCHECK_WARN_WINDOW_MINUTES";
+const TELOXIDE_OTLP_ENDPOINT_KEY: &str = "TELOXIDE_OTLP_ENDPOINT";
+const TELOXIDE_OTLP_SERVICE_NAME_KEY: &str = "TELOXIDE_OTLP_SERVICE_NAME";
+const TELOXIDE_SHUTDOWN_TIMEOUT_SECONDS_KEY: &str = "TELOXIDE_SHUTDOWN_TIMEOUT_SECONDS";
+const TELOXIDE_ADMIN_API_ENABLED_KEY: &str = "TELOXIDE_ADMIN_API_ENABLED";
+const TELOXIDE_ADMIN_API_BIND_ADDRESS_KEY: &str = "TELOXIDE_ADMIN_API_BIND_ADDRESS";
+const TELOXIDE_DASHBOARD_ENABLED_KEY: &str = "TELOXIDE_DASHBOARD_ENABLED";
+const TELOXIDE_DASHBOARD_BIND_ADDRESS_KEY: &str = "TELOXIDE_DASHBOARD_BIND_ADDRESS";
+const TELOXIDE_WEBAPP_ENABLED_KEY: &str = "TELOXIDE_WEBAPP_ENABLED";
+const TELOXIDE_WEBAPP_MENU_BUTTON_TEXT_KEY: &str = "TELOXIDE_WEBAPP_MENU_BUTTON_TEXT";
+const TELOXIDE_WEBAPP_ASSETS_DIR_KEY: &str = "TELOXIDE_WEBAPP_ASSETS_DIR";
+const TELOXIDE_EVENTS_URLS_KEY: &str = "TELOXIDE_EVENTS_URLS";
+const TELOXIDE_EVENTS_SECRET_KEY: &str = "TELOXIDE_EVENTS_SECRET";
+const TELOXIDE_EVENTS_MAX_ATTEMPTS_KEY: &str = "TELOXIDE_EVENTS_MAX_ATTEMPTS";
+const TELOXIDE_EVENTS_RETRY_DELAY_SECONDS_KEY: &str = "TELOXIDE_EVENTS_RETRY_DELAY_SECONDS";
 pub const TELOXIDE_BOT_NAME_KEY: &str = "TELOXIDE_BOT_NAME";
 pub const TELEGRAM_BOT_ENDPOINT_BOT: &str = "/bot";
 pub const TELEGRAM_BOT_ENDPOINT_HEALTHCHECK: &str = "/healthcheck";
+pub const TELEGRAM_BOT_ENDPOINT_READY: &str = "/healthcheck/ready";
+pub const TELEGRAM_BOT_ENDPOINT_LIVE: &str = "/healthcheck/live";
+pub const TELEGRAM_BOT_ENDPOINT_METRICS: &str = "/metrics";
+pub const TELEGRAM_BOT_ENDPOINT_ADMIN_API: &str = "/api/admin";
+pub const TELEGRAM_BOT_ENDPOINT_DASHBOARD: &str = "/dashboard";
+pub const TELEGRAM_BOT_ENDPOINT_WEBAPP: &str = "/webapp";
 const DATABASE_FILE_NAME: &str = "db.sqlite";
 
 #[derive(Deserialize, Debug, Clone)]
 pub(crate) struct BotConfig {
     pub bot_token: String,
+    /// Telegram username of the bot, used for start links
+    pub bot_name: String,
+    /// Telegram Bot API server, a self-hosted `telegram-bot-api` or a mock
+    pub api_url: String,
+    /// Separate listener for `/metrics`, e.g. in polling mode
+    pub metrics_socket_address: Option<SocketAddr>,
+    /// Time for running handlers to finish after a shutdown signal
+    pub shutdown_timeout: Duration,
     pub storage: BotStorageConfig,
 }
 
 impl BotConfig {
     pub fn new() -> Result<Self, anyhow::Error> {
-        let bot_token = env::var(TELOXIDE_TOKEN_KEY).expect("Could not find telegram bot token. Check env var TELOXIDE_TOKEN.");
-        let bot_storage_config = BotStorageConfig::new()?;
+        let mut reader = ConfigReader::new();
+        let bot_token = reader.required(TELOXIDE_TOKEN_KEY);
+        let bot_name = reader.required(TELOXIDE_BOT_NAME_KEY);
+        let api_url = reader.required(TELOXIDE_API_URL_KEY);
+        if let Err(error) = Url::parse(&api_url) {
+            reader.error(TELOXIDE_API_URL_KEY, &error.to_string());
+        }
+        let metrics_socket_address = reader.parse::<SocketAddr>(TELOXIDE_METRICS_BIND_ADDRESS_KEY);
+        let shutdown_timeout_seconds = reader.parse::<u64>(TELOXIDE_SHUTDOWN_TIMEOUT_SECONDS_KEY);
+        if shutdown_timeout_seconds == Some(0) {
+            reader.error(TELOXIDE_SHUTDOWN_TIMEOUT_SECONDS_KEY, "timeout must be at least one second");
+        }
+        let storage = BotStorageConfig::read(&mut reader);
+        reader.finish()?;
 
         Ok(Self {
             bot_token,
-            storage: bot_storage_config,
+            bot_name,
+            api_url,
+            metrics_socket_address,
+            shutdown_timeout: Duration::from_secs(shutdown_timeout_seconds.unwrap_or_default()),
+            storage,
         })
     }
+
+    /// Validated when loading the configuration.
+    pub fn telegram_api_url(&self) -> Url {
+        Url::parse(&self.api_url).expect("Telegram api url was validated on load.")
+    }
+
+    /// Url of a Bot API method, below the path of the api url, e.g. of a reverse proxy.
+    pub fn telegram_method_url(&self, method: &str) -> Result<Url, anyhow::Error> {
+        method_url(self.telegram_api_url(), &self.bot_token, method)
+    }
+}
+
+fn method_url(mut api_url: Url, bot_token: &str, method: &str) -> Result<Url, anyhow::Error> {
+    // a relative join replaces the last path segment unless the path ends with a slash
+    if !api_url.path().ends_with('/') {
+        api_url.set_path(&format!("{}/", api_url.path()));
+    }
+    // without "./" the colon of the token would make "bot<id>:" the scheme of an absolute url
+    Ok(api_url.join(&format!("./bot{}/{}", bot_token, method))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use crate::bot::core::bot_config::method_url;
+
+    #[test]
+    fn method_url_keeps_path_of_api_url() {
+        let method_url = |api_url: &str| method_url(Url::parse(api_url).unwrap(), "123:abc", "getMe").unwrap().to_string();
+        assert_eq!(method_url("https://api.telegram.org"), "https://api.telegram.org/bot123:abc/getMe");
+        assert_eq!(method_url("https://api.telegram.org/"), "https://api.telegram.org/bot123:abc/getMe");
+        assert_eq!(method_url("https://proxy.example/telegram"), "https://proxy.example/telegram/bot123:abc/getMe");
+        assert_eq!(method_url("https://proxy.example/telegram/"), "https://proxy.example/telegram/bot123:abc/getMe");
+    }
 }
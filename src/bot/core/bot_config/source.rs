@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+
+use crate::bot::core::bot_config::{DATABASE_URL_KEY, TELOXIDE_ADMIN_API_BIND_ADDRESS_KEY, TELOXIDE_ADMIN_API_ENABLED_KEY, TELOXIDE_API_URL_KEY, TELOXIDE_BACKUP_DIR_KEY, TELOXIDE_BACKUP_INTERVAL_HOURS_KEY, TELOXIDE_BACKUP_RETENTION_KEY, TELOXIDE_DASHBOARD_BIND_ADDRESS_KEY, TELOXIDE_DASHBOARD_ENABLED_KEY, TELOXIDE_EVENTS_MAX_ATTEMPTS_KEY, TELOXIDE_EVENTS_RETRY_DELAY_SECONDS_KEY, TELOXIDE_EVENTS_SECRET_KEY, TELOXIDE_EVENTS_URLS_KEY, TELOXIDE_BIND_ADDRESS_KEY, TELOXIDE_BIND_PORT_KEY, TELOXIDE_BOT_NAME_KEY, TELOXIDE_DATA_DIR_KEY, TELOXIDE_HEALTHCHECK_ERROR_WINDOW_MINUTES_KEY, TELOXIDE_HEALTHCHECK_MAX_PENDING_UPDATES_KEY, TELOXIDE_HEALTHCHECK_TASKS_KEY, TELOXIDE_HEALTHCHECK_TIMEOUT_SECONDS_KEY, TELOXIDE_HEALTHCHECK_WARN_WINDOW_MINUTES_KEY, TELOXIDE_LOG_DIR_KEY, TELOXIDE_LOG_FILE_FILTER_KEY, TELOXIDE_LOG_FORMAT_KEY, TELOXIDE_LOG_MAX_FILES_KEY, TELOXIDE_LOG_MESSAGE_TEXT_KEY, TELOXIDE_LOG_REDACTION_SALT_KEY, TELOXIDE_LOG_ROTATION_KEY, TELOXIDE_LOG_STDOUT_FILTER_KEY, TELOXIDE_METRICS_BIND_ADDRESS_KEY, TELOXIDE_OTLP_ENDPOINT_KEY, TELOXIDE_OTLP_SERVICE_NAME_KEY, TELOXIDE_PUBLIC_URL_KEY, TELOXIDE_SHUTDOWN_TIMEOUT_SECONDS_KEY, TELOXIDE_TOKEN_KEY, TELOXIDE_WEBAPP_ASSETS_DIR_KEY, TELOXIDE_WEBAPP_ENABLED_KEY, TELOXIDE_WEBAPP_MENU_BUTTON_TEXT_KEY, TELOXIDE_WEBHOOK_SECRET_KEY};
+
+const REDACTED: &str = "<redacted>";
+/// `TELOXIDE_TOKEN_FILE` points to a file containing the value of `TELOXIDE_TOKEN`
+const SECRET_FILE_SUFFIX: &str = "_FILE";
+/// Set by systemd for services with `LoadCredential=`
+const CREDENTIALS_DIRECTORY_KEY: &str = "CREDENTIALS_DIRECTORY";
+const DOCKER_SECRETS_DIRECTORY: &str = "/run/secrets";
+
+/// A setting that may be given in the configuration file, the environment or on the command line.
+#[derive(Debug)]
+pub(crate) struct Setting {
+    /// Key in the configuration file and for `--set`, `<section>.<name>`
+    pub file_key: &'static str,
+    pub env_key: &'static str,
+    pub default: Option<&'static str>,
+    /// Never printed, may be read from a file
+    pub secret: bool,
+}
+
+const fn setting(file_key: &'static str, env_key: &'static str, default: Option<&'static str>, secret: bool) -> Setting {
+    Setting { file_key, env_key, default, secret }
+}
+
+pub(crate) const SETTINGS: &[Setting] = &[
+    setting("telegram.token", TELOXIDE_TOKEN_KEY, None, true),
+    setting("telegram.bot_name", TELOXIDE_BOT_NAME_KEY, None, false),
+    setting("telegram.api_url", TELOXIDE_API_URL_KEY, Some("https://api.telegram.org/"), false),
+    setting("webhook.public_url", TELOXIDE_PUBLIC_URL_KEY, None, false),
+    setting("webhook.bind_address", TELOXIDE_BIND_ADDRESS_KEY, Some("0.0.0.0"), false),
+    setting("webhook.bind_port", TELOXIDE_BIND_PORT_KEY, None, false),
+    setting("webhook.secret_token", TELOXIDE_WEBHOOK_SECRET_KEY, None, true),
+    setting("metrics.bind_address", TELOXIDE_METRICS_BIND_ADDRESS_KEY, None, false),
+    setting("shutdown.timeout_seconds", TELOXIDE_SHUTDOWN_TIMEOUT_SECONDS_KEY, Some("30"), false),
+    setting("admin_api.enabled", TELOXIDE_ADMIN_API_ENABLED_KEY, Some("false"), false),
+    setting("admin_api.bind_address", TELOXIDE_ADMIN_API_BIND_ADDRESS_KEY, None, false),
+    setting("dashboard.enabled", TELOXIDE_DASHBOARD_ENABLED_KEY, Some("false"), false),
+    setting("dashboard.bind_address", TELOXIDE_DASHBOARD_BIND_ADDRESS_KEY, None, false),
+    setting("webapp.enabled", TELOXIDE_WEBAPP_ENABLED_KEY, Some("false"), false),
+    setting("webapp.menu_button_text", TELOXIDE_WEBAPP_MENU_BUTTON_TEXT_KEY, Some("Open app"), false),
+    setting("webapp.assets_dir", TELOXIDE_WEBAPP_ASSETS_DIR_KEY, None, false),
+    setting("events.urls", TELOXIDE_EVENTS_URLS_KEY, None, false),
+    setting("events.secret", TELOXIDE_EVENTS_SECRET_KEY, None, true),
+    setting("events.max_attempts", TELOXIDE_EVENTS_MAX_ATTEMPTS_KEY, Some("10"), false),
+    setting("events.retry_delay_seconds", TELOXIDE_EVENTS_RETRY_DELAY_SECONDS_KEY, Some("30"), false),
+    setting("telemetry.otlp_endpoint", TELOXIDE_OTLP_ENDPOINT_KEY, None, false),
+    setting("telemetry.service_name", TELOXIDE_OTLP_SERVICE_NAME_KEY, Some("telegrambot"), false),
+    setting("storage.log_dir", TELOXIDE_LOG_DIR_KEY, Some("/var/log/telegrambot/"), false),
+    setting("storage.data_dir", TELOXIDE_DATA_DIR_KEY, Some("/var/lib/telegrambot/"), false),
+    // may contain the database password
+    setting("storage.database_url", DATABASE_URL_KEY, None, true),
+    setting("logging.format", TELOXIDE_LOG_FORMAT_KEY, Some("compact"), false),
+    // RUST_LOG is used if not set
+    setting("logging.stdout_filter", TELOXIDE_LOG_STDOUT_FILTER_KEY, None, false),
+    setting("logging.file_filter", TELOXIDE_LOG_FILE_FILTER_KEY, None, false),
+    setting("logging.rotation", TELOXIDE_LOG_ROTATION_KEY, Some("daily"), false),
+    setting("logging.max_files", TELOXIDE_LOG_MAX_FILES_KEY, Some("14"), false),
+    setting("logging.redaction_salt", TELOXIDE_LOG_REDACTION_SALT_KEY, None, true),
+    setting("logging.log_message_text", TELOXIDE_LOG_MESSAGE_TEXT_KEY, Some("false"), false),
+    setting("backup.dir", TELOXIDE_BACKUP_DIR_KEY, None, false),
+    setting("backup.interval_hours", TELOXIDE_BACKUP_INTERVAL_HOURS_KEY, Some("24"), false),
+    setting("backup.retention", TELOXIDE_BACKUP_RETENTION_KEY, Some("7"), false),
+    setting("healthcheck.tasks", TELOXIDE_HEALTHCHECK_TASKS_KEY, None, false),
+    setting("healthcheck.timeout_seconds", TELOXIDE_HEALTHCHECK_TIMEOUT_SECONDS_KEY, Some("10"), false),
+    setting("healthcheck.max_pending_updates", TELOXIDE_HEALTHCHECK_MAX_PENDING_UPDATES_KEY, Some("100"), false),
+    setting("healthcheck.error_window_minutes", TELOXIDE_HEALTHCHECK_ERROR_WINDOW_MINUTES_KEY, Some("10"), false),
+    setting("healthcheck.warn_window_minutes", TELOXIDE_HEALTHCHECK_WARN_WINDOW_MINUTES_KEY, Some("1440"), false),
+];
+
+fn find_setting(env_key: &str) -> &'static Setting {
+    SETTINGS.iter()
+        .find(|setting| setting.env_key.eq(env_key))
+        .unwrap_or_else(|| panic!("Unknown setting {}", env_key))
+}
+
+/// Secret setting that is redacted in debug output.
+#[derive(Clone, PartialEq)]
+pub(crate) struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Where the effective value of a setting comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Origin {
+    Default,
+    File,
+    SecretFile,
+    Environment,
+    CommandLine,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Default => f.write_str("default"),
+            Origin::File => f.write_str("config file"),
+            Origin::SecretFile => f.write_str("secret file"),
+            Origin::Environment => f.write_str("environment"),
+            Origin::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// Layered settings, command line overrides environment overrides secret files overrides configuration file overrides defaults.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigSource {
+    pub file_path: Option<PathBuf>,
+    /// Values by environment key
+    file: HashMap<&'static str, String>,
+    secret_files: HashMap<&'static str, String>,
+    command_line: HashMap<&'static str, String>,
+}
+
+static CONFIG_SOURCE: OnceLock<ConfigSource> = OnceLock::new();
+
+impl ConfigSource {
+    /// Read the configuration file and `--set KEY=VALUE` overrides, all problems are reported at once.
+    pub fn load(file_path: Option<&Path>, overrides: &[String]) -> Result<Self, anyhow::Error> {
+        let mut errors = vec![];
+        let mut file = HashMap::new();
+        if let Some(file_path) = file_path {
+            match fs::read_to_string(file_path) {
+                Ok(content) => {
+                    file = Self::parse_file(&content, &mut errors);
+                }
+                Err(error) => {
+                    errors.push(format!("Could not read configuration file {:?}: {}", file_path, error));
+                }
+            }
+        }
+
+        let secret_files = Self::read_secret_files(&mut errors);
+
+        let mut command_line = HashMap::new();
+        for item in overrides {
+            match item.split_once('=') {
+                Some((key, value)) => {
+                    match SETTINGS.iter().find(|setting| setting.file_key.eq(key.trim())) {
+                        Some(setting) => {
+                            command_line.insert(setting.env_key, value.to_string());
+                        }
+                        None => {
+                            errors.push(format!("Unknown setting '{}' in --set {}", key, item));
+                        }
+                    }
+                }
+                None => {
+                    errors.push(format!("Expected --set KEY=VALUE, got '{}'", item));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow!("Invalid configuration:\n  - {}", errors.join("\n  - ")));
+        }
+        Ok(Self {
+            file_path: file_path.map(Path::to_path_buf),
+            file,
+            secret_files,
+            command_line,
+        })
+    }
+
+    /// Secrets from `<KEY>_FILE`, systemd credentials or docker secrets, named like the lowercase environment key.
+    fn read_secret_files(errors: &mut Vec<String>) -> HashMap<&'static str, String> {
+        let mut values = HashMap::new();
+        for setting in SETTINGS.iter().filter(|setting| setting.secret) {
+            let Some(path) = Self::secret_file_path(setting, errors) else {
+                continue;
+            };
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    // files usually end with a newline
+                    let value = content.trim_end_matches(['\r', '\n']).to_string();
+                    if value.is_empty() {
+                        errors.push(format!("Secret file {:?} for setting {} is empty", path, setting.file_key));
+                    } else {
+                        values.insert(setting.env_key, value);
+                    }
+                }
+                Err(error) => {
+                    errors.push(format!("Could not read secret file {:?} for setting {}: {}", path, setting.file_key, error));
+                }
+            }
+        }
+        values
+    }
+
+    fn secret_file_path(setting: &Setting, errors: &mut Vec<String>) -> Option<PathBuf> {
+        let file_key = format!("{}{}", setting.env_key, SECRET_FILE_SUFFIX);
+        if let Some(path) = env::var(&file_key).ok().filter(|path| !path.is_empty()) {
+            if env::var(setting.env_key).is_ok_and(|value| !value.is_empty()) {
+                errors.push(format!("Both {} and {} are set, remove one of them", setting.env_key, file_key));
+            }
+            return Some(PathBuf::from(path));
+        }
+
+        let credential_name = setting.env_key.to_lowercase();
+        let credentials_directory = env::var(CREDENTIALS_DIRECTORY_KEY).ok().map(PathBuf::from);
+        credentials_directory.into_iter()
+            .chain([PathBuf::from(DOCKER_SECRETS_DIRECTORY)])
+            .map(|directory| directory.join(&credential_name))
+            .find(|path| path.is_file())
+    }
+
+    fn parse_file(content: &str, errors: &mut Vec<String>) -> HashMap<&'static str, String> {
+        let mut values = HashMap::new();
+        let table = match content.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(error) => {
+                errors.push(format!("Could not parse configuration file: {}", error));
+                return values;
+            }
+        };
+
+        for (section_name, section) in table {
+            let Some(section) = section.as_table() else {
+                errors.push(format!("Expected a [{}] section in configuration file", section_name));
+                continue;
+            };
+            for (name, value) in section {
+                let file_key = format!("{}.{}", section_name, name);
+                let Some(setting) = SETTINGS.iter().find(|setting| setting.file_key.eq(&file_key)) else {
+                    errors.push(format!("Unknown setting '{}' in configuration file", file_key));
+                    continue;
+                };
+                let value = match value {
+                    toml::Value::String(value) => value.clone(),
+                    toml::Value::Integer(value) => value.to_string(),
+                    toml::Value::Boolean(value) => value.to_string(),
+                    _ => {
+                        errors.push(format!("Setting '{}' in configuration file must be a string, integer or boolean", file_key));
+                        continue;
+                    }
+                };
+                values.insert(setting.env_key, value);
+            }
+        }
+        values
+    }
+
+    /// Make the settings available to all configuration structs, call once on start.
+    pub fn install(self) {
+        if CONFIG_SOURCE.set(self).is_err() {
+            tracing::warn!("Configuration source was already installed, ignoring.");
+        }
+    }
+
+    /// Installed settings, only the environment and defaults if nothing was installed.
+    pub fn global() -> &'static ConfigSource {
+        CONFIG_SOURCE.get_or_init(ConfigSource::default)
+    }
+
+    /// Effective value of a setting by its environment key.
+    pub fn get(&self, env_key: &str) -> Option<(String, Origin)> {
+        if let Some(value) = self.command_line.get(env_key) {
+            return Some((value.clone(), Origin::CommandLine));
+        }
+        // empty variables, e.g. `TELOXIDE_BACKUP_DIR=` in `.env`, do not override the file
+        if let Some(value) = env::var(env_key).ok().filter(|value| !value.is_empty()) {
+            return Some((value, Origin::Environment));
+        }
+        if let Some(value) = self.secret_files.get(env_key) {
+            return Some((value.clone(), Origin::SecretFile));
+        }
+        if let Some(value) = self.file.get(env_key) {
+            return Some((value.clone(), Origin::File));
+        }
+        find_setting(env_key).default
+            .map(|value| (value.to_string(), Origin::Default))
+    }
+
+    /// Effective settings for display, secrets are redacted.
+    pub fn describe(&self) -> Vec<String> {
+        SETTINGS.iter()
+            .map(|setting| {
+                match self.get(setting.env_key) {
+                    Some((value, origin)) => {
+                        let value = if setting.secret { REDACTED.to_string() } else { value };
+                        format!("{} = {:?}  # {} {}", setting.file_key, value, origin, setting.env_key)
+                    }
+                    None => {
+                        format!("# {} is not set  # {}", setting.file_key, setting.env_key)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads settings and collects all validation errors instead of failing on the first one.
+pub(crate) struct ConfigReader {
+    source: &'static ConfigSource,
+    errors: Vec<String>,
+}
+
+impl ConfigReader {
+    pub fn new() -> Self {
+        Self {
+            source: ConfigSource::global(),
+            errors: vec![],
+        }
+    }
+
+    fn describe_key(&self, env_key: &str) -> String {
+        let setting = find_setting(env_key);
+        match self.source.get(env_key) {
+            Some((_value, origin)) => format!("{} ({} from {})", setting.file_key, env_key, origin),
+            None => format!("{} ({})", setting.file_key, env_key),
+        }
+    }
+
+    pub fn optional(&self, env_key: &str) -> Option<String> {
+        self.source.get(env_key).map(|(value, _origin)| value)
+    }
+
+    /// Returns an empty string and records an error if the setting is missing.
+    pub fn required(&mut self, env_key: &str) -> String {
+        match self.optional(env_key) {
+            Some(value) => value,
+            None => {
+                self.errors.push(format!("Missing setting {}", self.describe_key(env_key)));
+                String::new()
+            }
+        }
+    }
+
+    /// Parsed value of the setting, records an error if it is invalid.
+    pub fn parse<T>(&mut self, env_key: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.optional(env_key)?;
+        match value.parse::<T>() {
+            Ok(parsed) => Some(parsed),
+            Err(error) => {
+                self.error(env_key, &format!("could not parse '{}': {}", value, error));
+                None
+            }
+        }
+    }
+
+    pub fn error(&mut self, env_key: &str, message: &str) {
+        self.errors.push(format!("Invalid setting {}: {}", self.describe_key(env_key), message));
+    }
+
+    pub fn finish(self) -> Result<(), anyhow::Error> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid configuration:\n  - {}", self.errors.join("\n  - ")))
+        }
+    }
+}
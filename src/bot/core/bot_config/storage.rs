@@ -1,31 +1,86 @@
-use std::{env, fs};
+use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use serde::Deserialize;
 
-use crate::bot::core::bot_config::{DATABASE_FILE_NAME, TELOXIDE_DATA_DIR_KEY, TELOXIDE_LOG_DIR_KEY};
+use crate::bot::core::bot_config::source::ConfigReader;
+use crate::bot::core::bot_config::{DATABASE_FILE_NAME, DATABASE_URL_KEY, TELOXIDE_BACKUP_DIR_KEY, TELOXIDE_BACKUP_INTERVAL_HOURS_KEY, TELOXIDE_BACKUP_RETENTION_KEY, TELOXIDE_DATA_DIR_KEY, TELOXIDE_LOG_DIR_KEY};
 
 #[derive(Deserialize, Debug, Clone)]
 pub(crate) struct BotStorageConfig {
     pub log_directory: String,
     pub data_directory: String,
+    /// External database url, the sqlite database in the data directory is used if not set
+    pub external_database_url: Option<String>,
+    /// Periodic database backup, only enabled if a backup directory is configured
+    pub backup: Option<BotBackupConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DatabaseBackend {
+    Sqlite,
+    Postgres,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct BotBackupConfig {
+    pub directory: String,
+    pub interval: Duration,
+    /// Number of backup files to keep
+    pub retention: usize,
 }
 
 impl BotStorageConfig {
     pub fn new() -> Result<Self, anyhow::Error> {
-        let log_directory = env::var(TELOXIDE_LOG_DIR_KEY).unwrap_or("/var/log/telegrambot/".to_string());
-        let data_directory = env::var(TELOXIDE_DATA_DIR_KEY).unwrap_or("/var/lib/telegrambot/".to_string());
-        Self::check_directory_write_access(&log_directory, TELOXIDE_LOG_DIR_KEY)?;
-        Self::check_directory_write_access(&log_directory, TELOXIDE_DATA_DIR_KEY)?;
+        let mut reader = ConfigReader::new();
+        let storage_config = Self::read(&mut reader);
+        reader.finish()?;
+        Ok(storage_config)
+    }
 
-        Ok(Self {
+    pub(crate) fn read(reader: &mut ConfigReader) -> Self {
+        let log_directory = reader.required(TELOXIDE_LOG_DIR_KEY);
+        let data_directory = reader.required(TELOXIDE_DATA_DIR_KEY);
+        Self::check_directory_write_access(reader, &log_directory, TELOXIDE_LOG_DIR_KEY);
+        Self::check_directory_write_access(reader, &data_directory, TELOXIDE_DATA_DIR_KEY);
+        let backup = BotBackupConfig::read(reader);
+        let external_database_url = Self::read_external_database_url(reader);
+
+        Self {
             log_directory,
             data_directory,
-        })
+            external_database_url,
+            backup,
+        }
+    }
 
+    /// DATABASE_URL is also used by the diesel cli, only postgres urls replace the default sqlite database.
+    /// Sqlite urls are accepted and ignored, any other scheme is a configuration error.
+    fn read_external_database_url(reader: &mut ConfigReader) -> Option<String> {
+        let url = reader.optional(DATABASE_URL_KEY)?;
+        match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("postgres" | "postgresql") => Some(url),
+            Some("sqlite") => None,
+            _ => {
+                reader.error(DATABASE_URL_KEY, "unsupported scheme, expected a postgres://, postgresql:// or sqlite:// url");
+                None
+            }
+        }
     }
+
+    pub fn database_backend(&self) -> DatabaseBackend {
+        match self.external_database_url {
+            Some(_) => DatabaseBackend::Postgres,
+            None => DatabaseBackend::Sqlite,
+        }
+    }
+
     pub fn database_url(&self) -> Result<String, anyhow::Error> {
+        if let Some(url) = &self.external_database_url {
+            return Ok(url.clone());
+        }
         let string = self.database_path().into_os_string().into_string()
             .map_err(|e| anyhow!("Failed to convert database path to string: {:?}", e))?;
         Ok(format!("sqlite://{}", string))
@@ -35,21 +90,35 @@ impl BotStorageConfig {
         PathBuf::from(self.data_directory.clone()).join(DATABASE_FILE_NAME)
     }
 
-    fn check_directory_write_access(directory: &str, source_env_key: &str) -> Result<bool, anyhow::Error> {
-        let metadata = fs::metadata(directory)?;
+    fn check_directory_write_access(reader: &mut ConfigReader, directory: &str, source_key: &str) {
+        if directory.is_empty() {
+            // missing setting is already reported
+            return;
+        }
+        let metadata = match fs::metadata(directory) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                reader.error(source_key, &format!("directory '{}' is not accessible: {}", directory, error));
+                return;
+            }
+        };
         if !metadata.is_dir() {
-            return Err(anyhow!("Expected a directory in environment variable {}!", source_env_key));
+            reader.error(source_key, &format!("expected a directory, found '{}'", directory));
+            return;
         }
         let directory_owner_id = {
             use std::os::linux::fs::MetadataExt;
             metadata.st_uid()
         };
-        let process_owner_id = Self::process_owner_id()?;
-        if directory_owner_id.ne(&process_owner_id) {
-            return Err(anyhow!("Expected write access to directory in environment variable {}!", source_env_key));
+        match Self::process_owner_id() {
+            Ok(process_owner_id) if directory_owner_id.eq(&process_owner_id) => {}
+            Ok(_process_owner_id) => {
+                reader.error(source_key, &format!("expected write access to directory '{}', it is not owned by the process user", directory));
+            }
+            Err(error) => {
+                reader.error(source_key, &format!("could not determine process owner: {}", error));
+            }
         }
-
-        Ok(true)
     }
 
     fn process_owner_id() -> Result<u32, anyhow::Error> {
@@ -57,4 +126,28 @@ impl BotStorageConfig {
         let user_id = std::fs::metadata("/proc/self").map(|m| m.uid())?;
         Ok(user_id)
     }
-}
\ No newline at end of file
+}
+
+impl BotBackupConfig {
+    fn read(reader: &mut ConfigReader) -> Option<Self> {
+        let directory = reader.optional(TELOXIDE_BACKUP_DIR_KEY)?;
+        BotStorageConfig::check_directory_write_access(reader, &directory, TELOXIDE_BACKUP_DIR_KEY);
+
+        // defaults are part of the settings, values are only used if the configuration is valid
+        let interval_hours = reader.parse::<u64>(TELOXIDE_BACKUP_INTERVAL_HOURS_KEY);
+        if interval_hours == Some(0) {
+            reader.error(TELOXIDE_BACKUP_INTERVAL_HOURS_KEY, "backup interval must be at least one hour");
+        }
+
+        let retention = reader.parse::<usize>(TELOXIDE_BACKUP_RETENTION_KEY);
+        if retention == Some(0) {
+            reader.error(TELOXIDE_BACKUP_RETENTION_KEY, "backup retention must keep at least one backup");
+        }
+
+        Some(Self {
+            directory,
+            interval: Duration::from_secs(interval_hours.unwrap_or_default() * 60 * 60),
+            retention: retention.unwrap_or_default(),
+        })
+    }
+}
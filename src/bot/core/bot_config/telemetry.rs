@@ -0,0 +1,45 @@
+use reqwest::Url;
+
+use crate::bot::core::bot_config::source::ConfigReader;
+use crate::bot::core::bot_config::{TELOXIDE_OTLP_ENDPOINT_KEY, TELOXIDE_OTLP_SERVICE_NAME_KEY};
+
+/// Path of the trace signal below the base url of an OTLP/HTTP collector
+const OTLP_TRACES_PATH: &str = "v1/traces";
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+pub(crate) struct BotTelemetryConfig {
+    /// Traces endpoint of the collector, spans are only exported if configured
+    pub otlp_traces_url: Option<Url>,
+    pub service_name: String,
+}
+
+impl BotTelemetryConfig {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let otlp_traces_url = reader.optional(TELOXIDE_OTLP_ENDPOINT_KEY)
+            .and_then(|endpoint| match Self::traces_url(&endpoint) {
+                Ok(url) => Some(url),
+                Err(error) => {
+                    reader.error(TELOXIDE_OTLP_ENDPOINT_KEY, &format!("invalid url '{}': {}", endpoint, error));
+                    None
+                }
+            });
+        if otlp_traces_url.is_some() && !cfg!(feature = "otel") {
+            reader.error(TELOXIDE_OTLP_ENDPOINT_KEY, "the bot was built without the 'otel' feature");
+        }
+        let service_name = reader.required(TELOXIDE_OTLP_SERVICE_NAME_KEY);
+        reader.finish()?;
+
+        Ok(Self {
+            otlp_traces_url,
+            service_name,
+        })
+    }
+
+    /// `http://collector:4318` -> `http://collector:4318/v1/traces`
+    fn traces_url(endpoint: &str) -> Result<Url, anyhow::Error> {
+        let base = format!("{}/", endpoint.trim_end_matches('/'));
+        Ok(Url::parse(&base)?.join(OTLP_TRACES_PATH)?)
+    }
+}
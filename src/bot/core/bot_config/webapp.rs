@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use crate::bot::core::bot_config::source::ConfigReader;
+use crate::bot::core::bot_config::{TELOXIDE_WEBAPP_ASSETS_DIR_KEY, TELOXIDE_WEBAPP_ENABLED_KEY, TELOXIDE_WEBAPP_MENU_BUTTON_TEXT_KEY};
+
+#[derive(Debug, Clone)]
+pub(crate) struct BotWebAppConfig {
+    pub enabled: bool,
+    /// Label of the chat menu button that opens the mini app
+    pub menu_button_text: String,
+    /// Directory with an own `index.html` and further assets, otherwise the built-in page is served
+    pub assets_dir: Option<PathBuf>,
+}
+
+impl BotWebAppConfig {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut reader = ConfigReader::new();
+        let enabled = reader.parse::<bool>(TELOXIDE_WEBAPP_ENABLED_KEY);
+        let menu_button_text = reader.required(TELOXIDE_WEBAPP_MENU_BUTTON_TEXT_KEY);
+        let assets_dir = reader.parse::<PathBuf>(TELOXIDE_WEBAPP_ASSETS_DIR_KEY);
+        if let Some(assets_dir) = &assets_dir {
+            if !assets_dir.join("index.html").is_file() {
+                reader.error(TELOXIDE_WEBAPP_ASSETS_DIR_KEY, &format!("'{}' contains no index.html", assets_dir.display()));
+            }
+        }
+        reader.finish()?;
+
+        Ok(Self {
+            enabled: enabled.unwrap_or_default(),
+            menu_button_text,
+            assets_dir,
+        })
+    }
+}
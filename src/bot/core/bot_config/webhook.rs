@@ -1,9 +1,11 @@
-use std::env;
-use anyhow::anyhow;
 use std::net::SocketAddr;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use crate::bot::core::bot_config::{TELEGRAM_BOT_ENDPOINT_BOT, TELEGRAM_BOT_ENDPOINT_HEALTHCHECK, TELOXIDE_BIND_ADDRESS_KEY, TELOXIDE_BIND_PORT_KEY, TELOXIDE_PUBLIC_URL_KEY};
+use crate::bot::core::bot_config::source::{ConfigReader, Secret};
+use crate::bot::core::bot_config::{TELEGRAM_BOT_ENDPOINT_BOT, TELEGRAM_BOT_ENDPOINT_HEALTHCHECK, TELOXIDE_BIND_ADDRESS_KEY, TELOXIDE_BIND_PORT_KEY, TELOXIDE_PUBLIC_URL_KEY, TELOXIDE_WEBHOOK_SECRET_KEY};
+
+/// Limits of the `secret_token` parameter of setWebhook
+const SECRET_TOKEN_MAX_LENGTH: usize = 256;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub(crate) struct BotConfigWebHook {
@@ -11,36 +13,60 @@ pub(crate) struct BotConfigWebHook {
     pub public_url: Url,
     pub public_bot_url: Url,
     pub public_healthcheck_url: Url,
+    /// Sent by telegram in the `X-Telegram-Bot-Api-Secret-Token` header, generated on start if not configured
+    #[serde(skip)]
+    pub secret_token: Option<Secret>,
 }
 
 impl BotConfigWebHook {
     pub fn new() -> Result<Self, anyhow::Error> {
-        let bind_port = env::var(TELOXIDE_BIND_PORT_KEY)
-            .map_err(|_| anyhow!("Could not find environment variable {}", TELOXIDE_BIND_PORT_KEY))?;
-        let bind_port = bind_port.parse::<u16>().map_err(|error|
-            anyhow!("Could not parse bind port. Check environment variable '{}={}'. Error: {}", TELOXIDE_BIND_PORT_KEY, bind_port, error)
-        )?;
-
-        let bind_address = env::var(TELOXIDE_BIND_ADDRESS_KEY)
-            .unwrap_or("0.0.0.0".to_string());
-        let socket_address: SocketAddr = format!("{}:{}", bind_address, bind_port).parse()
-            .map_err(|error| {
-                anyhow!("Unable to parse socket address. Check environment vars: {}. Error: {}", TELOXIDE_BIND_ADDRESS_KEY, error)
-            })?;
-
-        let public_url_string = env::var(TELOXIDE_PUBLIC_URL_KEY)
-            .map_err(|_error| anyhow!("Could not get public bot address. Ensure environment variable '{}' exists.", TELOXIDE_PUBLIC_URL_KEY))?;
-        let public_url: Url = public_url_string.parse()
-            .map_err(|error| anyhow!("Failed to parse public url in environment variable '{}'. Error: {}", TELOXIDE_PUBLIC_URL_KEY, error))?;
+        let mut reader = ConfigReader::new();
+        let bind_port = reader.required(TELOXIDE_BIND_PORT_KEY);
+        let bind_address = reader.required(TELOXIDE_BIND_ADDRESS_KEY);
+        let socket_address = format!("{}:{}", bind_address, bind_port).parse::<SocketAddr>();
+        // a missing port is already reported
+        if let (Err(error), false) = (&socket_address, bind_port.is_empty()) {
+            reader.error(TELOXIDE_BIND_ADDRESS_KEY, &format!("unable to parse socket address '{}:{}': {}", bind_address, bind_port, error));
+        }
+
+        let public_url = reader.parse::<Url>(TELOXIDE_PUBLIC_URL_KEY);
+        if public_url.is_none() {
+            reader.required(TELOXIDE_PUBLIC_URL_KEY);
+        }
+        let secret_token = reader.optional(TELOXIDE_WEBHOOK_SECRET_KEY);
+        if let Some(secret_token) = &secret_token {
+            if let Err(error) = Self::check_secret_token(secret_token) {
+                reader.error(TELOXIDE_WEBHOOK_SECRET_KEY, error);
+            }
+        }
+        reader.finish()?;
 
+        let public_url = public_url.expect("Public url was validated.");
         let public_bot_url: Url = public_url.join(TELEGRAM_BOT_ENDPOINT_BOT)?;
         let public_healthcheck_url: Url = public_url.join(TELEGRAM_BOT_ENDPOINT_HEALTHCHECK)?;
 
         Ok(Self {
-            socket_address,
+            socket_address: socket_address?,
             public_url,
             public_bot_url,
             public_healthcheck_url,
+            secret_token: secret_token.map(Secret::new),
         })
     }
+
+    fn check_secret_token(secret_token: &str) -> Result<(), &'static str> {
+        if !(1..=SECRET_TOKEN_MAX_LENGTH).contains(&secret_token.len()) {
+            return Err("secret token must have 1 to 256 characters");
+        }
+        if !secret_token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err("secret token may only contain the characters a-z, A-Z, 0-9, _ and -");
+        }
+        Ok(())
+    }
+
+    /// Webhook mode is configured if any of its required settings is present.
+    pub fn is_configured() -> bool {
+        let reader = ConfigReader::new();
+        reader.optional(TELOXIDE_BIND_PORT_KEY).is_some() || reader.optional(TELOXIDE_PUBLIC_URL_KEY).is_some()
+    }
 }
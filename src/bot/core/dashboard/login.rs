@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+
+use crate::bot::core::signature::{data_check_string, hmac_sha256, to_hex, verify_hmac_sha256, HASH_FIELD};
+
+/// The widget signs the time of the login, older payloads are rejected
+const LOGIN_MAX_AGE_SECONDS: i64 = 60 * 60;
+/// Clocks of telegram and the bot may differ a little
+const LOGIN_MAX_CLOCK_SKEW_SECONDS: i64 = 60;
+pub(crate) const SESSION_LIFETIME_SECONDS: i64 = 12 * 60 * 60;
+
+/// The login widget uses the SHA-256 of the bot token as key, see https://core.telegram.org/widgets/login#checking-authorization
+fn login_key(bot_token: &str) -> Vec<u8> {
+    Sha256::digest(bot_token.as_bytes()).to_vec()
+}
+
+/// Checks the hash and age of the fields the Telegram Login Widget passes to the auth url, returns the telegram id.
+pub(crate) fn verify_login(fields: &BTreeMap<String, String>, bot_token: &str, now: i64) -> Result<i64, anyhow::Error> {
+    let hash = fields.get(HASH_FIELD).ok_or_else(|| anyhow!("The login data is not signed."))?;
+    if !verify_hmac_sha256(&login_key(bot_token), data_check_string(fields).as_bytes(), hash) {
+        return Err(anyhow!("The signature of the login data is invalid."));
+    }
+    let auth_date = fields.get("auth_date")
+        .and_then(|auth_date| auth_date.parse::<i64>().ok())
+        .ok_or_else(|| anyhow!("The login data has no valid auth_date."))?;
+    if auth_date > now + LOGIN_MAX_CLOCK_SKEW_SECONDS || now - auth_date > LOGIN_MAX_AGE_SECONDS {
+        return Err(anyhow!("The login has expired, please log in again."));
+    }
+    fields.get("id")
+        .and_then(|id| id.parse::<i64>().ok())
+        .ok_or_else(|| anyhow!("The login data has no valid id."))
+}
+
+/// Query of a login signed like the widget does it, to log in without the widget, e.g. on localhost.
+pub(crate) fn signed_login_query(telegram_id: i64, first_name: &str, bot_token: &str, now: i64) -> String {
+    let mut fields = BTreeMap::new();
+    fields.insert("auth_date".to_string(), now.to_string());
+    fields.insert("first_name".to_string(), first_name.to_string());
+    fields.insert("id".to_string(), telegram_id.to_string());
+    let hash = to_hex(&hmac_sha256(&login_key(bot_token), data_check_string(&fields).as_bytes()));
+    fields.insert(HASH_FIELD.to_string(), hash);
+    reqwest::Url::parse_with_params("http://localhost/", &fields)
+        .expect("Static url is valid.")
+        .query()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Sessions use another key than logins, a signed login can not be used as a session.
+fn session_key(bot_token: &str) -> Vec<u8> {
+    hmac_sha256(b"DashboardSession", bot_token.as_bytes())
+}
+
+/// Cookie value `<telegram id>.<expiry>.<signature>`, sessions end when the bot token changes.
+pub(crate) fn session_cookie_value(telegram_id: i64, bot_token: &str, now: i64) -> String {
+    let payload = format!("{}.{}", telegram_id, now + SESSION_LIFETIME_SECONDS);
+    let signature = to_hex(&hmac_sha256(&session_key(bot_token), payload.as_bytes()));
+    format!("{}.{}", payload, signature)
+}
+
+/// Telegram id of a valid and unexpired session.
+pub(crate) fn verify_session(value: &str, bot_token: &str, now: i64) -> Option<i64> {
+    let (payload, signature) = value.rsplit_once('.')?;
+    if !verify_hmac_sha256(&session_key(bot_token), payload.as_bytes(), signature) {
+        return None;
+    }
+    let (telegram_id, expires) = payload.split_once('.')?;
+    if expires.parse::<i64>().ok()? < now {
+        return None;
+    }
+    telegram_id.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::bot::core::dashboard::login::{session_cookie_value, signed_login_query, verify_login, verify_session, SESSION_LIFETIME_SECONDS};
+    use crate::bot::core::signature::HASH_FIELD;
+
+    const BOT_TOKEN: &str = "123456:test-token";
+    const NOW: i64 = 1_700_000_000;
+    const ADMIN: i64 = 1001;
+
+    fn login_fields(auth_date: i64) -> BTreeMap<String, String> {
+        let query = signed_login_query(ADMIN, "Admin", BOT_TOKEN, auth_date);
+        reqwest::Url::parse(&format!("http://localhost/?{}", query)).unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    #[test]
+    fn accepts_signed_login() {
+        assert_eq!(verify_login(&login_fields(NOW), BOT_TOKEN, NOW).unwrap(), ADMIN);
+        assert_eq!(verify_login(&login_fields(NOW - 30 * 60), BOT_TOKEN, NOW).unwrap(), ADMIN);
+    }
+
+    #[test]
+    fn rejects_tampered_login() {
+        let mut fields = login_fields(NOW);
+        fields.insert("id".to_string(), "1003".to_string());
+        assert!(verify_login(&fields, BOT_TOKEN, NOW).unwrap_err().to_string().contains("signature"));
+
+        assert!(verify_login(&login_fields(NOW), "654321:other-token", NOW).is_err());
+
+        let mut fields = login_fields(NOW);
+        fields.remove(HASH_FIELD);
+        assert!(verify_login(&fields, BOT_TOKEN, NOW).unwrap_err().to_string().contains("not signed"));
+    }
+
+    #[test]
+    fn rejects_expired_login() {
+        let expired = verify_login(&login_fields(NOW - 2 * 60 * 60), BOT_TOKEN, NOW);
+        assert!(expired.unwrap_err().to_string().contains("expired"));
+        let future = verify_login(&login_fields(NOW + 10 * 60), BOT_TOKEN, NOW);
+        assert!(future.unwrap_err().to_string().contains("expired"));
+    }
+
+    #[test]
+    fn rejects_login_replayed_as_session() {
+        let fields = login_fields(NOW);
+        let replayed = format!("{}.{}.{}", ADMIN, NOW + SESSION_LIFETIME_SECONDS, fields[HASH_FIELD]);
+        assert_eq!(verify_session(&replayed, BOT_TOKEN, NOW), None);
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let session = session_cookie_value(ADMIN, BOT_TOKEN, NOW);
+        assert_eq!(verify_session(&session, BOT_TOKEN, NOW + SESSION_LIFETIME_SECONDS), Some(ADMIN));
+        assert_eq!(verify_session(&session, BOT_TOKEN, NOW + SESSION_LIFETIME_SECONDS + 1), None);
+        // a longer expiry invalidates the signature
+        let extended = session.replacen(&(NOW + SESSION_LIFETIME_SECONDS).to_string(), &(NOW + 10 * SESSION_LIFETIME_SECONDS).to_string(), 1);
+        assert_eq!(verify_session(&extended, BOT_TOKEN, NOW + SESSION_LIFETIME_SECONDS + 1), None);
+        assert_eq!(verify_session(&session, "654321:other-token", NOW), None);
+    }
+}
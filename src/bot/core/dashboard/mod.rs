@@ -0,0 +1,243 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header::COOKIE;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+
+use crate::bot::core::admin_api::status_of;
+use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_DASHBOARD;
+use crate::bot::core::dashboard::login::verify_session;
+use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::user_representation::UserRepresentation;
+use crate::bot::core::healthcheck::readiness::BotReadiness;
+use crate::bot::core::repository::UserRepository;
+use crate::bot::core::shutdown::{shutdown_requested, spawn_background_task};
+
+pub(crate) mod login;
+mod pages;
+
+const SESSION_COOKIE: &str = "dashboard_session";
+
+#[derive(Clone)]
+pub(crate) struct DashboardState {
+    database_client: DatabaseClient,
+    readiness: Arc<BotReadiness>,
+    /// Shown by the login widget
+    bot_name: String,
+    /// Key of the login widget signature and of the sessions
+    bot_token: String,
+}
+
+/// Admin of the current session, looked up in the database on every request so admins removed by any process lose access immediately.
+#[derive(Clone)]
+struct DashboardAdmin(UserRepresentation);
+
+/// Server-rendered pages for admins below [`TELEGRAM_BOT_ENDPOINT_DASHBOARD`], all pages except the login require a session.
+pub(crate) fn dashboard_router(database_client: DatabaseClient, readiness: Arc<BotReadiness>, bot_name: &str, bot_token: &str) -> axum::Router {
+    let state = DashboardState {
+        database_client,
+        readiness,
+        bot_name: bot_name.to_string(),
+        bot_token: bot_token.to_string(),
+    };
+    let dashboard = axum::Router::new()
+        .route("/", get(pages::overview))
+        .route("/users", get(pages::users).post(pages::create_user))
+        .route("/users/:user_name/invite", get(pages::invite))
+        .route("/users/:user_name/start-token", post(pages::rotate_start_token))
+        .route("/audit", get(pages::audit_log))
+        .route("/broadcasts", get(pages::broadcasts))
+        .route("/logout", post(pages::logout))
+        // only applies to the routes above
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), require_admin_session))
+        .route("/login", get(pages::login))
+        .route("/login/telegram", get(pages::login_callback))
+        .with_state(state);
+    axum::Router::new().nest(TELEGRAM_BOT_ENDPOINT_DASHBOARD, dashboard)
+}
+
+/// Serve the dashboard on its own address, e.g. only reachable from the internal network.
+/// Stops accepting requests on shutdown, the shutdown waits for the running ones.
+pub(crate) async fn spawn_dashboard_listener(address: SocketAddr, router: axum::Router) -> Result<(), anyhow::Error> {
+    let tcp_listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|error| anyhow::anyhow!("Could not bind dashboard listener to {}: {}", address, error))?;
+    tracing::info!("Serving dashboard on http://{}{}", address, TELEGRAM_BOT_ENDPOINT_DASHBOARD);
+    spawn_background_task(async move {
+        if let Err(error) = axum::serve(tcp_listener, router).with_graceful_shutdown(shutdown_requested()).await {
+            tracing::error!("Dashboard listener failed: {}", error);
+        }
+    });
+    Ok(())
+}
+
+/// Lax keeps the session for the redirect from the login widget but not for cross-site form posts.
+fn session_cookie(value: &str, max_age: i64) -> String {
+    format!("{}={}; Path={}; Max-Age={}; HttpOnly; Secure; SameSite=Lax", SESSION_COOKIE, value, TELEGRAM_BOT_ENDPOINT_DASHBOARD, max_age)
+}
+
+fn login_redirect() -> Response {
+    Redirect::to(&format!("{}/login", TELEGRAM_BOT_ENDPOINT_DASHBOARD)).into_response()
+}
+
+async fn require_admin_session(State(state): State<DashboardState>, mut request: Request, next: Next) -> Response {
+    let session = request.headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string());
+    let now = chrono::Utc::now().timestamp();
+    let Some(telegram_id) = session.and_then(|session| verify_session(&session, &state.bot_token, now)) else {
+        return login_redirect();
+    };
+    match state.database_client.known_user(telegram_id).await {
+        Ok(Some(user)) if user.is_admin() => {
+            request.extensions_mut().insert(DashboardAdmin(user));
+            next.run(request).await
+        }
+        Err(error) => DashboardError::from(error).into_response(),
+        Ok(_) => {
+            tracing::warn!("Dashboard session of a user that is no admin anymore");
+            ([("set-cookie", session_cookie("", 0))], login_redirect()).into_response()
+        }
+    }
+}
+
+/// Error of a dashboard request, rendered as page.
+#[derive(Debug)]
+pub(crate) struct DashboardError {
+    status: StatusCode,
+    message: String,
+}
+
+impl DashboardError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    fn internal(error: impl std::fmt::Display) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+    }
+}
+
+impl From<DatabaseError> for DashboardError {
+    fn from(error: DatabaseError) -> Self {
+        Self::new(status_of(&error), error.to_string())
+    }
+}
+
+impl IntoResponse for DashboardError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() {
+            tracing::error!("Dashboard request failed: {}", self.message);
+        }
+        (self.status, pages::error_page(self.status, &self.message)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{COOKIE, LOCATION, SET_COOKIE};
+    use reqwest::redirect::Policy;
+
+    use crate::bot::core::bot_config::storage::DatabaseBackend;
+    use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_DASHBOARD;
+    use crate::bot::core::dashboard::login::{session_cookie_value, signed_login_query, SESSION_LIFETIME_SECONDS};
+    use crate::bot::core::dashboard::{dashboard_router, SESSION_COOKIE};
+    use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
+    use crate::bot::core::db::client::DatabaseClient;
+    use crate::bot::core::db::test_database::TestDatabase;
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::healthcheck::readiness::BotReadiness;
+    use crate::bot::core::mock_api::serve_local;
+
+    const BOT_TOKEN: &str = "123456:test-token";
+    const ADMIN: i64 = 1001;
+    const USER: i64 = 1002;
+
+    /// Serve the dashboard of a database with the linked accounts of an admin and a user.
+    async fn serve_dashboard(database: &TestDatabase) -> String {
+        let mut client = DatabaseClient::load(database.connection.clone(), "testbot").await.unwrap();
+        client.create_user("admin", &UserRole::Admin).await.unwrap();
+        client.link_telegram_account("admin", ADMIN).await.unwrap();
+        client.create_user("user", &UserRole::User).await.unwrap();
+        client.link_telegram_account("user", USER).await.unwrap();
+
+        let router = dashboard_router(client, BotReadiness::new(None), "testbot", BOT_TOKEN);
+        serve_local(router).await.join(TELEGRAM_BOT_ENDPOINT_DASHBOARD).unwrap().to_string()
+    }
+
+    fn http_client() -> reqwest::Client {
+        reqwest::Client::builder().no_proxy().redirect(Policy::none()).build().unwrap()
+    }
+
+    async fn get_with_session(dashboard_url: &str, session: Option<String>) -> reqwest::Response {
+        let mut request = http_client().get(format!("{}/users", dashboard_url));
+        if let Some(session) = session {
+            request = request.header(COOKIE, format!("{}={}", SESSION_COOKIE, session));
+        }
+        request.send().await.unwrap()
+    }
+
+    fn redirects_to_login(response: &reqwest::Response) -> bool {
+        response.status().is_redirection()
+            && response.headers().get(LOCATION).unwrap() == &format!("{}/login", TELEGRAM_BOT_ENDPOINT_DASHBOARD)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pages_require_admin_session() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let dashboard_url = serve_dashboard(&database).await;
+        let now = chrono::Utc::now().timestamp();
+
+        assert_eq!(get_with_session(&dashboard_url, Some(session_cookie_value(ADMIN, BOT_TOKEN, now))).await.status(), 200);
+        assert!(redirects_to_login(&get_with_session(&dashboard_url, None).await));
+        let expired = session_cookie_value(ADMIN, BOT_TOKEN, now - SESSION_LIFETIME_SECONDS - 60);
+        assert!(redirects_to_login(&get_with_session(&dashboard_url, Some(expired)).await));
+
+        let not_admin = get_with_session(&dashboard_url, Some(session_cookie_value(USER, BOT_TOKEN, now))).await;
+        assert!(redirects_to_login(&not_admin));
+        let cleared = not_admin.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(cleared.starts_with(&format!("{}=;", SESSION_COOKIE)) && cleared.contains("Max-Age=0"), "{}", cleared);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn removed_admin_loses_session() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let dashboard_url = serve_dashboard(&database).await;
+        let session = session_cookie_value(ADMIN, BOT_TOKEN, chrono::Utc::now().timestamp());
+        assert_eq!(get_with_session(&dashboard_url, Some(session.clone())).await.status(), 200);
+
+        // e.g. the admin cli
+        let other_client = DatabaseClient::load(database.connection.clone(), "testbot").await.unwrap();
+        other_client.delete_user("admin").await.unwrap();
+        assert!(redirects_to_login(&get_with_session(&dashboard_url, Some(session)).await));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn login_starts_session_of_admin() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let dashboard_url = serve_dashboard(&database).await;
+        let now = chrono::Utc::now().timestamp();
+        let login = |telegram_id: i64, bot_token: &str| {
+            http_client().get(format!("{}/login/telegram?{}", dashboard_url, signed_login_query(telegram_id, "Test", bot_token, now))).send()
+        };
+
+        assert_eq!(login(ADMIN, "654321:other-token").await.unwrap().status(), 401);
+        assert_eq!(login(USER, BOT_TOKEN).await.unwrap().status(), 403);
+
+        let response = login(ADMIN, BOT_TOKEN).await.unwrap();
+        assert!(response.status().is_redirection());
+        let cookie = response.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        let session = cookie.split(';').next().unwrap().strip_prefix(&format!("{}=", SESSION_COOKIE)).unwrap();
+        assert_eq!(get_with_session(&dashboard_url, Some(session.to_string())).await.status(), 200);
+    }
+}
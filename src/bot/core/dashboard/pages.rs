@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, Query, State};
+use axum::http::header::SET_COOKIE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Extension, Form};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::bot::core::audit::{audit, Actor, AuditAction};
+use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_DASHBOARD;
+use crate::bot::core::dashboard::login::{session_cookie_value, verify_login, SESSION_LIFETIME_SECONDS};
+use crate::bot::core::dashboard::{login_redirect, session_cookie, DashboardAdmin, DashboardError, DashboardState};
+use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
+use crate::bot::core::db::user_representation::UserRole;
+use crate::bot::core::healthcheck::readiness::ComponentStatus;
+use crate::bot::core::repository::{AuditRepository, IncidentRepository, UserRepository};
+
+const AUDIT_LOG_LIMIT: i64 = 200;
+const BROADCAST_LIMIT: i64 = 50;
+const RECENT_INCIDENTS_LIMIT: i64 = 5;
+const LOGIN_WIDGET_SCRIPT: &str = "https://telegram.org/js/telegram-widget.js?22";
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 0 auto; max-width: 60rem; padding: 1rem; color: #222; }
+nav { display: flex; gap: 1rem; align-items: center; border-bottom: 1px solid #ddd; padding-bottom: .5rem; margin-bottom: 1rem; }
+nav form { margin-left: auto; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: .3rem .5rem; border-bottom: 1px solid #eee; vertical-align: top; }
+.ok { color: #1a7f37; }
+.failed { color: #cf222e; }
+.muted { color: #666; }
+code { word-break: break-all; }
+";
+
+fn dashboard_path(path: &str) -> String {
+    format!("{}{}", TELEGRAM_BOT_ENDPOINT_DASHBOARD, path)
+}
+
+/// Path of a user page, the name is percent-encoded.
+fn user_path(user_name: &str, page: &str) -> String {
+    let mut url = Url::parse("http://localhost").expect("Static url is valid.");
+    url.path_segments_mut()
+        .expect("Http urls have path segments.")
+        .extend([TELEGRAM_BOT_ENDPOINT_DASHBOARD.trim_start_matches('/'), "users", user_name, page]);
+    url.path().to_string()
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|timestamp| timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+fn layout(title: &str, admin: Option<&DashboardAdmin>, content: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { (title) " - Telegram bot" }
+                style { (PreEscaped(STYLE)) }
+            }
+            body {
+                @if let Some(DashboardAdmin(admin)) = admin {
+                    nav {
+                        a href=(TELEGRAM_BOT_ENDPOINT_DASHBOARD) { "Status" }
+                        a href=(dashboard_path("/users")) { "Users" }
+                        a href=(dashboard_path("/audit")) { "Audit log" }
+                        a href=(dashboard_path("/broadcasts")) { "Broadcasts" }
+                        form method="post" action=(dashboard_path("/logout")) {
+                            span.muted { (admin.name) " " }
+                            button type="submit" { "Log out" }
+                        }
+                    }
+                }
+                h1 { (title) }
+                (content)
+            }
+        }
+    }
+}
+
+pub(super) fn error_page(status: StatusCode, message: &str) -> Markup {
+    layout(status.canonical_reason().unwrap_or("Error"), None, html! {
+        p.failed { (message) }
+        p { a href=(TELEGRAM_BOT_ENDPOINT_DASHBOARD) { "Back to the dashboard" } }
+    })
+}
+
+fn login_page(bot_name: &str, message: Option<&str>) -> Markup {
+    layout("Login", None, html! {
+        @if let Some(message) = message {
+            p.failed { (message) }
+        }
+        p { "Log in with the telegram account of an admin." }
+        script async src=(LOGIN_WIDGET_SCRIPT) data-telegram-login=(bot_name) data-size="large"
+            data-auth-url=(dashboard_path("/login/telegram")) data-request-access="write" {}
+    })
+}
+
+pub(super) async fn login(State(state): State<DashboardState>) -> Markup {
+    login_page(&state.bot_name, None)
+}
+
+/// Auth url of the login widget, telegram redirects here with the signed account fields.
+pub(super) async fn login_callback(State(state): State<DashboardState>, Query(fields): Query<BTreeMap<String, String>>) -> Response {
+    let now = chrono::Utc::now().timestamp();
+    let telegram_id = match verify_login(&fields, &state.bot_token, now) {
+        Ok(telegram_id) => telegram_id,
+        Err(error) => {
+            tracing::warn!("Rejected dashboard login: {}", error);
+            return (StatusCode::UNAUTHORIZED, login_page(&state.bot_name, Some(&error.to_string()))).into_response();
+        }
+    };
+    let admin = match state.database_client.known_user(telegram_id).await {
+        Ok(Some(user)) if user.is_admin() => user,
+        Err(error) => return DashboardError::from(error).into_response(),
+        Ok(_) => {
+            tracing::warn!("Rejected dashboard login of a telegram account without admin role");
+            return (StatusCode::FORBIDDEN, login_page(&state.bot_name, Some("Only admins may use the dashboard."))).into_response();
+        }
+    };
+    audit(&state.database_client, Actor::Dashboard(&admin.name), AuditAction::DashboardLogin, &admin.name, None).await;
+    let cookie = session_cookie(&session_cookie_value(telegram_id, &state.bot_token, now), SESSION_LIFETIME_SECONDS);
+    ([(SET_COOKIE, cookie)], Redirect::to(TELEGRAM_BOT_ENDPOINT_DASHBOARD)).into_response()
+}
+
+pub(super) async fn logout() -> Response {
+    ([(SET_COOKIE, session_cookie("", 0))], login_redirect()).into_response()
+}
+
+fn status_row(name: &str, status: &ComponentStatus) -> Markup {
+    html! {
+        tr {
+            td { (name) }
+            @if status.healthy {
+                td.ok { "ok" }
+            } @else {
+                td.failed { "failed" }
+            }
+            td { (status.message.as_deref().unwrap_or("")) }
+        }
+    }
+}
+
+pub(super) async fn overview(State(state): State<DashboardState>, Extension(admin): Extension<DashboardAdmin>) -> Result<Markup, DashboardError> {
+    let readiness = state.readiness.readiness().await;
+    let users = state.database_client.list_users().await?;
+    let registered = users.iter().filter(|user| user.telegram_id.is_some()).count();
+    let incidents = state.database_client.list_incidents(RECENT_INCIDENTS_LIMIT).await?;
+    Ok(layout("Status", Some(&admin), html! {
+        @if readiness.ok {
+            p.ok { "The bot is ready." }
+        } @else {
+            p.failed { "The bot is not ready." }
+        }
+        table {
+            (status_row("Database", &readiness.database))
+            (status_row("Dispatcher", &readiness.dispatcher))
+            (status_row("Updates", &readiness.updates))
+            (status_row("Telegram", &readiness.telegram))
+            (status_row("Webhook", &readiness.webhook))
+        }
+        p { (registered) " of " (users.len()) " users are registered." }
+        h2 { "Recent incidents" }
+        @if incidents.is_empty() {
+            p.muted { "No incidents." }
+        } @else {
+            table {
+                tr { th { "Id" } th { "Time" } th { "Command" } th { "Error" } }
+                @for incident in &incidents {
+                    tr {
+                        td { code { (incident.id) } }
+                        td { (format_timestamp(incident.created_at)) }
+                        td { (incident.command.as_deref().unwrap_or("-")) }
+                        td { (incident.error) }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+pub(super) async fn users(State(state): State<DashboardState>, Extension(admin): Extension<DashboardAdmin>) -> Result<Markup, DashboardError> {
+    let users = state.database_client.list_users().await?;
+    Ok(layout("Users", Some(&admin), html! {
+        table {
+            tr { th { "Name" } th { "Role" } th { "Registration" } th {} }
+            @for user in &users {
+                tr {
+                    td { (user.name) }
+                    td { (user.role) }
+                    @match user.telegram_id {
+                        Some(telegram_id) => td.ok { "registered, telegram id " (telegram_id) },
+                        None => td.muted { "invited" },
+                    }
+                    td { a href=(user_path(&user.name, "invite")) { "Invite link" } }
+                }
+            }
+        }
+        h2 { "Invite a user" }
+        form method="post" action=(dashboard_path("/users")) {
+            input name="name" required placeholder="Name";
+            " "
+            select name="role" {
+                option value="user" selected { "user" }
+                option value="admin" { "admin" }
+            }
+            " "
+            button type="submit" { "Create invite link" }
+        }
+    }))
+}
+
+#[derive(Deserialize)]
+pub(super) struct CreateUserForm {
+    name: String,
+    role: UserRole,
+}
+
+pub(super) async fn create_user(State(state): State<DashboardState>, Extension(admin): Extension<DashboardAdmin>, Form(form): Form<CreateUserForm>) -> Result<Redirect, DashboardError> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(DashboardError::new(StatusCode::UNPROCESSABLE_ENTITY, "The user name must not be empty."));
+    }
+    let user = state.database_client.create_user(name, &form.role).await?;
+    audit(&state.database_client, Actor::Dashboard(&admin.0.name), AuditAction::UserCreated, &user.name, Some(format!("role={}", user.role))).await;
+    Ok(Redirect::to(&user_path(&user.name, "invite")))
+}
+
+/// Deep link with the start token, also as QR code to scan with the phone.
+pub(super) async fn invite(State(state): State<DashboardState>, Extension(admin): Extension<DashboardAdmin>, Path(user_name): Path<String>) -> Result<Markup, DashboardError> {
+    let user = state.database_client.get_user(&user_name).await?;
+    let qr_code = QrCode::new(user.bot_start_url.as_bytes())
+        .map_err(DashboardError::internal)?
+        .render::<svg::Color>()
+        .min_dimensions(240, 240)
+        .build();
+    Ok(layout(&format!("Invite {}", user.name), Some(&admin), html! {
+        @match user.telegram_id {
+            Some(telegram_id) => p.ok { "Registered with telegram id " (telegram_id) "." },
+            None => p { "Send the link to " (user.name) ", opening it registers the telegram account." },
+        }
+        p { a href=(user.bot_start_url) { code { (user.bot_start_url) } } }
+        (PreEscaped(qr_code))
+        form method="post" action=(user_path(&user.name, "start-token")) {
+            button type="submit" { "Replace the invite link" }
+            span.muted { " The current link stops working." }
+        }
+    }))
+}
+
+pub(super) async fn rotate_start_token(State(state): State<DashboardState>, Extension(admin): Extension<DashboardAdmin>, Path(user_name): Path<String>) -> Result<Redirect, DashboardError> {
+    let user = state.database_client.rotate_start_token(&user_name).await?;
+    audit(&state.database_client, Actor::Dashboard(&admin.0.name), AuditAction::StartTokenRotated, &user.name, None).await;
+    Ok(Redirect::to(&user_path(&user.name, "invite")))
+}
+
+pub(super) async fn audit_log(State(state): State<DashboardState>, Extension(admin): Extension<DashboardAdmin>) -> Result<Markup, DashboardError> {
+    let events = state.database_client.list_audit_events(AUDIT_LOG_LIMIT).await?;
+    Ok(layout("Audit log", Some(&admin), html! {
+        table {
+            tr { th { "Time" } th { "Actor" } th { "Action" } th { "Target" } th { "Detail" } }
+            @for event in &events {
+                tr {
+                    td { (format_timestamp(event.created_at)) }
+                    td { (event.actor) }
+                    td { (event.action) }
+                    td { (event.target.as_deref().unwrap_or("")) }
+                    td { (event.detail.as_deref().unwrap_or("")) }
+                }
+            }
+        }
+    }))
+}
+
+pub(super) async fn broadcasts(State(state): State<DashboardState>, Extension(admin): Extension<DashboardAdmin>) -> Result<Markup, DashboardError> {
+    let broadcasts = state.database_client.list_broadcasts(BROADCAST_LIMIT).await?;
+    Ok(layout("Broadcasts", Some(&admin), html! {
+        table {
+            tr { th { "Time" } th { "Sender" } th { "Delivered" } th { "Message" } }
+            @for broadcast in &broadcasts {
+                tr {
+                    td { (format_timestamp(broadcast.created_at)) }
+                    td { (broadcast.sender) }
+                    @if broadcast.delivered < broadcast.recipients {
+                        td.failed { (broadcast.delivered) " of " (broadcast.recipients) }
+                    } @else {
+                        td { (broadcast.delivered) " of " (broadcast.recipients) }
+                    }
+                    td { (broadcast.message) }
+                }
+            }
+        }
+    }))
+}
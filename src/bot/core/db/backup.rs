@@ -0,0 +1,339 @@
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use diesel::{Connection, SqliteConnection};
+use libsqlite3_sys as ffi;
+use reqwest::Url;
+use tokio::task::JoinHandle;
+
+use crate::bot::core::bot_config::storage::{BotBackupConfig, BotStorageConfig, DatabaseBackend};
+use crate::bot::core::db::check::{applied_migration_count, check_database};
+
+/// Pages copied per backup step, the source database is unlocked in between
+const PAGES_PER_STEP: c_int = 128;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+const BUSY_TIMEOUT_MILLIS: c_int = 30_000;
+/// Consecutive busy or locked steps after which the backup gives up
+const MAX_BUSY_STEPS: u32 = 100;
+/// Prefix of periodic backups, only these files are pruned
+const BACKUP_FILE_PREFIX: &str = "db-backup-";
+const BACKUP_FILE_SUFFIX: &str = ".sqlite";
+
+/// Raw sqlite connection, diesel does not expose the online backup API.
+struct RawConnection {
+    handle: *mut ffi::sqlite3,
+}
+
+impl RawConnection {
+    fn open(path: &Path, flags: c_int) -> Result<Self, anyhow::Error> {
+        let path_string = path.to_str()
+            .ok_or_else(|| anyhow!("Database path is not valid utf-8: {:?}", path))?;
+        let c_path = CString::new(path_string)?;
+        let mut handle = std::ptr::null_mut();
+        let result = unsafe { ffi::sqlite3_open_v2(c_path.as_ptr(), &mut handle, flags, std::ptr::null()) };
+        // the handle must be closed even if opening failed
+        let connection = Self { handle };
+        if result != ffi::SQLITE_OK {
+            return Err(anyhow!("Could not open database {:?}: {}", path, connection.error_message()));
+        }
+        unsafe { ffi::sqlite3_busy_timeout(connection.handle, BUSY_TIMEOUT_MILLIS) };
+        Ok(connection)
+    }
+
+    fn error_message(&self) -> String {
+        if self.handle.is_null() {
+            return "out of memory".to_string();
+        }
+        unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.handle)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe { ffi::sqlite3_close(self.handle) };
+        }
+    }
+}
+
+/// Copy the main database of `source` into `destination` with the online backup API.
+/// Works while other connections read and write the source database (also in WAL mode).
+fn copy_database(source: &RawConnection, destination: &RawConnection) -> Result<(), anyhow::Error> {
+    let main = CString::new("main")?;
+    let backup = unsafe { ffi::sqlite3_backup_init(destination.handle, main.as_ptr(), source.handle, main.as_ptr()) };
+    if backup.is_null() {
+        return Err(anyhow!("Could not initialize backup: {}", destination.error_message()));
+    }
+
+    let mut busy_steps = 0;
+    loop {
+        let result = unsafe { ffi::sqlite3_backup_step(backup, PAGES_PER_STEP) };
+        match result {
+            ffi::SQLITE_DONE => {
+                break;
+            }
+            ffi::SQLITE_OK => {
+                busy_steps = 0;
+                std::thread::sleep(STEP_PAUSE);
+            }
+            // each step already waited for the busy timeout of the connection
+            ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED if busy_steps < MAX_BUSY_STEPS => {
+                busy_steps += 1;
+                std::thread::sleep(STEP_PAUSE);
+            }
+            _ => {
+                unsafe { ffi::sqlite3_backup_finish(backup) };
+                return Err(anyhow!("Backup step failed with code {}: {}", result, destination.error_message()));
+            }
+        }
+    }
+
+    let result = unsafe { ffi::sqlite3_backup_finish(backup) };
+    if result != ffi::SQLITE_OK {
+        return Err(anyhow!("Could not finish backup: {}", destination.error_message()));
+    }
+    Ok(())
+}
+
+/// Write a consistent copy of the database to a new file.
+/// A partially written file is removed if the backup fails.
+pub(crate) fn backup_database(database_path: &Path, backup_path: &Path) -> Result<(), anyhow::Error> {
+    if backup_path.exists() {
+        return Err(anyhow!("Backup target {:?} already exists, refusing to overwrite it.", backup_path));
+    }
+    let source = RawConnection::open(database_path, ffi::SQLITE_OPEN_READONLY)?;
+    let result = RawConnection::open(backup_path, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE)
+        .and_then(|destination| copy_database(&source, &destination));
+    if let Err(error) = result {
+        if backup_path.exists() {
+            if let Err(remove_error) = fs::remove_file(backup_path) {
+                tracing::error!("Could not remove incomplete backup {:?}: {}", backup_path, remove_error);
+            }
+        }
+        return Err(error);
+    }
+    tracing::info!("Created database backup at {:?}", backup_path);
+    Ok(())
+}
+
+/// Open an existing database file read-only, a missing path is not created.
+pub(crate) fn open_read_only(database_path: &Path) -> Result<SqliteConnection, anyhow::Error> {
+    if !database_path.is_file() {
+        return Err(anyhow!("Database file {:?} does not exist.", database_path));
+    }
+    let mut database_url = Url::from_file_path(fs::canonicalize(database_path)?)
+        .map_err(|_| anyhow!("Invalid database path {:?}", database_path))?;
+    database_url.set_query(Some("mode=ro"));
+    Ok(SqliteConnection::establish(database_url.as_str())?)
+}
+
+/// Make sure a backup file is a healthy, migrated bot database before it replaces the live one.
+/// The file is opened read-only, a missing path is not created.
+pub(crate) fn verify_backup(backup_path: &Path) -> Result<(), anyhow::Error> {
+    if !backup_path.is_file() {
+        return Err(anyhow!("Backup file {:?} does not exist.", backup_path));
+    }
+    let mut connection = open_read_only(backup_path)?;
+
+    let report = check_database(&mut connection)?;
+    if !report.is_ok() {
+        return Err(anyhow!("Refusing to restore a damaged backup:\n{}", report));
+    }
+    if applied_migration_count(&mut connection)? == 0 {
+        return Err(anyhow!("Refusing to restore {:?}, it has no applied migrations and is not a bot database.", backup_path));
+    }
+    Ok(())
+}
+
+/// Replace the content of the database with the content of a backup file.
+pub(crate) fn restore_database(backup_path: &Path, database_path: &Path) -> Result<(), anyhow::Error> {
+    if !backup_path.is_file() {
+        return Err(anyhow!("Backup file {:?} does not exist.", backup_path));
+    }
+    let source = RawConnection::open(backup_path, ffi::SQLITE_OPEN_READONLY)?;
+    let destination = RawConnection::open(database_path, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE)?;
+    copy_database(&source, &destination)?;
+    tracing::info!("Restored database {:?} from backup {:?}", database_path, backup_path);
+    Ok(())
+}
+
+/// Path of a new backup file in the given directory, named after the current time.
+pub(crate) fn timestamped_backup_path(directory: &Path, prefix: &str) -> PathBuf {
+    let now = chrono::offset::Utc::now().format("%Y%m%dT%H%M%SZ");
+    directory.join(format!("{}{}{}", prefix, now, BACKUP_FILE_SUFFIX))
+}
+
+/// Remove the oldest automatic backups, keeping `retention` files.
+/// The backup directory may be the data directory, the live database is never removed.
+fn prune_backups(directory: &Path, retention: usize, database_path: &Path) -> Result<(), anyhow::Error> {
+    let database_path = fs::canonicalize(database_path).unwrap_or_else(|_| database_path.to_path_buf());
+    let mut backups = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| fs::canonicalize(path).map(|path| path.ne(&database_path)).unwrap_or(false))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with(BACKUP_FILE_PREFIX) && name.ends_with(BACKUP_FILE_SUFFIX))
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    // timestamps in file names sort chronologically
+    backups.sort();
+
+    let obsolete_count = backups.len().saturating_sub(retention);
+    for backup in backups.iter().take(obsolete_count) {
+        tracing::info!("Removing old database backup {:?}", backup);
+        fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+fn run_scheduled_backup(database_path: &Path, backup_config: &BotBackupConfig) -> Result<(), anyhow::Error> {
+    let directory = PathBuf::from(&backup_config.directory);
+    let backup_path = timestamped_backup_path(&directory, BACKUP_FILE_PREFIX);
+    backup_database(database_path, &backup_path)?;
+    prune_backups(&directory, backup_config.retention, database_path)
+}
+
+/// Start periodic database backups if a backup directory is configured.
+pub(crate) fn spawn_periodic_backup(storage_config: &BotStorageConfig) -> Option<JoinHandle<()>> {
+    let backup_config = storage_config.backup.clone()?;
+    if storage_config.database_backend().ne(&DatabaseBackend::Sqlite) {
+        tracing::warn!("Periodic database backup only supports sqlite, ignoring backup configuration.");
+        return None;
+    }
+    let database_path = storage_config.database_path();
+    tracing::info!("Periodic database backup enabled: {:?}", backup_config);
+
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(backup_config.interval);
+        // the first tick completes immediately, do not back up on every restart
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let database_path = database_path.clone();
+            let backup_config = backup_config.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                run_scheduled_backup(&database_path, &backup_config)
+            }).await;
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => {
+                    tracing::error!("Periodic database backup failed: {}", error);
+                }
+                Err(error) => {
+                    tracing::error!("Periodic database backup task failed: {}", error);
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::sql_types::Text;
+    use diesel::{QueryableByName, RunQueryDsl};
+
+    use super::*;
+    use crate::bot::core::db::connection::AnyConnection;
+    use crate::bot::core::db::migration;
+
+    #[derive(QueryableByName)]
+    struct Note {
+        #[diesel(sql_type = Text)]
+        text: String,
+    }
+
+    fn migrated_database(path: &Path) {
+        let mut connection = SqliteConnection::establish(path.to_str().unwrap()).unwrap();
+        diesel::sql_query("CREATE TABLE __diesel_schema_migrations (version VARCHAR(50) PRIMARY KEY NOT NULL, run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP);")
+            .execute(&mut connection).unwrap();
+        diesel::sql_query("INSERT INTO __diesel_schema_migrations (version) VALUES ('20240101000000');")
+            .execute(&mut connection).unwrap();
+    }
+
+    #[test]
+    fn verify_backup_does_not_create_missing_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("missing.sqlite");
+        assert!(verify_backup(&path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn verify_backup_rejects_database_without_migrations() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("empty.sqlite");
+        fs::File::create(&path).unwrap();
+        let error = verify_backup(&path).unwrap_err();
+        assert!(error.to_string().contains("no applied migrations"), "{}", error);
+    }
+
+    #[test]
+    fn backup_of_migrated_database_can_be_restored() {
+        let directory = tempfile::tempdir().unwrap();
+        let database_path = directory.path().join("telegrambot.sqlite");
+        let backup_path = directory.path().join("backup.sqlite");
+        let restored_path = directory.path().join("restored.sqlite");
+        migrated_database(&database_path);
+        let mut connection = SqliteConnection::establish(database_path.to_str().unwrap()).unwrap();
+        diesel::sql_query("CREATE TABLE notes (text TEXT NOT NULL);").execute(&mut connection).unwrap();
+        diesel::sql_query("INSERT INTO notes (text) VALUES ('before backup');").execute(&mut connection).unwrap();
+
+        backup_database(&database_path, &backup_path).unwrap();
+        verify_backup(&backup_path).unwrap();
+        restore_database(&backup_path, &restored_path).unwrap();
+
+        let mut restored = open_read_only(&restored_path).unwrap();
+        let notes = diesel::sql_query("SELECT text FROM notes;").load::<Note>(&mut restored).unwrap();
+        assert_eq!(notes.iter().map(|note| note.text.as_str()).collect::<Vec<_>>(), vec!["before backup"]);
+    }
+
+    #[test]
+    fn check_of_read_only_database_reports_pending_migrations() {
+        let directory = tempfile::tempdir().unwrap();
+        let database_path = directory.path().join("telegrambot.sqlite");
+        migrated_database(&database_path);
+
+        let status = migration::migration_status(&mut AnyConnection::Sqlite(open_read_only(&database_path).unwrap())).unwrap();
+        assert!(!status.pending.is_empty());
+        assert_eq!(status.unknown, vec!["20240101000000"]);
+        assert_eq!(applied_migration_count(&mut open_read_only(&database_path).unwrap()).unwrap(), 1);
+        assert!(open_read_only(&directory.path().join("missing.sqlite")).is_err());
+        assert!(!directory.path().join("missing.sqlite").exists());
+    }
+
+    #[test]
+    fn pruning_keeps_database_and_other_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let database_path = directory.path().join("db.sqlite");
+        let files = ["db.sqlite", "db-before-migration-20260101T000000Z.sqlite", "db-backup-20260101T000000Z.sqlite", "db-backup-20260102T000000Z.sqlite", "db-backup-20260103T000000Z.sqlite"];
+        for file in files {
+            fs::write(directory.path().join(file), []).unwrap();
+        }
+
+        prune_backups(directory.path(), 1, &database_path).unwrap();
+        let mut remaining = fs::read_dir(directory.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        remaining.sort();
+        assert_eq!(remaining, vec!["db-backup-20260103T000000Z.sqlite", "db-before-migration-20260101T000000Z.sqlite", "db.sqlite"]);
+    }
+
+    #[test]
+    fn failed_backup_removes_target() {
+        let directory = tempfile::tempdir().unwrap();
+        let database_path = directory.path().join("garbage.sqlite");
+        let backup_path = directory.path().join("backup.sqlite");
+        fs::write(&database_path, [0x42; 4096]).unwrap();
+        assert!(backup_database(&database_path, &backup_path).is_err());
+        assert!(!backup_path.exists());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt::{Display, Formatter};
+
+use diesel::{QueryableByName, RunQueryDsl, SqliteConnection};
+use diesel::sql_types::{BigInt, Nullable, Text};
+
+use crate::bot::core::db::DatabaseError;
+
+#[derive(QueryableByName, Debug)]
+struct CountRow {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct IntegrityCheckRow {
+    #[diesel(sql_type = Text)]
+    integrity_check: String,
+}
+
+/// See https://www.sqlite.org/pragma.html#pragma_foreign_key_check
+#[derive(QueryableByName, Debug, Clone)]
+pub(crate) struct ForeignKeyViolation {
+    /// Table containing the foreign key
+    #[diesel(sql_type = Text)]
+    pub table: String,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub rowid: Option<i64>,
+    /// Table the foreign key refers to
+    #[diesel(sql_type = Text)]
+    pub parent: String,
+    #[diesel(sql_type = BigInt)]
+    pub fkid: i64,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct DatabaseCheckReport {
+    /// Output of `PRAGMA integrity_check`, a single "ok" row for a healthy database
+    pub integrity: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+}
+
+impl DatabaseCheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.integrity.iter().all(|row| row.eq("ok")) && self.foreign_key_violations.is_empty()
+    }
+}
+
+impl Display for DatabaseCheckReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "integrity_check:")?;
+        for row in &self.integrity {
+            writeln!(f, "  {}", row)?;
+        }
+        writeln!(f, "foreign_key_check: {} violation(s)", self.foreign_key_violations.len())?;
+        for violation in &self.foreign_key_violations {
+            writeln!(f, "  table={} rowid={:?} parent={} fkid={}", violation.table, violation.rowid, violation.parent, violation.fkid)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn check_database(connection: &mut SqliteConnection) -> Result<DatabaseCheckReport, DatabaseError> {
+    let integrity = diesel::sql_query("PRAGMA integrity_check;")
+        .load::<IntegrityCheckRow>(connection)
+        .map_err(|error| DatabaseError::Other(format!("integrity_check failed: {}", error)))?
+        .into_iter()
+        .map(|row| row.integrity_check)
+        .collect::<Vec<_>>();
+
+    let foreign_key_violations = diesel::sql_query("PRAGMA foreign_key_check;")
+        .load::<ForeignKeyViolation>(connection)
+        .map_err(|error| DatabaseError::Other(format!("foreign_key_check failed: {}", error)))?;
+
+    Ok(DatabaseCheckReport {
+        integrity,
+        foreign_key_violations,
+    })
+}
+
+/// Number of migrations recorded in the database, 0 if it was never migrated.
+/// Only reads, safe on a read-only connection.
+pub(crate) fn applied_migration_count(connection: &mut SqliteConnection) -> Result<i64, DatabaseError> {
+    let tables = diesel::sql_query("SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = '__diesel_schema_migrations';")
+        .get_result::<CountRow>(connection)
+        .map_err(|error| DatabaseError::Other(format!("Could not read the schema: {}", error)))?;
+    if tables.count == 0 {
+        return Ok(0);
+    }
+    let migrations = diesel::sql_query("SELECT COUNT(*) AS count FROM __diesel_schema_migrations;")
+        .get_result::<CountRow>(connection)
+        .map_err(|error| DatabaseError::Other(format!("Could not read the applied migrations: {}", error)))?;
+    Ok(migrations.count)
+}
@@ -1,140 +1,228 @@
-use diesel::{QueryDsl, RunQueryDsl, SelectableHelper, SqliteConnection};
+use diesel::{Connection, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
 use crate::bot::core::db::DatabaseError;
-use crate::bot::core::db::model::{NewTelegramAccount, NewUser, TelegramAccount, User};
-use crate::bot::core::db::schema::{telegram_accounts, users};
+use crate::bot::core::db::model::{ApiKey, NewApiKey, NewTelegramAccount, NewUser, TelegramAccount, User};
+use crate::bot::core::db::schema::{api_keys, telegram_accounts, users};
 use crate::bot::core::db::user_representation::{UserRepresentation, UserRole};
+use crate::bot::core::redaction::{TelegramId, Token};
 use crate::bot::core::util::random_start_token;
 use diesel::ExpressionMethods;
-use diesel::r2d2::ConnectionManager;
-use r2d2::PooledConnection;
 use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::db::connection::PooledDatabaseConnection;
+use crate::bot::core::repository::AccountRepository;
 
 pub trait DatabaseAdminClient {
+    async fn get_user(&self, user_name: &str) -> Result<UserRepresentation, DatabaseError>;
     async fn create_user(&self, user_name: &str, role: &UserRole) -> Result<UserRepresentation, DatabaseError>;
     async fn delete_user(&self, user_name: &str) -> Result<UserRepresentation, DatabaseError>;
-    async fn register_telegram_account_of_user(&mut self, start_token: &str, telegram_id: i64) -> Result<UserRepresentation, DatabaseError>;
+    /// Replace the start token, links with the old token stop working
+    async fn rotate_start_token(&self, user_name: &str) -> Result<UserRepresentation, DatabaseError>;
+    /// Link a telegram account without the start token, fails if the user or the account is already linked otherwise
+    async fn link_telegram_account(&mut self, user_name: &str, telegram_id: i64) -> Result<UserRepresentation, DatabaseError>;
+    async fn create_api_key(&self, name: &str, key_hash: &str) -> Result<ApiKey, DatabaseError>;
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>, DatabaseError>;
+    async fn delete_api_key(&self, name: &str) -> Result<ApiKey, DatabaseError>;
+    async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, DatabaseError>;
 }
 
 impl DatabaseAdminClient for DatabaseClient {
-    async fn create_user(&self, user_name: &str, role: &UserRole) -> Result<UserRepresentation, DatabaseError> {
-        let connection = &mut self.database.get().await
-            .map_err(|error| DatabaseError::Connection(format!("when creating user: {}", error)))?;
+    async fn get_user(&self, user_name: &str) -> Result<UserRepresentation, DatabaseError> {
+        let user_name = user_name.to_string();
+        let bot_name = self.bot_name.clone();
+        self.database.read(move |connection| Self::get_user_by_name(connection, &user_name, &bot_name)).await
+    }
 
-        let start_token = random_start_token();
-        let new_user = NewUser { name: user_name, start: &start_token, role: &role.to_string() };
-        diesel::insert_into(users::table)
-            .values(&new_user)
-            .returning(User::as_returning())
-            .get_result(connection)
-            .map(|user| {
-                let account = None;
-                UserRepresentation::from_user(&user, &account)
-            })
-            .map_err(|error| DatabaseError::CreateError(format!("Could not create user '{}'. {}", user_name, error)))
+    async fn create_user(&self, user_name: &str, role: &UserRole) -> Result<UserRepresentation, DatabaseError> {
+        let user_name = user_name.to_string();
+        let role = role.to_string();
+        let bot_name = self.bot_name.clone();
+        self.database.write(move |connection| {
+            let start_token = random_start_token();
+            let new_user = NewUser { name: &user_name, start: &start_token, role: &role };
+            // RETURNING is not available for all database backends, load the user after inserting instead
+            diesel::insert_into(users::table)
+                .values(&new_user)
+                .execute(connection)
+                .map_err(|error| DatabaseError::CreateError(format!("Could not create user '{}'. {}", user_name, error)))?;
+            Self::get_user_by_name(connection, &user_name, &bot_name)
+        }).await
     }
 
     async fn delete_user(&self, user_name: &str) -> Result<UserRepresentation, DatabaseError> {
-        let connection = &mut self.database.get().await
-            .map_err(|error| DatabaseError::Connection(format!("when deleting user: {}", error)))?;
-
-        let user = self.get_user_by_name(connection, user_name);
-        match user {
-            Ok(user) => {
+        let user_name = user_name.to_string();
+        let bot_name = self.bot_name.clone();
+        self.database.write(move |connection| {
+            // the account and the user are deleted together or not at all
+            connection.transaction(|connection| {
+                let user = Self::get_user_by_name(connection, &user_name, &bot_name)?;
                 if let Some(telegram_id) = user.telegram_id {
                     diesel::delete(telegram_accounts::table)
                         .filter(telegram_accounts::id.eq(&telegram_id))
                         .execute(connection)
                         .map_err(|error| DatabaseError::DeleteError(format!("Could not delete user '{}'. {}", user_name, error)))?;
                 };
-                let result = diesel::delete(users::table)
-                    .filter(users::name.eq(user_name))
+                let deleted = diesel::delete(users::table)
+                    .filter(users::name.eq(&user_name))
                     .execute(connection)
-                    .map_err(|error| DatabaseError::DeleteError(format!("Could not delete user '{}'. {}", user_name, error)));
-                match result {
-                    Ok(size) => {
-                        assert!(size.eq(&1));
-                        Ok(user)
-                    }
-                    Err(error) => {
-                        Err(error)
-                    }
+                    .map_err(|error| DatabaseError::DeleteError(format!("Could not delete user '{}'. {}", user_name, error)))?;
+                if deleted != 1 {
+                    return Err(DatabaseError::DeleteError(format!("Could not delete user '{}', {} rows were deleted.", user_name, deleted)));
                 }
-            }
-            Err(error) => {
-                Err(error)
-            }
-        }
-    }
-    async fn register_telegram_account_of_user(&mut self, start_token: &str, telegram_id: i64) -> Result<UserRepresentation, DatabaseError> {
-        match self.known_user(telegram_id) {
-            Some(user) => {
                 Ok(user)
+            })
+        }).await
+    }
+
+    async fn rotate_start_token(&self, user_name: &str) -> Result<UserRepresentation, DatabaseError> {
+        let user_name = user_name.to_string();
+        let bot_name = self.bot_name.clone();
+        self.database.write(move |connection| {
+            let start_token = random_start_token();
+            let updated = diesel::update(users::table)
+                .filter(users::name.eq(&user_name))
+                .set(users::start.eq(&start_token))
+                .execute(connection)
+                .map_err(|error| DatabaseError::Other(format!("Could not rotate start token of user '{}'. {}", user_name, error)))?;
+            if updated == 0 {
+                return Err(DatabaseError::UnknownUser(format!("Could not find user with name {}.", user_name)));
             }
-            None => {
-                let connection = &mut self.database.get().await
-                    .map_err(|error| DatabaseError::Connection(format!("while registering: {}", error)))?;
+            Self::get_user_by_name(connection, &user_name, &bot_name)
+        }).await
+    }
 
-                tracing::debug!("Telegram account does not exist yet, check if start token={} is present in user table. telegram id={}.", start_token, telegram_id);
-                let user = self.get_user_with_start_token(connection, start_token, telegram_id)?;
+    async fn link_telegram_account(&mut self, user_name: &str, telegram_id: i64) -> Result<UserRepresentation, DatabaseError> {
+        let user_name = user_name.to_string();
+        let bot_name = self.bot_name.clone();
+        self.database.write(move |connection| {
+            // both links are checked within the transaction of the insert
+            connection.transaction(|connection| {
+                let user = Self::get_user_by_name(connection, &user_name, &bot_name)?;
+                let linked_user_id = telegram_accounts::table
+                    .find(telegram_id)
+                    .select(telegram_accounts::user_id)
+                    .first::<i64>(connection)
+                    .optional()?;
+                match (linked_user_id, user.telegram_id) {
+                    (Some(linked_user_id), _) if linked_user_id == user.id => Ok(user),
+                    (Some(_), _) => Err(DatabaseError::CreateError(format!("Telegram id={} is already linked to another user.", TelegramId(telegram_id)))),
+                    (None, Some(present_telegram_id)) => Err(DatabaseError::CreateError(format!("User '{}' is already linked to telegram id={}.", user_name, TelegramId(present_telegram_id)))),
+                    (None, None) => {
+                        Self::insert_telegram_account(connection, user.id, telegram_id)?;
+                        Self::get_user_by_name(connection, &user_name, &bot_name)
+                    }
+                }
+            })
+        }).await
+    }
 
+    async fn create_api_key(&self, name: &str, key_hash: &str) -> Result<ApiKey, DatabaseError> {
+        let name = name.to_string();
+        let key_hash = key_hash.to_string();
+        self.database.write(move |connection| {
+            let new_key = NewApiKey { name: &name, key_hash: &key_hash, created_at: chrono::Utc::now().timestamp() };
+            diesel::insert_into(api_keys::table)
+                .values(&new_key)
+                .execute(connection)
+                .map_err(|error| DatabaseError::CreateError(format!("Could not create api key '{}'. {}", name, error)))?;
+            api_keys::table
+                .filter(api_keys::name.eq(&name))
+                .select(ApiKey::as_select())
+                .first(connection)
+                .map_err(|error| DatabaseError::Other(format!("Could not load api key '{}'. {}", name, error)))
+        }).await
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>, DatabaseError> {
+        self.database.read(|connection| {
+            api_keys::table
+                .order(api_keys::name)
+                .select(ApiKey::as_select())
+                .load(connection)
+                .map_err(|error| DatabaseError::Other(format!("Error loading api keys. {}", error)))
+        }).await
+    }
+
+    async fn delete_api_key(&self, name: &str) -> Result<ApiKey, DatabaseError> {
+        let name = name.to_string();
+        self.database.write(move |connection| {
+            let api_key = api_keys::table
+                .filter(api_keys::name.eq(&name))
+                .select(ApiKey::as_select())
+                .first(connection)
+                .map_err(|error| DatabaseError::Other(format!("Could not find api key '{}'. {}", name, error)))?;
+            diesel::delete(api_keys::table)
+                .filter(api_keys::id.eq(api_key.id))
+                .execute(connection)
+                .map_err(|error| DatabaseError::DeleteError(format!("Could not delete api key '{}'. {}", name, error)))?;
+            Ok(api_key)
+        }).await
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, DatabaseError> {
+        let key_hash = key_hash.to_string();
+        self.database.read(move |connection| {
+            api_keys::table
+                .filter(api_keys::key_hash.eq(&key_hash))
+                .select(ApiKey::as_select())
+                .first(connection)
+                .optional()
+                .map_err(|error| DatabaseError::Other(format!("Error loading api key. {}", error)))
+        }).await
+    }
+}
+
+impl AccountRepository for DatabaseClient {
+    async fn register_telegram_account_of_user(&mut self, start_token: &str, telegram_id: i64) -> Result<UserRepresentation, DatabaseError> {
+        let token = start_token.to_string();
+        let bot_name = self.bot_name.clone();
+        self.database.write(move |connection| {
+            // both links are checked within the transaction of the insert, concurrent start messages link a user once
+            connection.transaction(|connection| {
+                if let Some(user) = Self::get_user_by_telegram_id(connection, telegram_id, &bot_name)? {
+                    return Ok(user);
+                }
+                tracing::debug!("Telegram account does not exist yet, check if start token={} is present in user table. telegram id={}.", Token(&token), TelegramId(telegram_id));
+                let user = Self::get_user_with_start_token(connection, &token, telegram_id, &bot_name)?;
                 match user.telegram_id {
                     Some(present_telegram_id) => {
-                        if present_telegram_id.eq(&telegram_id) {
-                            // this case should not be possible because of the match above
-                            tracing::debug!("registered user has used the start token again. telegram id={}", telegram_id);
-                            Ok(user)
-                        } else {
-                            tracing::warn!("registered user has used the start token with a new telegram id, refusing to update/interact. telegram id={}", telegram_id);
-                            Err(DatabaseError::Other(format!("Telegram id={} does not match the found telegram id={}", telegram_id, present_telegram_id)))
-                        }
+                        tracing::warn!("registered user has used the start token with a new telegram id, refusing to update/interact. telegram id={}", TelegramId(telegram_id));
+                        Err(DatabaseError::Other(format!("Telegram id={} does not match the found telegram id={}", TelegramId(telegram_id), TelegramId(present_telegram_id))))
                     }
                     None => {
                         // received valid start token, creating new telegram account link
-                        let account = self.add_new_telegram_account(connection, start_token, user.id, telegram_id).await?;
-                        assert!(account.id.eq(&telegram_id), "Newly created telegram id should match.");
-                        Ok(self.known_user(telegram_id).expect("Newly created user should exist in hash map."))
+                        Self::insert_telegram_account(connection, user.id, telegram_id)?;
+                        Self::get_user_by_telegram_id(connection, telegram_id, &bot_name)?.ok_or_else(|| {
+                            DatabaseError::CreateError(format!("Telegram id={} is missing after registering it.", TelegramId(telegram_id)))
+                        })
                     }
                 }
-            }
-        }
+            })
+        }).await
     }
-
 }
 
 impl DatabaseClient {
-    async fn add_new_telegram_account(&mut self, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>, start_token: &str, user_id: i64, telegram_id: i64) -> Result<TelegramAccount, DatabaseError> {
+    pub(super) fn insert_telegram_account(connection: &mut PooledDatabaseConnection, user_id: i64, telegram_id: i64) -> Result<(), DatabaseError> {
         let new_account = NewTelegramAccount { id: &telegram_id, user_id: &user_id };
-        let result = diesel::insert_into(telegram_accounts::table)
+        let inserted = diesel::insert_into(telegram_accounts::table)
             .values(&new_account)
-            .returning(TelegramAccount::as_returning())
-            .get_result(connection)
-            .map_err(|error|
-                DatabaseError::CreateError(format!("Could not find token '{}' for telegram id '{}'. Error: {}", start_token, telegram_id, error))
-            );
-        match result {
-            Ok(account) => {
-                self.update_user_hash_map(connection).await
-                    .map_err(|error|
-                        DatabaseError::CreateError(format!("Could not update user hash map after creating tg_id={}. Error: {}", telegram_id, error))
-                    )?;
-                Ok(account)
-            }
-            Err(error) => {
-                Err(error)
-            }
+            .execute(connection)
+            .map_err(|error| DatabaseError::CreateError(format!("Could not create telegram account id={}. {}", TelegramId(telegram_id), error)))?;
+        if inserted != 1 {
+            return Err(DatabaseError::CreateError(format!("Could not create telegram account id={}, {} rows were inserted.", TelegramId(telegram_id), inserted)));
         }
+        Ok(())
     }
 
-    fn get_user_with_start_token(&self, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>, start_token: &str, telegram_id: i64) -> Result<UserRepresentation, DatabaseError> {
+    fn get_user_with_start_token(connection: &mut PooledDatabaseConnection, start_token: &str, telegram_id: i64, bot_name: &str) -> Result<UserRepresentation, DatabaseError> {
         users::table
             .filter(users::start.eq(start_token))
             .left_outer_join(telegram_accounts::table)
             .first::<(User, Option<TelegramAccount>)>(connection)
             .map(|(user, account)| {
-                UserRepresentation::from_user(&user, &account)
+                UserRepresentation::from_user(&user, &account, bot_name)
             })
             .map_err(|error|
-                DatabaseError::UnknownUser(format!("Could not find token '{}' for telegram id '{}'. Error: {}", start_token, telegram_id, error))
+                DatabaseError::UnknownUser(format!("Could not find token '{}' for telegram id '{}'. Error: {}", Token(start_token), TelegramId(telegram_id), error))
             )
     }
-}
\ No newline at end of file
+}
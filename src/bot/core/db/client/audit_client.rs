@@ -0,0 +1,52 @@
+use diesel::{QueryDsl, RunQueryDsl, SelectableHelper};
+use diesel::ExpressionMethods;
+
+use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::model::{AuditEvent, Broadcast, NewAuditEvent, NewBroadcast};
+use crate::bot::core::db::schema::{audit_log, broadcasts};
+use crate::bot::core::repository::AuditRepository;
+
+impl AuditRepository for DatabaseClient {
+    async fn record_audit_event(&self, event: NewAuditEvent) -> Result<(), DatabaseError> {
+        self.database.write(move |connection| {
+            diesel::insert_into(audit_log::table)
+                .values(&event)
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|error| DatabaseError::CreateError(format!("Could not record audit event {}. {}", event.action, error)))
+        }).await
+    }
+
+    async fn list_audit_events(&self, limit: i64) -> Result<Vec<AuditEvent>, DatabaseError> {
+        self.database.read(move |connection| {
+            audit_log::table
+                .order(audit_log::id.desc())
+                .limit(limit)
+                .select(AuditEvent::as_select())
+                .load(connection)
+                .map_err(|error| DatabaseError::Other(format!("Error loading audit log. {}", error)))
+        }).await
+    }
+
+    async fn record_broadcast(&self, broadcast: NewBroadcast) -> Result<(), DatabaseError> {
+        self.database.write(move |connection| {
+            diesel::insert_into(broadcasts::table)
+                .values(&broadcast)
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|error| DatabaseError::CreateError(format!("Could not record broadcast of {}. {}", broadcast.sender, error)))
+        }).await
+    }
+
+    async fn list_broadcasts(&self, limit: i64) -> Result<Vec<Broadcast>, DatabaseError> {
+        self.database.read(move |connection| {
+            broadcasts::table
+                .order(broadcasts::id.desc())
+                .limit(limit)
+                .select(Broadcast::as_select())
+                .load(connection)
+                .map_err(|error| DatabaseError::Other(format!("Error loading broadcasts. {}", error)))
+        }).await
+    }
+}
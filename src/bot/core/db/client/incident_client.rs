@@ -0,0 +1,31 @@
+use diesel::{QueryDsl, RunQueryDsl, SelectableHelper};
+use diesel::ExpressionMethods;
+
+use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::model::Incident;
+use crate::bot::core::db::schema::incidents;
+use crate::bot::core::repository::IncidentRepository;
+
+impl IncidentRepository for DatabaseClient {
+    async fn record_incident(&self, incident: Incident) -> Result<(), DatabaseError> {
+        self.database.write(move |connection| {
+            diesel::insert_into(incidents::table)
+                .values(&incident)
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|error| DatabaseError::CreateError(format!("Could not record incident {}. {}", incident.id, error)))
+        }).await
+    }
+
+    async fn list_incidents(&self, limit: i64) -> Result<Vec<Incident>, DatabaseError> {
+        self.database.read(move |connection| {
+            incidents::table
+                .order(incidents::created_at.desc())
+                .limit(limit)
+                .select(Incident::as_select())
+                .load(connection)
+                .map_err(|error| DatabaseError::Other(format!("Error loading incidents. {}", error)))
+        }).await
+    }
+}
@@ -1,72 +1,246 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
-
-use diesel::{QueryDsl, RunQueryDsl, SqliteConnection};
+use diesel::{OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
 use diesel::ExpressionMethods;
-use diesel::r2d2::ConnectionManager;
-use r2d2::PooledConnection;
 
-use crate::bot::core::db::connection::MyDatabaseConnection;
+use crate::bot::core::db::connection::{MyDatabaseConnection, PooledDatabaseConnection};
 use crate::bot::core::db::DatabaseError;
 use crate::bot::core::db::model::{TelegramAccount, User};
 use crate::bot::core::db::schema::{telegram_accounts, users};
 use crate::bot::core::db::user_representation::UserRepresentation;
+use crate::bot::core::redaction::TelegramId;
 
 pub mod admin_client;
 mod list_client;
+mod incident_client;
+mod audit_client;
+mod order_client;
+mod outbox_client;
 
+/// Users are not cached, replicas of the bot and the admin cli share the database.
 #[derive(Debug, Clone)]
 pub(crate) struct DatabaseClient {
-    /// Map of telegram user id to user representation
-    user_ids: Arc<RwLock<HashMap<i64, UserRepresentation>>>,
     database: MyDatabaseConnection,
+    /// Telegram username of the bot, used for start links
+    bot_name: String,
 }
 
 impl DatabaseClient {
-    pub(crate) async fn load(database: MyDatabaseConnection) -> Result<Self, anyhow::Error> {
-        let connection = &mut database.get().await?;
-        let user_ids = Default::default();
-
-        let mut client = Self {
-            user_ids,
+    /// Fails if the database is not reachable.
+    pub(crate) async fn load(database: MyDatabaseConnection, bot_name: &str) -> Result<Self, anyhow::Error> {
+        database.ping().await?;
+        Ok(Self {
             database,
-        };
-        client.update_user_hash_map(connection).await?;
-        Ok(client)
+            bot_name: bot_name.to_string(),
+        })
     }
 
-    async fn update_user_hash_map(&mut self, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>) -> anyhow::Result<()> {
-        let user_list = self.list_users_with_telegram_account(connection).await?;
-        tracing::debug!("Updating user hash map.");
-        let user_ids = self.user_ids.write();
-        match user_ids {
-            Ok(mut user_ids) => {
-                for user in user_list {
-                    let telegram_id = user.telegram_id.expect("Only existing telegram accounts were listed.");
-                    user_ids.insert(telegram_id, user);
-                }
-            }
-            Err(error) => {
-                tracing::error!("Failed to lock user hash map. {}", error);
-            }
-        }
-
-
-        Ok(())
-    }
-
-    fn get_user_by_name(&self, connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>, user_name: &str) -> Result<UserRepresentation, DatabaseError> {
+    fn get_user_by_name(connection: &mut PooledDatabaseConnection, user_name: &str, bot_name: &str) -> Result<UserRepresentation, DatabaseError> {
         users::table
             .filter(users::name.eq(user_name))
             .left_outer_join(telegram_accounts::table)
             .first::<(User, Option<TelegramAccount>)>(connection)
             .map(|(user, account)| {
-                UserRepresentation::from_user(&user, &account)
+                UserRepresentation::from_user(&user, &account, bot_name)
             })
             .map_err(|error|
                 DatabaseError::UnknownUser(format!("Could not find user with name {}. Error: {}", user_name, error))
             )
     }
 
+    fn get_user_by_telegram_id(connection: &mut PooledDatabaseConnection, telegram_id: i64, bot_name: &str) -> Result<Option<UserRepresentation>, DatabaseError> {
+        Ok(telegram_accounts::table
+            .find(telegram_id)
+            .inner_join(users::table)
+            .select((TelegramAccount::as_select(), User::as_select()))
+            .first::<(TelegramAccount, User)>(connection)
+            .optional()
+            .map_err(|error| DatabaseError::Other(format!("Error loading user of telegram id={}. {}", TelegramId(telegram_id), error)))?
+            .map(|(account, user)| UserRepresentation::from_user(&user, &Some(account), bot_name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
+    use crate::bot::core::db::client::DatabaseClient;
+    use crate::bot::core::db::DatabaseError;
+    use crate::bot::core::db::migration;
+    use crate::bot::core::db::model::{NewOrder, NewOutboxEntry, OutboxAttempt};
+    use crate::bot::core::db::test_database::{backend_tests, TestDatabase};
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::repository::{AccountRepository, OrderRepository, OutboxRepository, UserRepository};
+
+    async fn client(database: &TestDatabase) -> DatabaseClient {
+        DatabaseClient::load(database.connection.clone(), "testbot").await.unwrap()
+    }
+
+    fn outbox_entry(url: &str) -> NewOutboxEntry {
+        NewOutboxEntry {
+            created_at: 100,
+            event_id: "event".to_string(),
+            event_type: "user_registered".to_string(),
+            url: url.to_string(),
+            payload: "{}".to_string(),
+            next_attempt_at: Some(100),
+        }
+    }
+
+    backend_tests! {
+        async fn registers_user_with_start_token(database: TestDatabase) {
+            let mut client = client(&database).await;
+            let user = client.create_user("alice", &UserRole::Admin).await.unwrap();
+            assert!(!client.known_user_exists(42).await.unwrap());
+
+            let registered = client.register_telegram_account_of_user(&user.start_token, 42).await.unwrap();
+            assert_eq!(registered.telegram_id, Some(42));
+            assert!(client.known_admin_user_exists(42).await.unwrap());
+            // a reloaded client knows the account from the database
+            let reloaded = DatabaseClient::load(database.connection.clone(), "testbot").await.unwrap();
+            assert_eq!(reloaded.list_registered_users().await.unwrap().len(), 1);
+            assert!(client.register_telegram_account_of_user(&user.start_token, 43).await.is_err());
+        }
+
+        async fn deletes_user_with_telegram_account(database: TestDatabase) {
+            let mut client = client(&database).await;
+            client.create_user("bob", &UserRole::User).await.unwrap();
+            client.link_telegram_account("bob", 7).await.unwrap();
+            assert!(client.known_user_exists(7).await.unwrap());
+
+            let deleted = client.delete_user("bob").await.unwrap();
+            assert_eq!(deleted.telegram_id, Some(7));
+            assert!(!client.known_user_exists(7).await.unwrap());
+            assert!(matches!(client.get_user("bob").await, Err(DatabaseError::UnknownUser(_))));
+            assert!(matches!(client.delete_user("bob").await, Err(DatabaseError::UnknownUser(_))));
+        }
+
+        async fn links_telegram_account_once(database: TestDatabase) {
+            let mut client = client(&database).await;
+            client.create_user("erin", &UserRole::User).await.unwrap();
+            client.create_user("frank", &UserRole::User).await.unwrap();
+            assert_eq!(client.link_telegram_account("erin", 8).await.unwrap().telegram_id, Some(8));
+            assert_eq!(client.link_telegram_account("erin", 8).await.unwrap().telegram_id, Some(8));
 
+            assert!(matches!(client.link_telegram_account("frank", 8).await, Err(DatabaseError::CreateError(_))));
+            assert!(matches!(client.link_telegram_account("erin", 9).await, Err(DatabaseError::CreateError(_))));
+            assert!(matches!(client.link_telegram_account("nobody", 10).await, Err(DatabaseError::UnknownUser(_))));
+            assert_eq!(client.get_user("frank").await.unwrap().telegram_id, None);
+            assert!(!client.known_user_exists(9).await.unwrap());
+        }
+
+        async fn links_start_token_once_for_concurrent_registrations(database: TestDatabase) {
+            let client = client(&database).await;
+            let user = client.create_user("heidi", &UserRole::User).await.unwrap();
+            let registrations = (20..30)
+                .map(|telegram_id| {
+                    let mut client = client.clone();
+                    let start_token = user.start_token.clone();
+                    tokio::spawn(async move { client.register_telegram_account_of_user(&start_token, telegram_id).await })
+                })
+                .collect::<Vec<_>>();
+            let mut linked = vec![];
+            for registration in registrations {
+                if let Ok(user) = registration.await.unwrap() {
+                    linked.push(user.telegram_id);
+                }
+            }
+            assert_eq!(linked.len(), 1);
+            assert_eq!(client.get_user("heidi").await.unwrap().telegram_id, linked[0]);
+            assert_eq!(client.list_telegram_accounts().await.unwrap().len(), 1);
+
+            // the database rejects a second account of the user as well
+            let user_id = user.id;
+            let inserted = database.connection.write(move |connection| DatabaseClient::insert_telegram_account(connection, user_id, 31)).await;
+            assert!(matches!(inserted, Err(DatabaseError::CreateError(_))));
+        }
+
+        async fn keeps_one_account_per_user_when_adding_the_unique_index(database: TestDatabase) {
+            let client = client(&database).await;
+            let user = client.create_user("ivan", &UserRole::User).await.unwrap();
+            let database_path = database.connection.database_path.clone();
+            {
+                let connection = &mut database.connection.pool.get().unwrap();
+                assert_eq!(migration::revert_last_migration(connection, &database_path).unwrap(), "20261019170000");
+            }
+            // linked twice before the index, e.g. by concurrent registrations
+            for telegram_id in [13, 12] {
+                let user_id = user.id;
+                database.connection.write(move |connection| DatabaseClient::insert_telegram_account(connection, user_id, telegram_id)).await.unwrap();
+            }
+            {
+                let connection = &mut database.connection.pool.get().unwrap();
+                migration::run_migrations(connection, &database_path).unwrap();
+            }
+            let accounts = client.list_telegram_accounts().await.unwrap();
+            assert_eq!(accounts.iter().map(|account| account.id).collect::<Vec<_>>(), vec![12]);
+        }
+
+        async fn sees_users_changed_by_another_client(database: TestDatabase) {
+            // e.g. a second replica of the bot or the admin cli
+            let bot = client(&database).await;
+            let mut admin = client(&database).await;
+            let user = admin.create_user("grace", &UserRole::User).await.unwrap();
+            admin.link_telegram_account("grace", 11).await.unwrap();
+            assert_eq!(bot.known_user(11).await.unwrap().map(|user| user.name), Some("grace".to_string()));
+            assert!(!bot.known_admin_user_exists(11).await.unwrap());
+
+            admin.delete_user("grace").await.unwrap();
+            assert!(!bot.known_user_exists(11).await.unwrap());
+            // the start token of the deleted user is gone as well
+            assert!(matches!(bot.clone().register_telegram_account_of_user(&user.start_token, 11).await, Err(DatabaseError::UnknownUser(_))));
+        }
+
+        async fn rejects_duplicate_user_names(database: TestDatabase) {
+            let client = client(&database).await;
+            client.create_user("carol", &UserRole::User).await.unwrap();
+            assert!(matches!(client.create_user("carol", &UserRole::User).await, Err(DatabaseError::CreateError(_))));
+        }
+
+        async fn lists_orders_of_user(database: TestDatabase) {
+            let client = client(&database).await;
+            for (created_at, telegram_id) in [(1, 5), (2, 5), (3, 6)] {
+                client.record_order(NewOrder {
+                    created_at,
+                    telegram_id,
+                    full_name: "Dave".to_string(),
+                    product: "Apple".to_string(),
+                }).await.unwrap();
+            }
+            let orders = client.list_orders(5, 10).await.unwrap();
+            assert_eq!(orders.iter().map(|order| order.created_at).collect::<Vec<_>>(), vec![2, 1]);
+        }
+
+        async fn delivers_outbox_entries(database: TestDatabase) {
+            let client = client(&database).await;
+            let (a, b) = ("http://a.example", "http://b.example");
+            client.enqueue_outbox_entries(vec![outbox_entry(a), outbox_entry(b), outbox_entry(a)]).await.unwrap();
+            assert_eq!(client.pending_outbox_urls().await.unwrap(), vec![a, b]);
+            let due_of_a = client.due_outbox_entries(a, 100, 10).await.unwrap();
+            assert_eq!(due_of_a.len(), 2);
+            assert!(due_of_a[0].id < due_of_a[1].id);
+            let due_of_b = client.due_outbox_entries(b, 100, 10).await.unwrap();
+            assert_eq!(due_of_b.len(), 1);
+
+            client.record_outbox_attempt(due_of_b[0].id, OutboxAttempt {
+                attempts: 1,
+                next_attempt_at: None,
+                delivered_at: Some(101),
+                last_error: None,
+            }).await.unwrap();
+            client.record_outbox_attempt(due_of_a[0].id, OutboxAttempt {
+                attempts: 1,
+                next_attempt_at: Some(200),
+                delivered_at: None,
+                last_error: Some("timeout".to_string()),
+            }).await.unwrap();
+            assert_eq!(client.pending_outbox_urls().await.unwrap(), vec![a]);
+            // the second entry waits for the retry of the first one
+            assert!(client.due_outbox_entries(a, 150, 10).await.unwrap().is_empty());
+            let retried = client.due_outbox_entries(a, 200, 10).await.unwrap();
+            assert_eq!(retried.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![due_of_a[0].id, due_of_a[1].id]);
+            assert_eq!(retried[0].last_error.as_deref(), Some("timeout"));
+            assert_eq!(client.due_outbox_entries(a, 200, 1).await.unwrap().len(), 1);
+
+            // pending entries are kept
+            assert_eq!(client.prune_outbox(1000).await.unwrap(), 1);
+        }
+    }
 }
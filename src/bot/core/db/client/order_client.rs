@@ -0,0 +1,33 @@
+use diesel::{QueryDsl, RunQueryDsl, SelectableHelper};
+use diesel::ExpressionMethods;
+
+use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::model::{NewOrder, Order};
+use crate::bot::core::db::schema::orders;
+use crate::bot::core::redaction::TelegramId;
+use crate::bot::core::repository::OrderRepository;
+
+impl OrderRepository for DatabaseClient {
+    async fn record_order(&self, order: NewOrder) -> Result<(), DatabaseError> {
+        self.database.write(move |connection| {
+            diesel::insert_into(orders::table)
+                .values(&order)
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|error| DatabaseError::CreateError(format!("Could not record order of {}. {}", TelegramId(order.telegram_id), error)))
+        }).await
+    }
+
+    async fn list_orders(&self, telegram_id: i64, limit: i64) -> Result<Vec<Order>, DatabaseError> {
+        self.database.read(move |connection| {
+            orders::table
+                .filter(orders::telegram_id.eq(telegram_id))
+                .order(orders::id.desc())
+                .limit(limit)
+                .select(Order::as_select())
+                .load(connection)
+                .map_err(|error| DatabaseError::Other(format!("Error loading orders of {}. {}", TelegramId(telegram_id), error)))
+        }).await
+    }
+}
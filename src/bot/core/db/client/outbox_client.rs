@@ -0,0 +1,72 @@
+use diesel::{BoolExpressionMethods, Connection, QueryDsl, RunQueryDsl, SelectableHelper};
+use diesel::ExpressionMethods;
+
+use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::model::{NewOutboxEntry, OutboxAttempt, OutboxEntry};
+use crate::bot::core::db::schema::event_outbox;
+use crate::bot::core::repository::OutboxRepository;
+
+impl OutboxRepository for DatabaseClient {
+    async fn enqueue_outbox_entries(&self, entries: Vec<NewOutboxEntry>) -> Result<(), DatabaseError> {
+        self.database.write(move |connection| {
+            // one entry per url, batch inserts are not available for all database backends
+            connection.transaction(|connection| {
+                for entry in &entries {
+                    diesel::insert_into(event_outbox::table)
+                        .values(entry)
+                        .execute(connection)?;
+                }
+                Ok(())
+            }).map_err(|error: diesel::result::Error| DatabaseError::CreateError(format!("Could not add events to the outbox. {}", error)))
+        }).await
+    }
+
+    async fn pending_outbox_urls(&self) -> Result<Vec<String>, DatabaseError> {
+        self.database.read(move |connection| {
+            event_outbox::table
+                .filter(event_outbox::next_attempt_at.is_not_null())
+                .select(event_outbox::url)
+                .distinct()
+                .order(event_outbox::url.asc())
+                .load(connection)
+                .map_err(|error| DatabaseError::Other(format!("Error loading the urls of the event outbox. {}", error)))
+        }).await
+    }
+
+    async fn due_outbox_entries(&self, url: &str, now: i64, limit: i64) -> Result<Vec<OutboxEntry>, DatabaseError> {
+        let url = url.to_string();
+        let entries: Vec<OutboxEntry> = self.database.read(move |connection| {
+            event_outbox::table
+                .filter(event_outbox::url.eq(url))
+                .filter(event_outbox::next_attempt_at.is_not_null())
+                .order(event_outbox::id.asc())
+                .limit(limit)
+                .select(OutboxEntry::as_select())
+                .load(connection)
+                .map_err(|error| DatabaseError::Other(format!("Error loading the event outbox. {}", error)))
+        }).await?;
+        Ok(entries.into_iter()
+            .take_while(|entry| entry.next_attempt_at.is_some_and(|next_attempt_at| next_attempt_at <= now))
+            .collect())
+    }
+
+    async fn record_outbox_attempt(&self, id: i64, attempt: OutboxAttempt) -> Result<(), DatabaseError> {
+        self.database.write(move |connection| {
+            diesel::update(event_outbox::table.find(id))
+                .set(&attempt)
+                .execute(connection)
+                .map(|_| ())
+                .map_err(|error| DatabaseError::Other(format!("Could not record delivery attempt of outbox entry {}. {}", id, error)))
+        }).await
+    }
+
+    async fn prune_outbox(&self, created_before: i64) -> Result<usize, DatabaseError> {
+        self.database.write(move |connection| {
+            diesel::delete(event_outbox::table)
+                .filter(event_outbox::next_attempt_at.is_null().and(event_outbox::created_at.lt(created_before)))
+                .execute(connection)
+                .map_err(|error| DatabaseError::DeleteError(format!("Could not prune the event outbox. {}", error)))
+        }).await
+    }
+}
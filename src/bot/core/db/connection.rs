@@ -1,31 +1,72 @@
-use std::error::Error;
-use std::time::Duration;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
+use diesel::Connection;
+use diesel::r2d2::R2D2Connection;
+use r2d2::{Pool, PooledConnection};
+use tracing::Instrument;
+
+#[cfg(feature = "sqlite")]
 use diesel::connection::SimpleConnection;
-use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+#[cfg(feature = "postgres")]
+use diesel::PgConnection;
+#[cfg(feature = "sqlite")]
 use diesel::SqliteConnection;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
 use crate::bot::core::bot_config::BotConfig;
+use crate::bot::core::bot_config::storage::DatabaseBackend;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::migration;
+use crate::bot::core::metrics::metrics;
+
+/// Readers do not block each other or the writer in WAL mode
+const SQLITE_READ_POOL_SIZE: u32 = 4;
+const POSTGRES_POOL_SIZE: u32 = 10;
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/");
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("Enable at least one database backend feature: sqlite, postgres");
 
-fn run_migrations(connection: &mut SqliteConnection) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-    connection.run_pending_migrations(MIGRATIONS)?;
-    Ok(())
+/// Connection to one of the database backends enabled in this build.
+#[derive(diesel::MultiConnection)]
+pub enum AnyConnection {
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteConnection),
+    #[cfg(feature = "postgres")]
+    Postgresql(PgConnection),
 }
 
+pub type PooledDatabaseConnection = PooledConnection<DatabaseConnectionManager>;
+
 #[derive(Debug)]
 pub struct ConnectionOptions {
     pub enable_wal: bool,
     pub enable_foreign_keys: bool,
     pub busy_timeout: Option<Duration>,
+    /// Reject writes on this connection
+    pub query_only: bool,
 }
 
-impl r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
+impl r2d2::CustomizeConnection<AnyConnection, diesel::r2d2::Error>
 for ConnectionOptions
 {
-    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+    fn on_acquire(&self, conn: &mut AnyConnection) -> Result<(), diesel::r2d2::Error> {
+        match conn {
+            #[cfg(feature = "sqlite")]
+            AnyConnection::Sqlite(conn) => {
+                self.customize_sqlite(conn)
+            }
+            #[cfg(feature = "postgres")]
+            AnyConnection::Postgresql(_conn) => {
+                // postgres enforces foreign keys and handles concurrent writers on its own
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ConnectionOptions {
+    #[cfg(feature = "sqlite")]
+    fn customize_sqlite(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
         (|| {
             if self.enable_wal {
                 conn.batch_execute("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
@@ -36,46 +77,257 @@ for ConnectionOptions
             if let Some(d) = self.busy_timeout {
                 conn.batch_execute(&format!("PRAGMA busy_timeout = {};", d.as_millis()))?;
             }
+            if self.query_only {
+                conn.batch_execute("PRAGMA query_only = ON;")?;
+            }
             Ok(())
         })()
             .map_err(diesel::r2d2::Error::QueryError)
     }
 }
 
+/// Establishes connections to the configured backend only.
+/// The derived `AnyConnection::establish` tries every backend in turn and hides the actual connection error.
+#[derive(Debug, Clone)]
+pub struct DatabaseConnectionManager {
+    backend: DatabaseBackend,
+    database_url: String,
+}
+
+impl r2d2::ManageConnection for DatabaseConnectionManager {
+    type Connection = AnyConnection;
+    type Error = diesel::r2d2::Error;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        match self.backend {
+            #[cfg(feature = "sqlite")]
+            DatabaseBackend::Sqlite => {
+                SqliteConnection::establish(&self.database_url)
+                    .map(AnyConnection::Sqlite)
+                    .map_err(diesel::r2d2::Error::ConnectionError)
+            }
+            #[cfg(feature = "postgres")]
+            DatabaseBackend::Postgres => {
+                PgConnection::establish(&self.database_url)
+                    .map(AnyConnection::Postgresql)
+                    .map_err(diesel::r2d2::Error::ConnectionError)
+            }
+            #[allow(unreachable_patterns)]
+            backend => {
+                Err(diesel::r2d2::Error::ConnectionError(diesel::ConnectionError::BadConnection(
+                    format!("Database backend {:?} is not enabled in this build, check the cargo features.", backend)
+                )))
+            }
+        }
+    }
+
+    fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        conn.ping().map_err(diesel::r2d2::Error::QueryError)
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        std::thread::panicking() || conn.is_broken()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MyDatabaseConnection {
-    pub pool: Pool<ConnectionManager<SqliteConnection>>,
+    /// Pool for writing, sqlite allows a single writer at a time
+    pub pool: Pool<DatabaseConnectionManager>,
+    /// Pool for reading, shares the connections of `pool` for postgres
+    pub read_pool: Pool<DatabaseConnectionManager>,
+    /// Location of the sqlite database file, unused for other backends
+    pub database_path: PathBuf,
 }
 
 impl MyDatabaseConnection {
+    /// Open the database and apply pending migrations.
     pub async fn new() -> Result<Self, anyhow::Error> {
+        let database = Self::open().await?;
+        let connection = &mut database.get().await?;
+        let migrated = migration::run_migrations(connection, &database.database_path)?;
+        if !migrated.is_empty() {
+            tracing::info!("Applied migrations: {}", migrated.join(", "));
+        }
+        Ok(database)
+    }
+
+    /// Open the database without touching the schema.
+    pub async fn open() -> Result<Self, anyhow::Error> {
         let bot_config = BotConfig::new()?;
-        let database_exists = bot_config.storage.database_path().exists();
+        let backend = bot_config.storage.database_backend();
+        let database_path = bot_config.storage.database_path();
         let database_url = bot_config.storage.database_url()?;
-        tracing::info!("Opening database at url={}", database_url);
-
-        // https://stackoverflow.com/questions/57123453/how-to-use-diesel-with-sqlite-connections-and-avoid-database-is-locked-type-of
-        let pool = Pool::builder()
-            .max_size(1)  // Only one connection at a time
-            .connection_customizer(Box::new(ConnectionOptions {
-                enable_wal: true,
-                enable_foreign_keys: true,
-                busy_timeout: Some(Duration::from_secs(30)),
-            }))
-            .build(ConnectionManager::<SqliteConnection>::new(database_url))?;
-
-        if !database_exists {
-            let mut connection = pool.get()?;
-            log::info!("Running migrations for new database ...");
-            run_migrations(&mut connection).expect("Failed to run migrations!");
-        }
+        Self::connect(backend, database_url, database_path)
+    }
+
+    /// Create the connection pools of the given backend.
+    pub(crate) fn connect(backend: DatabaseBackend, database_url: String, database_path: PathBuf) -> Result<Self, anyhow::Error> {
+        let manager = DatabaseConnectionManager { backend, database_url };
+
+        let (pool, read_pool) = match backend {
+            DatabaseBackend::Sqlite => {
+                tracing::info!("Opening sqlite database at path={:?}", database_path);
+                // https://stackoverflow.com/questions/57123453/how-to-use-diesel-with-sqlite-connections-and-avoid-database-is-locked-type-of
+                let pool = Pool::builder()
+                    .max_size(1)  // Only one writing connection at a time
+                    .connection_customizer(Box::new(ConnectionOptions {
+                        enable_wal: true,
+                        enable_foreign_keys: true,
+                        busy_timeout: Some(Duration::from_secs(30)),
+                        query_only: false,
+                    }))
+                    .build(manager.clone())?;
+                // the journal mode is persisted in the database file by the writing connection
+                let read_pool = Pool::builder()
+                    .max_size(SQLITE_READ_POOL_SIZE)
+                    .connection_customizer(Box::new(ConnectionOptions {
+                        enable_wal: false,
+                        enable_foreign_keys: true,
+                        busy_timeout: Some(Duration::from_secs(30)),
+                        query_only: true,
+                    }))
+                    .build(manager)?;
+                (pool, read_pool)
+            }
+            DatabaseBackend::Postgres => {
+                tracing::info!("Opening postgres database");
+                let pool = Pool::builder()
+                    .max_size(POSTGRES_POOL_SIZE)
+                    .build(manager)?;
+                (pool.clone(), pool)
+            }
+        };
 
         Ok(Self {
-            pool
+            pool,
+            read_pool,
+            database_path,
         })
     }
 
-    pub async fn get(&self) -> Result<PooledConnection<ConnectionManager<SqliteConnection>>, r2d2::Error> {
-        self.pool.get()
+    /// Get a writing connection, waits on the blocking thread pool until one is available.
+    /// Prefer [`Self::read`] and [`Self::write`], queries on the returned connection block the current thread.
+    pub async fn get(&self) -> Result<PooledDatabaseConnection, DatabaseError> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || pool.get())
+            .await
+            .map_err(|error| DatabaseError::Other(format!("Database task failed: {}", error)))?
+            .map_err(|error| DatabaseError::Connection(error.to_string()))
+    }
+
+    /// Run blocking read-only database work on the blocking thread pool.
+    pub async fn read<F, T>(&self, task: F) -> Result<T, DatabaseError>
+    where
+        F: FnOnce(&mut PooledDatabaseConnection) -> Result<T, DatabaseError> + Send + 'static,
+        T: Send + 'static,
+    {
+        Self::run_blocking(self.read_pool.clone(), "read", task).await
+    }
+
+    /// Run blocking database work that modifies data on the blocking thread pool.
+    pub async fn write<F, T>(&self, task: F) -> Result<T, DatabaseError>
+    where
+        F: FnOnce(&mut PooledDatabaseConnection) -> Result<T, DatabaseError> + Send + 'static,
+        T: Send + 'static,
+    {
+        Self::run_blocking(self.pool.clone(), "write", task).await
+    }
+
+    /// Round-trip to the database, used by the readiness endpoint.
+    pub async fn ping(&self) -> Result<(), DatabaseError> {
+        self.read(|connection| {
+            connection.ping().map_err(|error| DatabaseError::Other(error.to_string()))
+        }).await
+    }
+
+    /// Write the WAL of sqlite into the database file on shutdown, the pools close their connections when dropped.
+    pub async fn close(self) -> Result<(), DatabaseError> {
+        self.write(|connection| {
+            match &mut **connection {
+                #[cfg(feature = "sqlite")]
+                AnyConnection::Sqlite(connection) => {
+                    connection.batch_execute("PRAGMA wal_checkpoint(TRUNCATE);")
+                        .map_err(|error| DatabaseError::Other(format!("WAL checkpoint failed. {}", error)))?;
+                    tracing::info!("Closed sqlite database after a WAL checkpoint");
+                }
+                #[cfg(feature = "postgres")]
+                AnyConnection::Postgresql(_) => {}
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Waiting for a pooled connection and sqlite's busy timeout must not stall the async runtime.
+    /// Runs within a `database` span, a child of the `update` span when called by a handler.
+    async fn run_blocking<F, T>(pool: Pool<DatabaseConnectionManager>, pool_name: &'static str, task: F) -> Result<T, DatabaseError>
+    where
+        F: FnOnce(&mut PooledDatabaseConnection) -> Result<T, DatabaseError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let span = tracing::info_span!("database", pool = pool_name, otel.status_code = tracing::field::Empty);
+        let start = Instant::now();
+        let blocking_span = span.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let _entered = blocking_span.enter();
+            let connection = &mut pool.get()
+                .map_err(|error| DatabaseError::Connection(error.to_string()))?;
+            task(connection)
+        })
+            .instrument(span.clone())
+            .await
+            .map_err(|error| DatabaseError::Other(format!("Database task failed: {}", error)))?;
+        metrics().record_database_query(pool_name, start.elapsed());
+        if result.is_err() {
+            span.record("otel.status_code", "ERROR");
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use diesel::connection::SimpleConnection;
+
+    use crate::bot::core::bot_config::storage::DatabaseBackend;
+    use crate::bot::core::db::client::DatabaseClient;
+    use crate::bot::core::db::DatabaseError;
+    use crate::bot::core::db::test_database::TestDatabase;
+    use crate::bot::core::repository::UserRepository;
+
+    const LONG_WRITE: Duration = Duration::from_millis(1500);
+
+    /// A single runtime thread like a busy bot, waiting for the writer must not stall other updates.
+    #[tokio::test(flavor = "current_thread")]
+    async fn handlers_stay_responsive_during_long_write() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let client = DatabaseClient::load(database.connection.clone(), "testbot").await.unwrap();
+
+        let writer = database.connection.clone();
+        let long_write = tokio::spawn(async move {
+            writer.write(|connection| {
+                connection.batch_execute("BEGIN IMMEDIATE;")
+                    .map_err(|error| DatabaseError::Other(error.to_string()))?;
+                std::thread::sleep(LONG_WRITE);
+                connection.batch_execute("COMMIT;")
+                    .map_err(|error| DatabaseError::Other(error.to_string()))
+            }).await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // waits for the connection held by the long write
+        let waiting = database.connection.clone();
+        let waiting_get = tokio::spawn(async move { waiting.get().await.map(|_connection| ()) });
+
+        let start = Instant::now();
+        for _ in 0..50 {
+            client.list_registered_users().await.unwrap();
+        }
+        assert!(start.elapsed() < LONG_WRITE / 2, "reads took {:?}", start.elapsed());
+        assert!(!long_write.is_finished());
+
+        long_write.await.unwrap().unwrap();
+        waiting_get.await.unwrap().unwrap();
     }
 }
@@ -0,0 +1,158 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::anyhow;
+use diesel::backend::Backend;
+use diesel::migration::MigrationSource;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+#[cfg(feature = "sqlite")]
+use crate::bot::core::db::backup::{backup_database, timestamped_backup_path};
+use crate::bot::core::db::connection::AnyConnection;
+
+#[cfg(feature = "sqlite")]
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
+#[cfg(feature = "postgres")]
+const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
+
+#[derive(Debug, Clone)]
+pub(crate) struct MigrationStatus {
+    /// Migrations shipped with this binary and applied to the database
+    pub applied: Vec<String>,
+    /// Migrations shipped with this binary but not yet applied
+    pub pending: Vec<String>,
+    /// Migrations applied to the database but unknown to this binary
+    pub unknown: Vec<String>,
+}
+
+impl MigrationStatus {
+    /// The database was migrated by a newer version of the bot.
+    pub fn is_schema_newer(&self) -> bool {
+        !self.unknown.is_empty()
+    }
+}
+
+impl Display for MigrationStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for version in &self.applied {
+            writeln!(f, "[X] {}", version)?;
+        }
+        for version in &self.pending {
+            writeln!(f, "[ ] {}", version)?;
+        }
+        for version in &self.unknown {
+            writeln!(f, "[?] {} (unknown to this binary)", version)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn migration_status(connection: &mut AnyConnection) -> Result<MigrationStatus, anyhow::Error> {
+    match connection {
+        #[cfg(feature = "sqlite")]
+        AnyConnection::Sqlite(connection) => backend_migration_status(connection, SQLITE_MIGRATIONS),
+        #[cfg(feature = "postgres")]
+        AnyConnection::Postgresql(connection) => backend_migration_status(connection, POSTGRES_MIGRATIONS),
+    }
+}
+
+/// Apply all pending migrations. Existing sqlite databases are backed up first.
+/// Refuses to touch a database whose schema is newer than this binary.
+pub(crate) fn run_migrations(connection: &mut AnyConnection, database_path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let status = migration_status(connection)?;
+    if status.is_schema_newer() {
+        return Err(anyhow!("Database schema is newer than this binary, refusing to start. Unknown migrations: {}", status.unknown.join(", ")));
+    }
+    if status.pending.is_empty() {
+        tracing::debug!("Database schema is up to date.");
+        return Ok(vec![]);
+    }
+
+    if !status.applied.is_empty() {
+        backup_before_schema_change(connection, database_path, "db-before-migration-")?;
+    }
+    tracing::info!("Running pending migrations: {}", status.pending.join(", "));
+    let migrated = match connection {
+        #[cfg(feature = "sqlite")]
+        AnyConnection::Sqlite(connection) => connection.run_pending_migrations(SQLITE_MIGRATIONS)
+            .map(|versions| versions.iter().map(|version| version.to_string()).collect::<Vec<_>>()),
+        #[cfg(feature = "postgres")]
+        AnyConnection::Postgresql(connection) => connection.run_pending_migrations(POSTGRES_MIGRATIONS)
+            .map(|versions| versions.iter().map(|version| version.to_string()).collect::<Vec<_>>()),
+    }.map_err(|error| anyhow!("Failed to run migrations: {}", error))?;
+    Ok(migrated)
+}
+
+/// Revert the most recently applied migration after backing up the database.
+pub(crate) fn revert_last_migration(connection: &mut AnyConnection, database_path: &Path) -> Result<String, anyhow::Error> {
+    let status = migration_status(connection)?;
+    if status.is_schema_newer() {
+        return Err(anyhow!("Cannot revert migrations unknown to this binary: {}", status.unknown.join(", ")));
+    }
+    if status.applied.is_empty() {
+        return Err(anyhow!("No migration applied, nothing to revert."));
+    }
+
+    backup_before_schema_change(connection, database_path, "db-before-rollback-")?;
+    let version = match connection {
+        #[cfg(feature = "sqlite")]
+        AnyConnection::Sqlite(connection) => connection.revert_last_migration(SQLITE_MIGRATIONS)
+            .map(|version| version.to_string()),
+        #[cfg(feature = "postgres")]
+        AnyConnection::Postgresql(connection) => connection.revert_last_migration(POSTGRES_MIGRATIONS)
+            .map(|version| version.to_string()),
+    }.map_err(|error| anyhow!("Failed to revert migration: {}", error))?;
+    Ok(version)
+}
+
+fn backend_migration_status<DB: Backend>(connection: &mut impl MigrationHarness<DB>, migrations: EmbeddedMigrations) -> Result<MigrationStatus, anyhow::Error> {
+    let known = MigrationSource::<DB>::migrations(&migrations)
+        .map_err(|error| anyhow!("Could not load embedded migrations: {}", error))?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .collect::<Vec<_>>();
+    let applied_in_database = connection.applied_migrations()
+        .map_err(|error| anyhow!("Could not load applied migrations: {}", error))?
+        .iter()
+        .map(|version| version.to_string())
+        .collect::<Vec<_>>();
+
+    let applied = known.iter()
+        .filter(|version| applied_in_database.contains(version))
+        .cloned()
+        .collect::<Vec<_>>();
+    let pending = known.iter()
+        .filter(|version| !applied_in_database.contains(version))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut unknown = applied_in_database.iter()
+        .filter(|version| !known.contains(version))
+        .cloned()
+        .collect::<Vec<_>>();
+    unknown.sort();
+
+    Ok(MigrationStatus {
+        applied,
+        pending,
+        unknown,
+    })
+}
+
+fn backup_before_schema_change(connection: &AnyConnection, database_path: &Path, prefix: &str) -> Result<(), anyhow::Error> {
+    match connection {
+        #[cfg(feature = "sqlite")]
+        AnyConnection::Sqlite(_) => {
+            let directory = database_path.parent()
+                .ok_or_else(|| anyhow!("Database path {:?} has no parent directory.", database_path))?;
+            let backup_path = timestamped_backup_path(directory, prefix);
+            tracing::info!("Backing up database to {:?} before changing the schema.", backup_path);
+            backup_database(database_path, &backup_path)
+        }
+        #[cfg(feature = "postgres")]
+        AnyConnection::Postgresql(_) => {
+            let _ = (database_path, prefix);
+            tracing::warn!("Skipping backup before schema change, back up postgres databases with pg_dump.");
+            Ok(())
+        }
+    }
+}
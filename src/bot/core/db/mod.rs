@@ -3,6 +3,13 @@ pub(crate) mod schema;
 pub(crate) mod connection;
 pub(crate) mod client;
 pub(crate) mod user_representation;
+#[cfg(feature = "sqlite")]
+pub(crate) mod backup;
+#[cfg(feature = "sqlite")]
+pub(crate) mod check;
+pub(crate) mod migration;
+#[cfg(test)]
+pub(crate) mod test_database;
 
 
 #[derive(thiserror::Error, Clone, Debug)]
@@ -18,3 +25,10 @@ pub enum DatabaseError {
     #[error("Could not connect: {0}")]
     Connection(String),
 }
+
+/// Errors of statements within a transaction, see `Connection::transaction`.
+impl From<diesel::result::Error> for DatabaseError {
+    fn from(error: diesel::result::Error) -> Self {
+        DatabaseError::Other(error.to_string())
+    }
+}
@@ -1,5 +1,11 @@
-use diesel::{Associations, Identifiable, Insertable, Queryable, Selectable};
+use diesel::{AsChangeset, Associations, Identifiable, Insertable, Queryable, Selectable};
 
+use crate::bot::core::db::schema::api_keys;
+use crate::bot::core::db::schema::audit_log;
+use crate::bot::core::db::schema::broadcasts;
+use crate::bot::core::db::schema::event_outbox;
+use crate::bot::core::db::schema::incidents;
+use crate::bot::core::db::schema::orders;
 use crate::bot::core::db::schema::telegram_accounts;
 use crate::bot::core::db::schema::users;
 
@@ -14,7 +20,8 @@ pub struct NewUser<'a> {
 
 #[derive(Queryable, Selectable, Identifiable, PartialEq, Debug)]
 #[diesel(table_name = users)]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct User {
     pub id: i64,
     pub name: String,
@@ -32,8 +39,171 @@ pub struct NewTelegramAccount<'a> {
 #[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Debug)]
 #[diesel(table_name = telegram_accounts)]
 #[diesel(belongs_to(User))]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct TelegramAccount {
     pub id: i64,
     pub user_id: i64,
 }
+
+/// Failed update, the id is shown to the affected user and the admins.
+#[derive(Insertable, Queryable, Selectable, Clone, PartialEq, Debug)]
+#[diesel(table_name = incidents)]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct Incident {
+    pub id: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    pub update_id: i64,
+    pub chat_id: Option<i64>,
+    pub command: Option<String>,
+    pub dialogue_state: Option<String>,
+    pub error: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_keys)]
+pub struct NewApiKey<'a> {
+    pub name: &'a str,
+    pub key_hash: &'a str,
+    pub created_at: i64,
+}
+
+/// Key of the admin api, only the hash of the key is stored.
+#[derive(Queryable, Selectable, Identifiable, Clone, PartialEq, Debug)]
+#[diesel(table_name = api_keys)]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub key_hash: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEvent {
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Who did it, e.g. `cli`, `telegram:<user>`, `api:<key>` or `dashboard:<user>`
+    pub actor: String,
+    pub action: String,
+    /// Name of the affected user or api key
+    pub target: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// Administrative action, shown in the audit log of the dashboard.
+#[derive(Queryable, Selectable, Clone, PartialEq, Debug)]
+#[diesel(table_name = audit_log)]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct AuditEvent {
+    pub id: i64,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub detail: Option<String>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = broadcasts)]
+pub struct NewBroadcast {
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Same format as the actor of the audit log
+    pub sender: String,
+    pub message: String,
+    pub recipients: i64,
+    /// Fewer than the recipients if a delivery failed or the bot shut down
+    pub delivered: i64,
+}
+
+/// Finished broadcast, shown in the broadcast history of the dashboard.
+#[derive(Queryable, Selectable, Clone, PartialEq, Debug)]
+#[diesel(table_name = broadcasts)]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct Broadcast {
+    pub id: i64,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    pub sender: String,
+    pub message: String,
+    pub recipients: i64,
+    pub delivered: i64,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = orders)]
+pub struct NewOrder {
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Chat of the purchase dialogue, orders stay when the user is deleted
+    pub telegram_id: i64,
+    pub full_name: String,
+    pub product: String,
+}
+
+/// Purchased product, listed in the mini app.
+#[derive(Queryable, Selectable, Clone, PartialEq, Debug)]
+#[diesel(table_name = orders)]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct Order {
+    pub id: i64,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    pub telegram_id: i64,
+    pub full_name: String,
+    pub product: String,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = event_outbox)]
+pub struct NewOutboxEntry {
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Same for all urls and attempts, receivers use it to drop duplicates
+    pub event_id: String,
+    pub event_type: String,
+    pub url: String,
+    /// Signed json body
+    pub payload: String,
+    pub next_attempt_at: Option<i64>,
+}
+
+/// Event notification for one url, pending while it has a next attempt.
+#[derive(Queryable, Selectable, Clone, PartialEq, Debug)]
+#[diesel(table_name = event_outbox)]
+#[cfg_attr(feature = "sqlite", diesel(check_for_backend(diesel::sqlite::Sqlite)))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct OutboxEntry {
+    pub id: i64,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    pub event_id: String,
+    pub event_type: String,
+    pub url: String,
+    pub payload: String,
+    pub attempts: i64,
+    /// Unix timestamp in seconds, None once delivered or given up
+    pub next_attempt_at: Option<i64>,
+    pub delivered_at: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+/// Outcome of a delivery attempt.
+#[derive(AsChangeset, Clone, Debug)]
+#[diesel(table_name = event_outbox, treat_none_as_null = true)]
+pub struct OutboxAttempt {
+    pub attempts: i64,
+    pub next_attempt_at: Option<i64>,
+    pub delivered_at: Option<i64>,
+    pub last_error: Option<String>,
+}
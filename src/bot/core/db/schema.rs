@@ -1,5 +1,73 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_keys (id) {
+        id -> BigInt,
+        name -> Text,
+        key_hash -> Text,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> BigInt,
+        created_at -> BigInt,
+        actor -> Text,
+        action -> Text,
+        target -> Nullable<Text>,
+        detail -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    broadcasts (id) {
+        id -> BigInt,
+        created_at -> BigInt,
+        sender -> Text,
+        message -> Text,
+        recipients -> BigInt,
+        delivered -> BigInt,
+    }
+}
+
+diesel::table! {
+    event_outbox (id) {
+        id -> BigInt,
+        created_at -> BigInt,
+        event_id -> Text,
+        event_type -> Text,
+        url -> Text,
+        payload -> Text,
+        attempts -> BigInt,
+        next_attempt_at -> Nullable<BigInt>,
+        delivered_at -> Nullable<BigInt>,
+        last_error -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    incidents (id) {
+        id -> Text,
+        created_at -> BigInt,
+        update_id -> BigInt,
+        chat_id -> Nullable<BigInt>,
+        command -> Nullable<Text>,
+        dialogue_state -> Nullable<Text>,
+        error -> Text,
+    }
+}
+
+diesel::table! {
+    orders (id) {
+        id -> BigInt,
+        created_at -> BigInt,
+        telegram_id -> BigInt,
+        full_name -> Text,
+        product -> Text,
+    }
+}
+
 diesel::table! {
     telegram_accounts (id) {
         id -> BigInt,
@@ -19,6 +87,12 @@ diesel::table! {
 diesel::joinable!(telegram_accounts -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    audit_log,
+    broadcasts,
+    event_outbox,
+    incidents,
+    orders,
     telegram_accounts,
     users,
 );
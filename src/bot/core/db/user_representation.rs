@@ -1,8 +1,6 @@
-use std::env;
 use std::fmt::{Display, Formatter};
 use clap::ValueEnum;
 
-use crate::bot::core::bot_config::TELOXIDE_BOT_NAME_KEY;
 use crate::bot::core::db::model::{TelegramAccount, User};
 
 #[derive(PartialEq, Debug, Clone)]
@@ -19,7 +17,8 @@ impl UserRepresentation {
         self.role.eq(&UserRole::Admin)
     }
 }
-#[derive(PartialEq, Debug, Clone, ValueEnum)]
+#[derive(PartialEq, Debug, Clone, ValueEnum, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
 pub enum UserRole {
     User,
     Admin,
@@ -53,10 +52,7 @@ impl UserRole {
 }
 
 impl UserRepresentation {
-    pub fn from_user(user: &User, account: &Option<TelegramAccount>) -> Self {
-        let bot_name = env::var(TELOXIDE_BOT_NAME_KEY)
-            .unwrap_or_else(|_| panic!("Bot name must be set in env {}", TELOXIDE_BOT_NAME_KEY));
-
+    pub fn from_user(user: &User, account: &Option<TelegramAccount>, bot_name: &str) -> Self {
         let bot_start_url = format!("https://t.me/{}?start={}", bot_name, user.start);
         let telegram_id = account.as_ref().map(|account| account.id);
         let role = UserRole::from_text(&user.role);
@@ -1,44 +1,249 @@
 use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures::{FutureExt, StreamExt};
+use futures::stream::BoxStream;
+use teloxide::backoff::exponential_backoff_strategy;
+use teloxide::Bot;
+use teloxide::payloads::GetUpdatesSetters;
 use teloxide::prelude::Requester;
-use teloxide::update_listeners::UpdateListener;
-use teloxide::update_listeners::webhooks::{axum_to_router, Options};
-use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_HEALTHCHECK;
+use teloxide::requests::HasPayload;
+use teloxide::RequestError;
+use teloxide::stop::{mk_stop_token, StopFlag, StopToken};
+use teloxide::types::{AllowedUpdate, Update};
+use teloxide::update_listeners::{AsUpdateStream, UpdateListener};
+use teloxide::update_listeners::webhooks::{axum_no_setup, Options};
+use crate::bot::core::bot_config::{TELEGRAM_BOT_ENDPOINT_HEALTHCHECK, TELEGRAM_BOT_ENDPOINT_LIVE, TELEGRAM_BOT_ENDPOINT_READY};
 use crate::bot::core::healthcheck::endpoint::healthcheck_endpoint;
+use crate::bot::core::healthcheck::readiness::{BotReadiness, liveness_endpoint, readiness_endpoint};
+use crate::bot::core::metrics::MeasuredRequest;
+
+const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+/// Long polling timeout of `getUpdates`, same as the default polling of teloxide.
+const POLLING_TIMEOUT_SECONDS: u32 = 10;
 
-pub async fn axum_update_listener<R>(
-    bot: R,
-    options: Options,
-) -> Result<impl UpdateListener<Err = Infallible>, R::Err>
-    where
-        R: Requester + Send + 'static,
-        <R as Requester>::DeleteWebhook: Send,
-{
+/// Binding fails before the webhook is registered, the server stops with the update listener.
+/// `routes` are served next to the webhook, e.g. metrics and the admin api.
+pub async fn axum_update_listener(
+    bot: Bot,
+    mut options: Options,
+    readiness: Arc<BotReadiness>,
+    routes: axum::Router,
+) -> Result<impl UpdateListener<Err = Infallible>, anyhow::Error> {
     // loosely derived from: teloxide: src/update_listeners/webhooks/axum.rs
     let Options { address, .. } = options;
+    // registered with setWebhook below, generated if not configured
+    let secret_token = Arc::new(options.get_or_gen_secret_token().to_owned());
 
-    let (mut update_listener, stop_flag, app) = axum_to_router(bot, options).await?;
+    let tcp_listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|error| anyhow!("Could not bind webhook listener to {}: {}", address, error))?;
+    // setWebhook and deleteWebhook are sent here instead of by teloxide to measure them
+    set_webhook(&bot, &mut options).await?;
+    let (mut update_listener, stop_flag, app) = axum_no_setup(options);
+    let stop_flag = stop_flag.then(move |()| async move {
+        if let Err(error) = bot.delete_webhook().send_measured().await {
+            tracing::error!("Couldn't delete webhook: {}", error);
+        }
+    });
+    // only the webhook route, unknown paths stay 404
+    let app = app.route_layer(axum::middleware::from_fn_with_state(secret_token, verify_secret_token));
     let my_router = axum::Router::new()
         .route(TELEGRAM_BOT_ENDPOINT_HEALTHCHECK, axum::routing::get(healthcheck_endpoint))
+        .route(TELEGRAM_BOT_ENDPOINT_READY, axum::routing::get(readiness_endpoint))
+        .route(TELEGRAM_BOT_ENDPOINT_LIVE, axum::routing::get(liveness_endpoint))
+        .with_state(readiness)
+        .merge(routes)
         .fallback_service(app);
 
     let stop_token = update_listener.stop_token();
 
     tokio::spawn(async move {
-        let tcp_listener = tokio::net::TcpListener::bind(address)
-            .await
-            .inspect_err(|_err| {
-                stop_token.stop();
-            })
-            .expect("Couldn't bind to the address");
-
-        axum::serve(tcp_listener, my_router)
+        // finishes running requests after the stop flag, new connections are refused
+        let result = axum::serve(tcp_listener, my_router)
             .with_graceful_shutdown(stop_flag)
-            .await
-            .inspect_err(|_err| {
-                stop_token.stop();
-            })
-            .expect("Axum server error");
+            .await;
+        if let Err(error) = result {
+            tracing::error!("Webhook server failed: {}", error);
+            stop_token.stop();
+        }
     });
 
     Ok(update_listener)
-}
\ No newline at end of file
+}
+
+/// Register the webhook with the options like teloxide, the certificate is taken out of them.
+async fn set_webhook(bot: &Bot, options: &mut Options) -> Result<(), RequestError> {
+    let secret_token = options.get_or_gen_secret_token().to_owned();
+    let mut request = bot.set_webhook(options.url.clone());
+    request.payload_mut().certificate = options.certificate.take();
+    request.payload_mut().max_connections = options.max_connections;
+    request.payload_mut().drop_pending_updates = Some(options.drop_pending_updates);
+    request.payload_mut().secret_token = Some(secret_token);
+    request.send_measured().await?;
+    Ok(())
+}
+
+/// Long polling like the default polling of teloxide, but every Bot API call is measured.
+pub struct PollingUpdateListener {
+    bot: Bot,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+    offset: i32,
+    stop_token: StopToken,
+    stop_flag: StopFlag,
+}
+
+/// Deletes a registered webhook, telegram does not answer `getUpdates` while one is set.
+pub async fn polling_update_listener(bot: Bot) -> PollingUpdateListener {
+    match bot.get_webhook_info().send_measured().await {
+        Ok(webhook_info) if webhook_info.url.is_some() => {
+            if let Err(error) = bot.delete_webhook().send_measured().await {
+                tracing::error!("Failed to delete a webhook: {}", error);
+            }
+        }
+        Ok(_) => {}
+        Err(error) => {
+            tracing::error!("Failed to get webhook info: {}", error);
+        }
+    }
+    let (stop_token, stop_flag) = mk_stop_token();
+    PollingUpdateListener { bot, allowed_updates: None, offset: 0, stop_token, stop_flag }
+}
+
+impl UpdateListener for PollingUpdateListener {
+    type Err = RequestError;
+
+    fn stop_token(&mut self) -> StopToken {
+        self.stop_token.clone()
+    }
+
+    fn hint_allowed_updates(&mut self, hint: &mut dyn Iterator<Item = AllowedUpdate>) {
+        self.allowed_updates = Some(hint.collect());
+    }
+}
+
+struct PollingState<'a> {
+    listener: &'a mut PollingUpdateListener,
+    updates: std::vec::IntoIter<Update>,
+    retry_delay: Option<Duration>,
+    error_count: u32,
+    stopping: bool,
+}
+
+impl<'a> AsUpdateStream<'a> for PollingUpdateListener {
+    type StreamErr = RequestError;
+    type Stream = BoxStream<'a, Result<Update, RequestError>>;
+
+    fn as_stream(&'a mut self) -> Self::Stream {
+        let state = PollingState { listener: self, updates: Vec::new().into_iter(), retry_delay: None, error_count: 0, stopping: false };
+        futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(update) = state.updates.next() {
+                    return Some((Ok(update), state));
+                }
+                if state.stopping {
+                    return None;
+                }
+                let stop_flag = state.listener.stop_flag.clone();
+                if let Some(retry_delay) = state.retry_delay.take() {
+                    tokio::select! {
+                        () = stop_flag.clone() => {}
+                        () = tokio::time::sleep(retry_delay) => {}
+                    }
+                }
+
+                let listener = &mut *state.listener;
+                let mut request = listener.bot.get_updates().offset(listener.offset).timeout(POLLING_TIMEOUT_SECONDS);
+                request.payload_mut().allowed_updates = listener.allowed_updates.take();
+                let result = tokio::select! {
+                    biased;
+                    () = stop_flag => {
+                        // confirms the received updates, they are not sent again after a restart
+                        state.stopping = true;
+                        listener.bot.get_updates().offset(listener.offset).limit(1).timeout(0).send_measured().await.map(|_| Vec::new())
+                    }
+                    result = request.send_measured() => result,
+                };
+                match result {
+                    Ok(updates) => {
+                        state.error_count = 0;
+                        if let Some(update) = updates.last() {
+                            listener.offset = update.id.as_offset();
+                        }
+                        state.updates = updates.into_iter();
+                    }
+                    Err(error) => {
+                        let retry_delay = match &error {
+                            RequestError::RetryAfter(seconds) => {
+                                state.error_count = 0;
+                                seconds.duration()
+                            }
+                            _ => {
+                                let retry_delay = exponential_backoff_strategy(state.error_count);
+                                state.error_count = state.error_count.saturating_add(1);
+                                retry_delay
+                            }
+                        };
+                        tracing::info!("Retrying getting updates in {}s", retry_delay.as_secs());
+                        state.retry_delay = Some(retry_delay);
+                        return Some((Err(error), state));
+                    }
+                }
+            }
+        }).boxed()
+    }
+}
+
+/// Reject updates that were not sent by telegram, independent of the checks within teloxide.
+async fn verify_secret_token(State(secret_token): State<Arc<String>>, request: Request, next: Next) -> Response {
+    let is_valid = request.headers()
+        .get(SECRET_TOKEN_HEADER)
+        .is_some_and(|header| constant_time_eq(header.as_bytes(), secret_token.as_bytes()));
+    if is_valid {
+        next.run(request).await
+    } else {
+        tracing::warn!("Rejected webhook request without valid secret token to path {}", request.uri().path());
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len() && left.iter().zip(right).fold(0, |difference, (l, r)| difference | (l ^ r)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use teloxide::Bot;
+    use teloxide::update_listeners::webhooks::Options;
+
+    use crate::bot::core::dispatch::{axum_update_listener, SECRET_TOKEN_HEADER};
+    use crate::bot::core::healthcheck::readiness::BotReadiness;
+    use crate::bot::core::mock_api::MockApi;
+
+    #[tokio::test]
+    async fn secret_token_is_only_required_by_webhook() {
+        let api = MockApi::new("testbot");
+        let bot = Bot::new("123456:test-token").set_api_url(api.serve().await);
+        let address = std::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap().local_addr().unwrap();
+        let base_url = format!("http://{}", address);
+        let options = Options::new(address, format!("{}/webhook", base_url).parse().unwrap())
+            .secret_token("abc_DEF-123".to_string());
+        let _listener = axum_update_listener(bot, options, BotReadiness::new(None), axum::Router::new()).await.unwrap();
+
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let status = |request: reqwest::RequestBuilder| async move { request.send().await.unwrap().status().as_u16() };
+        assert_eq!(status(client.post(format!("{}/webhook", base_url))).await, 401);
+        assert_eq!(status(client.post(format!("{}/webhook", base_url)).header(SECRET_TOKEN_HEADER, "wrong")).await, 401);
+        assert_ne!(status(client.post(format!("{}/webhook", base_url)).header(SECRET_TOKEN_HEADER, "abc_DEF-123")).await, 401);
+        assert_eq!(status(client.get(format!("{}/unknown", base_url))).await, 404);
+        assert_eq!(status(client.post(format!("{}/unknown", base_url))).await, 404);
+    }
+}
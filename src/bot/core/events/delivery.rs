@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use reqwest::header::CONTENT_TYPE;
+use tokio::task::{Id, JoinHandle, JoinSet};
+
+use crate::bot::core::db::model::{OutboxAttempt, OutboxEntry};
+use crate::bot::core::events::EventBus;
+use crate::bot::core::repository::OutboxRepository;
+use crate::bot::core::signature::{hmac_sha256, to_hex};
+
+/// `sha256=<hex>` of the HMAC-SHA256 of the body with the configured secret
+pub(crate) const SIGNATURE_HEADER: &str = "X-Telegrambot-Signature-256";
+pub(crate) const EVENT_HEADER: &str = "X-Telegrambot-Event";
+/// Entries of a url loaded at once, the next batch is loaded once the delivery finished
+const BATCH_SIZE: i64 = 20;
+/// Finds retries and events of other processes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RETRY_DELAY_SECONDS: i64 = 60 * 60;
+/// Delivered and given up entries are kept for a week
+const OUTBOX_RETENTION_SECONDS: i64 = 7 * 24 * 60 * 60;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Deliver the outbox until aborted, pending entries are kept in the outbox and delivered after a restart.
+pub(crate) fn spawn_event_delivery<R: OutboxRepository>(repository: R, events: EventBus) -> Option<JoinHandle<()>> {
+    if events.config.urls.is_empty() {
+        return None;
+    }
+    tracing::info!("Delivering events to {} urls", events.config.urls.len());
+    Some(tokio::spawn(deliver_events(repository, events)))
+}
+
+async fn deliver_events<R: OutboxRepository>(repository: R, events: EventBus) {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(error) => {
+            tracing::error!("Could not create the http client of the event delivery: {}", error);
+            return;
+        }
+    };
+    let poll_interval = POLL_INTERVAL.min(Duration::from_secs(events.config.retry_delay_seconds as u64));
+    let mut last_prune: Option<Instant> = None;
+    // one delivery per url, an unreachable url does not delay the others
+    let mut deliveries = JoinSet::new();
+    let mut busy_urls = HashMap::<Id, String>::new();
+    loop {
+        if last_prune.map_or(true, |last_prune| last_prune.elapsed() > PRUNE_INTERVAL) {
+            let created_before = chrono::Utc::now().timestamp() - OUTBOX_RETENTION_SECONDS;
+            match repository.prune_outbox(created_before).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!("Removed {} old entries from the event outbox", pruned),
+                Err(error) => tracing::error!("Could not prune the event outbox: {}", error),
+            }
+            last_prune = Some(Instant::now());
+        }
+        match repository.pending_outbox_urls().await {
+            Ok(mut urls) => {
+                // entries of busy urls are left to their running delivery, it may still retry an earlier entry
+                urls.retain(|url| !busy_urls.values().any(|busy_url| busy_url == url));
+                for url in urls {
+                    match repository.due_outbox_entries(&url, chrono::Utc::now().timestamp(), BATCH_SIZE).await {
+                        Ok(entries) if entries.is_empty() => {}
+                        Ok(entries) => {
+                            let delivery = deliveries.spawn(deliver_to_url(client.clone(), repository.clone(), events.clone(), entries));
+                            busy_urls.insert(delivery.id(), url);
+                        }
+                        Err(error) => tracing::error!("Could not load the event outbox: {}", error),
+                    }
+                }
+            }
+            Err(error) => tracing::error!("Could not load the event outbox: {}", error),
+        }
+        tokio::select! {
+            _ = events.published.notified() => {}
+            _ = tokio::time::sleep(poll_interval) => {}
+            Some(finished) = deliveries.join_next_with_id() => {
+                let id = match finished {
+                    Ok((id, ())) => id,
+                    Err(error) => {
+                        tracing::error!("Event delivery task failed: {}", error);
+                        error.id()
+                    }
+                };
+                busy_urls.remove(&id);
+            }
+        }
+    }
+}
+
+/// Deliver the entries of one url in order, the remaining entries wait for the retry of a failed delivery.
+async fn deliver_to_url<R: OutboxRepository>(client: reqwest::Client, repository: R, events: EventBus, entries: Vec<OutboxEntry>) {
+    let count = entries.len();
+    for (index, entry) in entries.into_iter().enumerate() {
+        if !deliver(&client, &repository, &events, entry).await {
+            if index + 1 < count {
+                tracing::debug!("Skipped {} events of a failed url until the next batch", count - index - 1);
+            }
+            return;
+        }
+    }
+}
+
+/// Returns true if the receiver accepted the event.
+async fn deliver<R: OutboxRepository>(client: &reqwest::Client, repository: &R, events: &EventBus, entry: OutboxEntry) -> bool {
+    let secret = events.config.secret.as_ref().expect("Secret is required with urls.");
+    let signature = to_hex(&hmac_sha256(secret.expose().as_bytes(), entry.payload.as_bytes()));
+    let result = client.post(&entry.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &entry.event_type)
+        .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+        .body(entry.payload.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    let attempts = entry.attempts + 1;
+    let now = chrono::Utc::now().timestamp();
+    let delivered = result.is_ok();
+    let attempt = match result {
+        Ok(_) => {
+            tracing::debug!("Delivered event {} {} after {} attempts", entry.event_type, entry.event_id, attempts);
+            OutboxAttempt { attempts, next_attempt_at: None, delivered_at: Some(now), last_error: None }
+        }
+        Err(error) => {
+            // the url may contain credentials, the causes tell a refused connection from a timeout
+            let error = format!("{:#}", anyhow::Error::new(error.without_url()));
+            let next_attempt_at = if attempts < events.config.max_attempts {
+                tracing::warn!("Delivery {} of event {} {} failed, retrying: {}", attempts, entry.event_type, entry.event_id, error);
+                Some(now + retry_delay(events.config.retry_delay_seconds, attempts))
+            } else {
+                tracing::error!("Gave up event {} {} after {} attempts: {}", entry.event_type, entry.event_id, attempts, error);
+                None
+            };
+            OutboxAttempt { attempts, next_attempt_at, delivered_at: None, last_error: Some(error) }
+        }
+    };
+    if let Err(error) = repository.record_outbox_attempt(entry.id, attempt).await {
+        tracing::error!("Could not record the delivery of event {}: {}", entry.event_id, error);
+    }
+    delivered
+}
+
+/// Doubles with every failed attempt.
+fn retry_delay(retry_delay_seconds: i64, attempts: i64) -> i64 {
+    retry_delay_seconds
+        .saturating_mul(1 << (attempts - 1).clamp(0, 20))
+        .min(MAX_RETRY_DELAY_SECONDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use reqwest::Url;
+
+    use crate::bot::core::bot_config::events::BotEventsConfig;
+    use crate::bot::core::bot_config::storage::DatabaseBackend;
+    use crate::bot::core::bot_config::source::Secret;
+    use crate::bot::core::events::{BotEvent, EventBus};
+    use crate::bot::core::events::delivery::{spawn_event_delivery, EVENT_HEADER, SIGNATURE_HEADER};
+    use crate::bot::core::db::client::DatabaseClient;
+    use crate::bot::core::db::connection::MyDatabaseConnection;
+    use crate::bot::core::db::test_database::TestDatabase;
+    use crate::bot::core::mock_api::serve_local;
+    use crate::bot::core::repository::in_memory::InMemoryRepository;
+    use crate::bot::core::repository::OutboxRepository;
+    use crate::bot::core::signature::verify_hmac_sha256;
+
+    const EVENTS_SECRET: &str = "test-events-secret";
+    /// The first delivery is retried after a second
+    const EVENTS_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Delivery received by the [`EventReceiver`].
+    #[derive(Clone, Debug)]
+    struct ReceivedEvent {
+        event_type: String,
+        signature: String,
+        body: String,
+        accepted: bool,
+    }
+
+    /// Local receiver of the event webhooks, rejects the first delivery to check the retry.
+    #[derive(Default)]
+    struct EventReceiver {
+        deliveries: Mutex<Vec<ReceivedEvent>>,
+    }
+
+    impl EventReceiver {
+        async fn serve(self: &Arc<Self>) -> Url {
+            let router = axum::Router::new()
+                .route("/events", axum::routing::post(receive_event))
+                .with_state(self.clone());
+            serve_local(router).await.join("events").unwrap()
+        }
+
+        /// Wait until the given number of deliveries was accepted.
+        async fn accepted(&self, count: usize) -> Vec<ReceivedEvent> {
+            let deadline = Instant::now() + EVENTS_TIMEOUT;
+            loop {
+                let deliveries = self.deliveries.lock().unwrap().clone();
+                let accepted = deliveries.into_iter().filter(|delivery| delivery.accepted).collect::<Vec<_>>();
+                if accepted.len() >= count {
+                    return accepted;
+                }
+                assert!(Instant::now() < deadline, "expected {} accepted events, received {:?}", count, accepted);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    async fn receive_event(State(receiver): State<Arc<EventReceiver>>, headers: HeaderMap, body: String) -> StatusCode {
+        let header = |name: &str| headers.get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let mut deliveries = receiver.deliveries.lock().unwrap();
+        let accepted = !deliveries.is_empty();
+        deliveries.push(ReceivedEvent { event_type: header(EVENT_HEADER), signature: header(SIGNATURE_HEADER), body, accepted });
+        if accepted {
+            StatusCode::NO_CONTENT
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+
+    fn signature_is_valid(delivery: &ReceivedEvent) -> bool {
+        delivery.signature.strip_prefix("sha256=")
+            .is_some_and(|signature| verify_hmac_sha256(EVENTS_SECRET.as_bytes(), delivery.body.as_bytes(), signature))
+    }
+
+    fn events_config(urls: Vec<Url>) -> BotEventsConfig {
+        BotEventsConfig {
+            urls,
+            secret: Some(Secret::new(EVENTS_SECRET.to_string())),
+            max_attempts: 3,
+            retry_delay_seconds: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_events_and_retries_rejected_delivery() {
+        let receiver = Arc::new(EventReceiver::default());
+        let repository = InMemoryRepository::new("testbot");
+        let events = EventBus::new(events_config(vec![receiver.serve().await]));
+        let delivery_task = spawn_event_delivery(repository.clone(), events.clone()).unwrap();
+
+        events.publish(&repository, BotEvent::UserRegistered { user_name: "alice".to_string(), telegram_id: 1002 }).await;
+        events.publish(&repository, BotEvent::BotBlocked { telegram_id: 1003 }).await;
+        let accepted = receiver.accepted(2).await;
+        delivery_task.abort();
+
+        let mut accepted_types = accepted.iter().map(|delivery| delivery.event_type.as_str()).collect::<Vec<_>>();
+        accepted_types.sort();
+        assert_eq!(accepted_types, vec!["bot_blocked", "user_registered"]);
+        let deliveries = receiver.deliveries.lock().unwrap().clone();
+        assert!(deliveries.iter().all(signature_is_valid), "{:?}", deliveries);
+        let rejected = deliveries.iter().find(|delivery| !delivery.accepted).unwrap();
+        assert!(accepted.iter().any(|delivery| delivery.body == rejected.body), "rejected event was not retried: {}", rejected.body);
+    }
+
+    #[tokio::test]
+    async fn delivers_events_of_url_in_order_after_retry() {
+        let receiver = Arc::new(EventReceiver::default());
+        let url = receiver.serve().await;
+        let repository = InMemoryRepository::new("testbot");
+        let events = EventBus::new(events_config(vec![url.clone()]));
+        let delivery_task = spawn_event_delivery(repository.clone(), events.clone()).unwrap();
+
+        events.publish(&repository, BotEvent::UserRegistered { user_name: "alice".to_string(), telegram_id: 1002 }).await;
+        // the first delivery is rejected, the retry is due a second later
+        let deadline = Instant::now() + EVENTS_TIMEOUT;
+        while repository.due_outbox_entries(url.as_str(), i64::MAX, 10).await.unwrap().first().map_or(true, |entry| entry.attempts == 0) {
+            assert!(Instant::now() < deadline, "the rejected delivery was not recorded");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        events.publish(&repository, BotEvent::BotBlocked { telegram_id: 1003 }).await;
+        receiver.accepted(2).await;
+        delivery_task.abort();
+
+        let deliveries = receiver.deliveries.lock().unwrap().clone();
+        let received = deliveries.iter().map(|delivery| (delivery.event_type.as_str(), delivery.accepted)).collect::<Vec<_>>();
+        assert_eq!(received, vec![("user_registered", false), ("user_registered", true), ("bot_blocked", true)]);
+    }
+
+    /// Accepts connections but never answers, like a receiver that hangs.
+    async fn serve_hanging_receiver() -> Url {
+        let tcp_listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+        let url = Url::parse(&format!("http://{}/events", tcp_listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((connection, _)) = tcp_listener.accept().await {
+                connections.push(connection);
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn hanging_url_does_not_delay_other_urls() {
+        let receiver = Arc::new(EventReceiver::default());
+        let repository = InMemoryRepository::new("testbot");
+        // entries of the hanging url come first in the outbox
+        let events = EventBus::new(events_config(vec![serve_hanging_receiver().await, receiver.serve().await]));
+        let delivery_task = spawn_event_delivery(repository.clone(), events.clone()).unwrap();
+
+        events.publish(&repository, BotEvent::BotBlocked { telegram_id: 1003 }).await;
+        // the request timeout of the hanging url is 10s, the retry of the other url is due after 1s
+        let accepted = tokio::time::timeout(Duration::from_secs(5), receiver.accepted(1)).await;
+        delivery_task.abort();
+        assert_eq!(accepted.expect("delivery waited for the hanging url")[0].event_type, "bot_blocked");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keeps_outbox_entry_across_restart() {
+        let database = TestDatabase::open(DatabaseBackend::Sqlite);
+        let receiver = Arc::new(EventReceiver::default());
+        let url = receiver.serve().await;
+        let events = EventBus::new(events_config(vec![url.clone()]));
+
+        let client = DatabaseClient::load(database.connection.clone(), "testbot").await.unwrap();
+        let delivery_task = spawn_event_delivery(client.clone(), events.clone()).unwrap();
+        events.publish(&client, BotEvent::UserDeleted { user_name: "alice".to_string(), telegram_id: Some(1002) }).await;
+        // stop the process after the rejected delivery is recorded, the retry is due a second later
+        let deadline = Instant::now() + EVENTS_TIMEOUT;
+        while client.due_outbox_entries(url.as_str(), i64::MAX, 10).await.unwrap().first().map_or(true, |entry| entry.attempts == 0) {
+            assert!(Instant::now() < deadline, "the rejected delivery was not recorded");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        delivery_task.abort();
+        let _ = delivery_task.await;
+        drop(client);
+
+        // the restarted process opens the database file again
+        let database_path = database.connection.database_path.clone();
+        let database_url = format!("sqlite://{}", database_path.to_str().unwrap());
+        let connection = MyDatabaseConnection::connect(DatabaseBackend::Sqlite, database_url, database_path).unwrap();
+        let restarted = DatabaseClient::load(connection, "testbot").await.unwrap();
+        let delivery_task = spawn_event_delivery(restarted, events.clone()).unwrap();
+        let accepted = receiver.accepted(1).await;
+        delivery_task.abort();
+
+        let deliveries = receiver.deliveries.lock().unwrap().clone();
+        assert_eq!(deliveries.len(), 2, "{:?}", deliveries);
+        assert!(!deliveries[0].accepted && signature_is_valid(&deliveries[1]));
+        assert_eq!((accepted[0].event_type.as_str(), &accepted[0].body), ("user_deleted", &deliveries[0].body));
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::bot::core::bot_config::events::BotEventsConfig;
+use crate::bot::core::db::model::NewOutboxEntry;
+use crate::bot::core::repository::OutboxRepository;
+use crate::bot::core::util::random_start_token;
+
+pub(crate) mod delivery;
+
+/// Something other systems may react to, posted to the event webhooks as `{"id", "created_at", "type", "data"}`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub(crate) enum BotEvent {
+    /// A telegram account was linked to a user, with the start link or by an admin
+    UserRegistered { user_name: String, telegram_id: i64 },
+    UserDeleted { user_name: String, telegram_id: Option<i64> },
+    OrderPlaced { telegram_id: i64, full_name: String, product: String },
+    /// Fewer deliveries than recipients if a delivery failed or the bot shut down
+    BroadcastSent { sender: String, recipients: usize, delivered: usize },
+    /// A message could not be delivered because the user blocked the bot
+    BotBlocked { telegram_id: i64 },
+}
+
+impl BotEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            BotEvent::UserRegistered { .. } => "user_registered",
+            BotEvent::UserDeleted { .. } => "user_deleted",
+            BotEvent::OrderPlaced { .. } => "order_placed",
+            BotEvent::BroadcastSent { .. } => "broadcast_sent",
+            BotEvent::BotBlocked { .. } => "bot_blocked",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EventPayload<'a> {
+    id: &'a str,
+    /// Unix timestamp in seconds
+    created_at: i64,
+    #[serde(flatten)]
+    event: &'a BotEvent,
+}
+
+/// Publishes events to the outbox of the event webhooks, injected into handlers as dptree dependency.
+#[derive(Clone)]
+pub(crate) struct EventBus {
+    config: Arc<BotEventsConfig>,
+    /// Wakes the delivery of this process, events of other processes, e.g. the CLI, are found by polling
+    published: Arc<Notify>,
+}
+
+impl EventBus {
+    pub(crate) fn new(config: BotEventsConfig) -> Self {
+        Self { config: Arc::new(config), published: Default::default() }
+    }
+
+    /// Adds the event to the outbox for every url, failures are logged, the action that caused the event still succeeded.
+    pub(crate) async fn publish<R: OutboxRepository>(&self, repository: &R, event: BotEvent) {
+        if self.config.urls.is_empty() {
+            return;
+        }
+        let event_id = random_start_token();
+        let created_at = chrono::Utc::now().timestamp();
+        let payload = serde_json::to_string(&EventPayload { id: &event_id, created_at, event: &event })
+            .expect("Events are serializable.");
+        let entries = self.config.urls.iter()
+            .map(|url| NewOutboxEntry {
+                created_at,
+                event_id: event_id.clone(),
+                event_type: event.name().to_string(),
+                url: url.to_string(),
+                payload: payload.clone(),
+                next_attempt_at: Some(created_at),
+            })
+            .collect();
+        match repository.enqueue_outbox_entries(entries).await {
+            Ok(()) => {
+                tracing::debug!("Published event {} {}", event.name(), event_id);
+                self.published.notify_one();
+            }
+            Err(error) => tracing::error!("Could not publish event {}: {}", event.name(), error),
+        }
+    }
+}
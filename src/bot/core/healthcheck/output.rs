@@ -0,0 +1,321 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+
+use crate::bot::core::healthcheck::task::{HealthcheckCollectedResult, HealthcheckResult, HealthcheckTaskResult, TaskResult};
+
+const METRIC_PREFIX: &str = "telegrambot_healthcheck";
+
+#[derive(PartialEq, Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Complete result, exit code 1 if unhealthy
+    #[default]
+    Json,
+    /// Readable summary, exit code 1 if unhealthy
+    Text,
+    /// Nagios plugin output with perfdata, exit code 0 (OK), 1 (WARNING) or 2 (CRITICAL)
+    Nagios,
+    /// Node exporter textfile collector format, always exit code 0
+    Prometheus,
+}
+
+/// Overall state, warnings do not make the bot unhealthy.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Level {
+    Ok,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    fn of_task(status: &HealthcheckTaskResult) -> Self {
+        match status {
+            HealthcheckTaskResult::Success | HealthcheckTaskResult::Unchecked => Level::Ok,
+            HealthcheckTaskResult::Warning => Level::Warning,
+            HealthcheckTaskResult::Failed | HealthcheckTaskResult::TimedOut => Level::Critical,
+        }
+    }
+
+    fn of_result(result: &HealthcheckCollectedResult) -> Self {
+        result.tasks.iter()
+            .map(|task| Level::of_task(&task.status))
+            .fold(Level::Ok, |level, task_level| match (level, task_level) {
+                (Level::Critical, _) | (_, Level::Critical) => Level::Critical,
+                (Level::Warning, _) | (_, Level::Warning) => Level::Warning,
+                _ => Level::Ok,
+            })
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Level::Ok => "OK",
+            Level::Warning => "WARNING",
+            Level::Critical => "CRITICAL",
+        }
+    }
+}
+
+/// Failures and warnings reported by a task.
+fn problems(task: &TaskResult) -> (Vec<String>, Vec<String>) {
+    match &task.result {
+        HealthcheckResult::WebhookInfo(result) => {
+            let failures = result.message.iter().chain(&result.failures).cloned().collect();
+            (failures, result.warnings.clone())
+        }
+        HealthcheckResult::OwnEndpoint(result) => {
+            (result.message.iter().cloned().collect(), vec![])
+        }
+        HealthcheckResult::NoResult(message) => {
+            (vec![message.clone()], vec![])
+        }
+    }
+}
+
+/// Render the result and determine the exit code of the process.
+pub fn render(result: &HealthcheckCollectedResult, format: OutputFormat) -> (String, i32) {
+    let level = Level::of_result(result);
+    let unhealthy_exit_code = if result.healthy { 0 } else { 1 };
+    match format {
+        OutputFormat::Json => (result.to_json(), unhealthy_exit_code),
+        OutputFormat::Text => (render_text(result, level), unhealthy_exit_code),
+        OutputFormat::Nagios => {
+            let exit_code = match level {
+                Level::Ok => 0,
+                Level::Warning => 1,
+                Level::Critical => 2,
+            };
+            (render_nagios(result, level), exit_code)
+        }
+        OutputFormat::Prometheus => (render_prometheus(result), 0),
+    }
+}
+
+fn render_text(result: &HealthcheckCollectedResult, level: Level) -> String {
+    let mut text = format!("Healthcheck: {} ({} ms)\n", level.label(), result.duration_ms);
+    for task in &result.tasks {
+        let _ = writeln!(text, "  [{:<8}] {} ({} ms)", Level::of_task(&task.status).label(), task.name, task.duration_ms);
+        let (failures, warnings) = problems(task);
+        for failure in failures {
+            let _ = writeln!(text, "      error:   {}", failure);
+        }
+        for warning in warnings {
+            let _ = writeln!(text, "      warning: {}", warning);
+        }
+    }
+    text.trim_end().to_string()
+}
+
+fn render_nagios(result: &HealthcheckCollectedResult, level: Level) -> String {
+    let summary = result.tasks.iter()
+        .filter(|task| Level::of_task(&task.status) != Level::Ok)
+        .map(|task| {
+            let (failures, warnings) = problems(task);
+            format!("{}: {}", task.name, failures.into_iter().chain(warnings).collect::<Vec<_>>().join(", "))
+        })
+        .collect::<Vec<_>>();
+    let summary = if summary.is_empty() {
+        format!("{} tasks passed", result.tasks.len())
+    } else {
+        summary.join("; ")
+    };
+
+    let mut perfdata = vec![format!("duration={}ms;;;0", result.duration_ms)];
+    for task in &result.tasks {
+        perfdata.push(format!("'{}'={}ms;;;0", task.name, task.duration_ms));
+        if let HealthcheckResult::WebhookInfo(webhook) = &task.result {
+            if let Some(info) = &webhook.result {
+                perfdata.push(format!("pending_updates={};;;0", info.pending_update_count));
+            }
+        }
+    }
+    // nagios uses the first line up to the pipe as status, line breaks would start long output
+    format!("TELEGRAMBOT {} - {} | {}", level.label(), summary.replace(['\n', '|'], " "), perfdata.join(" "))
+}
+
+fn render_prometheus(result: &HealthcheckCollectedResult) -> String {
+    let mut text = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, String)>| {
+        let _ = writeln!(text, "# HELP {}_{} {}", METRIC_PREFIX, name, help);
+        let _ = writeln!(text, "# TYPE {}_{} gauge", METRIC_PREFIX, name);
+        for (labels, value) in samples {
+            let _ = writeln!(text, "{}_{}{} {}", METRIC_PREFIX, name, labels, value);
+        }
+    };
+    let task_label = |task: &TaskResult| format!("{{task=\"{}\"}}", task.name);
+
+    gauge("healthy", "1 if no healthcheck task failed", vec![(String::new(), (result.healthy as u8).to_string())]);
+    gauge("duration_seconds", "Duration of the healthcheck", vec![(String::new(), seconds(result.duration_ms))]);
+    gauge("last_run_timestamp_seconds", "Time of the healthcheck", vec![(String::new(), chrono::offset::Utc::now().timestamp().to_string())]);
+    gauge("task_healthy", "1 if the task succeeded, possibly with warnings", result.tasks.iter()
+        .map(|task| (task_label(task), ((Level::of_task(&task.status) != Level::Critical) as u8).to_string()))
+        .collect());
+    gauge("task_warning", "1 if the task reported warnings", result.tasks.iter()
+        .map(|task| (task_label(task), ((Level::of_task(&task.status) == Level::Warning) as u8).to_string()))
+        .collect());
+    gauge("task_timed_out", "1 if the task did not finish within the timeout", result.tasks.iter()
+        .map(|task| (task_label(task), ((task.status == HealthcheckTaskResult::TimedOut) as u8).to_string()))
+        .collect());
+    gauge("task_duration_seconds", "Duration of the task", result.tasks.iter()
+        .map(|task| (task_label(task), seconds(task.duration_ms)))
+        .collect());
+    gauge("webhook_pending_updates", "Updates waiting for delivery by telegram", result.tasks.iter()
+        .filter_map(|task| match &task.result {
+            HealthcheckResult::WebhookInfo(webhook) => webhook.result.as_ref(),
+            _ => None,
+        })
+        .map(|info| (String::new(), info.pending_update_count.to_string()))
+        .collect());
+    text.trim_end().to_string()
+}
+
+fn seconds(duration_ms: u128) -> String {
+    format!("{:.3}", duration_ms as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::bot::core::healthcheck::output::{render, OutputFormat, METRIC_PREFIX};
+    use crate::bot::core::healthcheck::task::{HealthcheckCollectedResult, HealthcheckResult, HealthcheckTaskResult, TaskResult};
+    use crate::bot::core::healthcheck::tasks::own_health_endpoint::OwnEndpointCheckResult;
+    use crate::bot::core::healthcheck::webhook_info::{TelegramUpdateMode, WebhookCheckResult};
+
+    fn webhook_task(status: HealthcheckTaskResult, failures: Vec<&str>, warnings: Vec<&str>) -> TaskResult {
+        let info = serde_json::from_value(json!({
+            "url": "https://bot.example.com/bot",
+            "has_custom_certificate": false,
+            "pending_update_count": 7,
+        })).unwrap();
+        TaskResult {
+            name: "webhook".to_string(),
+            duration_ms: 120,
+            result: HealthcheckResult::WebhookInfo(WebhookCheckResult {
+                healthy: failures.is_empty(),
+                mode: TelegramUpdateMode::Webhook,
+                message: None,
+                failures: failures.into_iter().map(String::from).collect(),
+                warnings: warnings.into_iter().map(String::from).collect(),
+                result: Some(info),
+                api_url: None,
+            }),
+            status,
+        }
+    }
+
+    fn endpoint_task(status: HealthcheckTaskResult, message: Option<&str>) -> TaskResult {
+        TaskResult {
+            name: "own_endpoint".to_string(),
+            duration_ms: 30,
+            result: HealthcheckResult::OwnEndpoint(OwnEndpointCheckResult {
+                healthy: message.is_none(),
+                url: Some("https://bot.example.com/healthcheck".to_string()),
+                status_code: message.is_none().then_some(200),
+                duration_ms: Some(30),
+                message: message.map(String::from),
+                reply: None,
+            }),
+            status,
+        }
+    }
+
+    fn collected(tasks: Vec<TaskResult>) -> HealthcheckCollectedResult {
+        let healthy = tasks.iter().all(|task| !matches!(task.status, HealthcheckTaskResult::Failed | HealthcheckTaskResult::TimedOut));
+        HealthcheckCollectedResult { healthy, duration_ms: 1500, tasks }
+    }
+
+    fn ok() -> HealthcheckCollectedResult {
+        collected(vec![webhook_task(HealthcheckTaskResult::Success, vec![], vec![]), endpoint_task(HealthcheckTaskResult::Unchecked, None)])
+    }
+
+    fn warning() -> HealthcheckCollectedResult {
+        collected(vec![webhook_task(HealthcheckTaskResult::Warning, vec![], vec!["Last delivery error 2 hours ago"]), endpoint_task(HealthcheckTaskResult::Success, None)])
+    }
+
+    fn critical() -> HealthcheckCollectedResult {
+        let timed_out = TaskResult {
+            name: "own_endpoint".to_string(),
+            duration_ms: 5000,
+            result: HealthcheckResult::NoResult("No result within 5s".to_string()),
+            status: HealthcheckTaskResult::TimedOut,
+        };
+        collected(vec![webhook_task(HealthcheckTaskResult::Failed, vec!["Webhook url differs"], vec!["High load"]), timed_out])
+    }
+
+    #[test]
+    fn exit_codes_of_formats() {
+        let cases = [
+            (OutputFormat::Json, [0, 0, 1]),
+            (OutputFormat::Text, [0, 0, 1]),
+            (OutputFormat::Nagios, [0, 1, 2]),
+            (OutputFormat::Prometheus, [0, 0, 0]),
+        ];
+        for (format, exit_codes) in cases {
+            let rendered = [ok(), warning(), critical()].map(|result| render(&result, format).1);
+            assert_eq!(rendered, exit_codes, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn renders_json_and_text() {
+        let (json, _) = render(&critical(), OutputFormat::Json);
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!((json["healthy"].as_bool(), json["tasks"][1]["status"].as_str()), (Some(false), Some("TimedOut")));
+
+        let (text, _) = render(&warning(), OutputFormat::Text);
+        assert_eq!(text, "Healthcheck: WARNING (1500 ms)\n  [WARNING ] webhook (120 ms)\n      warning: Last delivery error 2 hours ago\n  [OK      ] own_endpoint (30 ms)");
+        let (text, _) = render(&critical(), OutputFormat::Text);
+        assert!(text.starts_with("Healthcheck: CRITICAL (1500 ms)\n  [CRITICAL] webhook (120 ms)\n      error:   Webhook url differs\n"), "{}", text);
+    }
+
+    #[test]
+    fn renders_nagios_status_line_with_perfdata() {
+        let cases = [
+            (ok(), "TELEGRAMBOT OK - 2 tasks passed"),
+            (warning(), "TELEGRAMBOT WARNING - webhook: Last delivery error 2 hours ago"),
+            (critical(), "TELEGRAMBOT CRITICAL - webhook: Webhook url differs, High load; own_endpoint: No result within 5s"),
+        ];
+        for (result, status) in cases {
+            let endpoint_duration = result.tasks[1].duration_ms;
+            let (output, _) = render(&result, OutputFormat::Nagios);
+            assert_eq!(output, format!("{} | duration=1500ms;;;0 'webhook'=120ms;;;0 pending_updates=7;;;0 'own_endpoint'={}ms;;;0", status, endpoint_duration));
+        }
+    }
+
+    #[test]
+    fn renders_prometheus_exposition_format() {
+        for result in [ok(), warning(), critical()] {
+            let (output, _) = render(&result, OutputFormat::Prometheus);
+            let mut declared = vec![];
+            for line in output.lines() {
+                if let Some(comment) = line.strip_prefix("# HELP ").or_else(|| line.strip_prefix("# TYPE ")) {
+                    let name = comment.split(' ').next().unwrap();
+                    assert!(name.starts_with(METRIC_PREFIX), "{}", line);
+                    declared.push(name.to_string());
+                    continue;
+                }
+                // <name>[{task="<task>"}] <value>, the metric is declared before its samples
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                let name = series.split('{').next().unwrap();
+                assert_eq!(declared.last().map(String::as_str), Some(name), "{}", line);
+                assert!(series.eq(name) || (series.ends_with("\"}") && series[name.len()..].starts_with("{task=\"")), "{}", line);
+                assert!(value.parse::<f64>().is_ok(), "{}", line);
+            }
+            assert!(output.contains("# TYPE telegrambot_healthcheck_healthy gauge\n"));
+        }
+
+        let (output, _) = render(&critical(), OutputFormat::Prometheus);
+        for sample in [
+            "telegrambot_healthcheck_healthy 0",
+            "telegrambot_healthcheck_duration_seconds 1.500",
+            "telegrambot_healthcheck_task_healthy{task=\"webhook\"} 0",
+            "telegrambot_healthcheck_task_timed_out{task=\"own_endpoint\"} 1",
+            "telegrambot_healthcheck_webhook_pending_updates 7",
+        ] {
+            assert!(output.lines().any(|line| line == sample), "{} missing in\n{}", sample, output);
+        }
+        let (output, _) = render(&warning(), OutputFormat::Prometheus);
+        assert!(output.lines().any(|line| line == "telegrambot_healthcheck_task_warning{task=\"webhook\"} 1"), "{}", output);
+        assert!(output.lines().any(|line| line == "telegrambot_healthcheck_healthy 1"), "{}", output);
+    }
+}
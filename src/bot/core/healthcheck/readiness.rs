@@ -0,0 +1,249 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Serialize;
+
+use crate::bot::core::db::connection::MyDatabaseConnection;
+use crate::bot::core::healthcheck::tasks::webhook::WebhookCheckTask;
+use crate::bot::core::healthcheck::webhook_info::{TelegramUpdateMode, WebhookCheckResult};
+
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(5);
+const TELEGRAM_TIMEOUT: Duration = Duration::from_secs(10);
+/// getWebhookInfo is not requested more often, load balancers probe every few seconds
+const WEBHOOK_INFO_MAX_AGE: Duration = Duration::from_secs(60);
+/// Telegram has updates pending but none was processed within this time
+const UPDATE_STALL_THRESHOLD: Duration = Duration::from_secs(300);
+
+/// Runtime state of the bot shared between the dispatcher and the health endpoints.
+#[derive(Debug)]
+pub(crate) struct BotReadiness {
+    /// None in tests without a database
+    database: Option<MyDatabaseConnection>,
+    started: Instant,
+    last_update: Mutex<Option<Instant>>,
+    dispatcher_running: AtomicBool,
+    webhook_info: tokio::sync::Mutex<Option<(Instant, WebhookCheckResult)>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct ComponentStatus {
+    pub healthy: bool,
+    pub message: Option<String>,
+    pub duration_ms: Option<u128>,
+}
+
+impl ComponentStatus {
+    fn new(healthy: bool, message: String) -> Self {
+        Self { healthy, message: Some(message), duration_ms: None }
+    }
+}
+
+/// Public and unauthenticated, messages do not contain error details, they are logged instead.
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct ReadinessResult {
+    /// Database, dispatcher and telegram are healthy
+    pub ok: bool,
+    pub database: ComponentStatus,
+    pub dispatcher: ComponentStatus,
+    /// The Bot API answered getWebhookInfo
+    pub telegram: ComponentStatus,
+    /// Informational, a misconfigured webhook is not fixed by routing traffic elsewhere
+    pub updates: ComponentStatus,
+    /// Informational, findings of the webhook healthcheck
+    pub webhook: ComponentStatus,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct LivenessResult {
+    pub ok: bool,
+    pub dispatcher: ComponentStatus,
+}
+
+impl BotReadiness {
+    pub(crate) fn new(database: Option<MyDatabaseConnection>) -> Arc<Self> {
+        Arc::new(Self {
+            database,
+            started: Instant::now(),
+            last_update: Mutex::new(None),
+            dispatcher_running: AtomicBool::new(false),
+            webhook_info: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn record_update(&self) {
+        *self.last_update.lock().expect("Readiness lock poisoned") = Some(Instant::now());
+    }
+
+    pub(crate) fn set_dispatcher_running(&self, running: bool) {
+        self.dispatcher_running.store(running, Ordering::SeqCst);
+    }
+
+    fn check_dispatcher(&self) -> ComponentStatus {
+        if self.dispatcher_running.load(Ordering::SeqCst) {
+            ComponentStatus::new(true, "Dispatcher is running".to_string())
+        } else {
+            ComponentStatus::new(false, "Dispatcher is not running".to_string())
+        }
+    }
+
+    async fn check_database(&self) -> ComponentStatus {
+        let Some(database) = &self.database else {
+            return ComponentStatus::new(true, "No database".to_string());
+        };
+        let start = Instant::now();
+        let (healthy, message) = match tokio::time::timeout(DATABASE_TIMEOUT, database.ping()).await {
+            Ok(Ok(())) => (true, None),
+            Ok(Err(error)) => {
+                tracing::warn!("Readiness: database ping failed: {}", error);
+                (false, Some("Database ping failed".to_string()))
+            }
+            Err(_) => (false, Some(format!("No reply within {:?}", DATABASE_TIMEOUT))),
+        };
+        ComponentStatus { healthy, message, duration_ms: Some(start.elapsed().as_millis()) }
+    }
+
+    /// getWebhookInfo result, refreshed if older than [`WEBHOOK_INFO_MAX_AGE`].
+    async fn webhook_info(&self) -> (WebhookCheckResult, Duration) {
+        let mut cache = self.webhook_info.lock().await;
+        if let Some((checked, result)) = cache.as_ref() {
+            if checked.elapsed() < WEBHOOK_INFO_MAX_AGE {
+                return (result.clone(), checked.elapsed());
+            }
+        }
+        let result = match tokio::time::timeout(TELEGRAM_TIMEOUT, WebhookCheckTask::telegram_healthcheck_api()).await {
+            Ok(result) => result,
+            Err(_) => WebhookCheckResult::error(format!("No reply from telegram within {:?}", TELEGRAM_TIMEOUT)),
+        };
+        // details once per refresh, the endpoint only reports counts
+        for problem in result.message.iter().chain(&result.failures).chain(&result.warnings) {
+            tracing::warn!("Readiness: getWebhookInfo reported: {}", problem);
+        }
+        *cache = Some((Instant::now(), result.clone()));
+        (result, Duration::ZERO)
+    }
+
+    fn check_telegram(webhook_info: &WebhookCheckResult, age: Duration) -> ComponentStatus {
+        if matches!(webhook_info.mode, TelegramUpdateMode::Error) {
+            ComponentStatus::new(false, format!("getWebhookInfo failed (checked {}s ago)", age.as_secs()))
+        } else {
+            ComponentStatus::new(true, format!("getWebhookInfo ok (checked {}s ago)", age.as_secs()))
+        }
+    }
+
+    fn check_webhook(webhook_info: &WebhookCheckResult) -> ComponentStatus {
+        let message = format!("{} failures, {} warnings, run the healthcheck command for details", webhook_info.failures.len(), webhook_info.warnings.len());
+        ComponentStatus::new(webhook_info.failures.is_empty(), message)
+    }
+
+    fn check_updates(&self, webhook_info: &WebhookCheckResult) -> ComponentStatus {
+        let last_update = *self.last_update.lock().expect("Readiness lock poisoned");
+        let pending = webhook_info.result.as_ref().map(|result| result.pending_update_count).unwrap_or(0);
+        let idle = last_update.unwrap_or(self.started).elapsed();
+        let description = match last_update {
+            Some(_) => format!("Last update processed {}s ago, {} pending", idle.as_secs(), pending),
+            None => format!("No update processed since start {}s ago, {} pending", idle.as_secs(), pending),
+        };
+        // a quiet bot is fine, updates waiting at telegram are not
+        ComponentStatus::new(pending == 0 || idle < UPDATE_STALL_THRESHOLD, description)
+    }
+
+    pub(crate) async fn readiness(&self) -> ReadinessResult {
+        let (database, (webhook_info, age)) = tokio::join!(self.check_database(), self.webhook_info());
+        self.assess(database, &webhook_info, age)
+    }
+
+    /// Only failures that another replica or a restart may not have, webhook findings would take down every replica.
+    fn assess(&self, database: ComponentStatus, webhook_info: &WebhookCheckResult, age: Duration) -> ReadinessResult {
+        let dispatcher = self.check_dispatcher();
+        let telegram = Self::check_telegram(webhook_info, age);
+        ReadinessResult {
+            ok: database.healthy && dispatcher.healthy && telegram.healthy,
+            database,
+            dispatcher,
+            telegram,
+            updates: self.check_updates(webhook_info),
+            webhook: Self::check_webhook(webhook_info),
+        }
+    }
+}
+
+/// Deep check of all components, load balancers should stop routing if this fails.
+pub(crate) async fn readiness_endpoint(State(readiness): State<Arc<BotReadiness>>) -> (StatusCode, Json<ReadinessResult>) {
+    let result = readiness.readiness().await;
+    (status_code(result.ok), Json(result))
+}
+
+/// Cheap check without external dependencies, orchestrators restart the bot if this fails.
+pub(crate) async fn liveness_endpoint(State(readiness): State<Arc<BotReadiness>>) -> (StatusCode, Json<LivenessResult>) {
+    let dispatcher = readiness.check_dispatcher();
+    (status_code(dispatcher.healthy), Json(LivenessResult { ok: dispatcher.healthy, dispatcher }))
+}
+
+fn status_code(ok: bool) -> StatusCode {
+    if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bot::core::healthcheck::readiness::{BotReadiness, ComponentStatus};
+    use crate::bot::core::healthcheck::webhook_info::{TelegramUpdateMode, WebhookCheckResult};
+
+    const SECRET_ERROR: &str = "Wrong response from the webhook: 502 Bad Gateway from 10.0.0.7";
+
+    fn webhook_info_with_findings() -> WebhookCheckResult {
+        let mut webhook_info = WebhookCheckResult::error(String::new());
+        webhook_info.healthy = true;
+        webhook_info.mode = TelegramUpdateMode::Webhook;
+        webhook_info.message = None;
+        webhook_info.fail(format!("Last error: {}", SECRET_ERROR));
+        webhook_info.warn("Webhook secret token is not configured".to_string());
+        webhook_info
+    }
+
+    fn running_bot() -> std::sync::Arc<BotReadiness> {
+        let readiness = BotReadiness::new(None);
+        readiness.set_dispatcher_running(true);
+        readiness
+    }
+
+    #[test]
+    fn webhook_findings_do_not_fail_readiness() {
+        let database = ComponentStatus::new(true, "In-memory repository".to_string());
+        let result = running_bot().assess(database, &webhook_info_with_findings(), Duration::ZERO);
+        assert!(result.ok);
+        assert!(result.telegram.healthy);
+        assert!(!result.webhook.healthy);
+        assert_eq!(result.webhook.message.as_deref(), Some("1 failures, 1 warnings, run the healthcheck command for details"));
+        let body = serde_json::to_string(&result).unwrap();
+        assert!(!body.contains(SECRET_ERROR), "{}", body);
+    }
+
+    #[test]
+    fn unreachable_telegram_fails_readiness() {
+        let database = ComponentStatus::new(true, "In-memory repository".to_string());
+        let webhook_info = WebhookCheckResult::error(format!("WebhookInfoError: {}", SECRET_ERROR));
+        let result = running_bot().assess(database, &webhook_info, Duration::ZERO);
+        assert!(!result.ok);
+        assert!(!result.telegram.healthy);
+        let body = serde_json::to_string(&result).unwrap();
+        assert!(!body.contains(SECRET_ERROR), "{}", body);
+    }
+
+    #[test]
+    fn stopped_dispatcher_or_database_fail_readiness() {
+        let webhook_info = webhook_info_with_findings();
+        let stopped = BotReadiness::new(None);
+        assert!(!stopped.assess(ComponentStatus::new(true, String::new()), &webhook_info, Duration::ZERO).ok);
+        assert!(!running_bot().assess(ComponentStatus::new(false, String::new()), &webhook_info, Duration::ZERO).ok);
+    }
+}
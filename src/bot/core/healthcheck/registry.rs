@@ -0,0 +1,201 @@
+use std::time::Instant;
+
+use crate::bot::core::bot_config::healthcheck::BotHealthcheckConfig;
+use crate::bot::core::bot_config::webhook::BotConfigWebHook;
+use crate::bot::core::healthcheck::task::{HealthcheckCollectedResult, HealthcheckResult, HealthcheckTask, HealthcheckTaskResult, TaskResult};
+use crate::bot::core::healthcheck::tasks::own_health_endpoint::HealthEndpointCheck;
+use crate::bot::core::healthcheck::tasks::webhook::WebhookCheckTask;
+
+struct RegisteredTask {
+    name: &'static str,
+    create: fn() -> Box<dyn HealthcheckTask>,
+    /// Used if the configuration does not list the enabled tasks
+    enabled_by_default: fn() -> bool,
+}
+
+/// Known healthcheck tasks, enabled via `TELOXIDE_HEALTHCHECK_TASKS`.
+pub(crate) struct HealthcheckRegistry {
+    tasks: Vec<RegisteredTask>,
+}
+
+impl HealthcheckRegistry {
+    pub(crate) fn new() -> Self {
+        let mut registry = Self { tasks: vec![] };
+        registry.register("WebhookInfo", || Box::new(WebhookCheckTask::new()), || true);
+        // the public healthcheck url only exists in webhook mode
+        registry.register("OwnEndpoint", || Box::new(HealthEndpointCheck::new()), BotConfigWebHook::is_configured);
+        registry
+    }
+
+    pub(crate) fn register(&mut self, name: &'static str, create: fn() -> Box<dyn HealthcheckTask>, enabled_by_default: fn() -> bool) {
+        self.tasks.push(RegisteredTask { name, create, enabled_by_default });
+    }
+
+    pub(crate) fn names(&self) -> Vec<&'static str> {
+        self.tasks.iter().map(|task| task.name).collect()
+    }
+
+    /// Run the enabled tasks concurrently, each limited by the configured timeout.
+    pub(crate) async fn run(&self, config: &BotHealthcheckConfig) -> HealthcheckCollectedResult {
+        let start = Instant::now();
+        let timeout = config.timeout;
+        let handles = self.tasks.iter()
+            .filter(|task| config.is_enabled(task.name).unwrap_or_else(task.enabled_by_default))
+            .map(|task| {
+                let mut instance = (task.create)();
+                let handle = tokio::spawn(async move {
+                    let task_start = Instant::now();
+                    let timed_out = tokio::time::timeout(timeout, instance.execute()).await.is_err();
+                    let duration_ms = task_start.elapsed().as_millis();
+                    if timed_out {
+                        TaskResult {
+                            name: instance.name().to_string(),
+                            status: HealthcheckTaskResult::TimedOut,
+                            duration_ms,
+                            result: HealthcheckResult::NoResult(format!("No result within {:?}", timeout)),
+                        }
+                    } else {
+                        TaskResult {
+                            name: instance.name().to_string(),
+                            status: instance.check_result(),
+                            duration_ms,
+                            result: instance.get_result(),
+                        }
+                    }
+                });
+                (task.name, handle)
+            })
+            .collect::<Vec<_>>();
+
+        let mut tasks = vec![];
+        for (name, handle) in handles {
+            match handle.await {
+                Ok(result) => tasks.push(result),
+                Err(error) => tasks.push(TaskResult {
+                    name: name.to_string(),
+                    status: HealthcheckTaskResult::Failed,
+                    duration_ms: 0,
+                    result: HealthcheckResult::NoResult(format!("Task panicked: {}", error)),
+                }),
+            }
+        }
+        HealthcheckCollectedResult {
+            healthy: tasks.iter().all(|task| matches!(task.status, HealthcheckTaskResult::Success | HealthcheckTaskResult::Warning | HealthcheckTaskResult::Unchecked)),
+            duration_ms: start.elapsed().as_millis(),
+            tasks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::bot::core::bot_config::healthcheck::BotHealthcheckConfig;
+    use crate::bot::core::healthcheck::registry::HealthcheckRegistry;
+    use crate::bot::core::healthcheck::task::{HealthcheckCollectedResult, HealthcheckResult, HealthcheckTask, HealthcheckTaskResult, TaskFuture};
+
+    const TASK_DURATION: Duration = Duration::from_millis(300);
+
+    enum Behavior {
+        Sleep(Duration),
+        Panic,
+    }
+
+    struct StubTask {
+        name: &'static str,
+        behavior: Behavior,
+        finished: bool,
+    }
+
+    impl StubTask {
+        fn boxed(name: &'static str, behavior: Behavior) -> Box<dyn HealthcheckTask> {
+            Box::new(Self { name, behavior, finished: false })
+        }
+    }
+
+    impl HealthcheckTask for StubTask {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn execute(&mut self) -> TaskFuture<'_> {
+            Box::pin(async move {
+                match self.behavior {
+                    Behavior::Sleep(duration) => tokio::time::sleep(duration).await,
+                    Behavior::Panic => panic!("{} failed", self.name),
+                }
+                self.finished = true;
+            })
+        }
+
+        fn check_result(&self) -> HealthcheckTaskResult {
+            if self.finished { HealthcheckTaskResult::Success } else { HealthcheckTaskResult::Failed }
+        }
+
+        fn get_result(&self) -> HealthcheckResult {
+            HealthcheckResult::NoResult(format!("{} finished", self.name))
+        }
+    }
+
+    fn registry() -> HealthcheckRegistry {
+        let mut registry = HealthcheckRegistry { tasks: vec![] };
+        registry.register("First", || StubTask::boxed("First", Behavior::Sleep(TASK_DURATION)), || true);
+        registry.register("Second", || StubTask::boxed("Second", Behavior::Sleep(TASK_DURATION)), || true);
+        registry.register("Slow", || StubTask::boxed("Slow", Behavior::Sleep(Duration::from_secs(60))), || false);
+        registry.register("Panicking", || StubTask::boxed("Panicking", Behavior::Panic), || false);
+        registry
+    }
+
+    fn config(tasks: Option<&[&str]>, timeout: Duration) -> BotHealthcheckConfig {
+        BotHealthcheckConfig {
+            tasks: tasks.map(|tasks| tasks.iter().map(|task| task.to_string()).collect()),
+            timeout,
+        }
+    }
+
+    fn status(result: &HealthcheckCollectedResult, name: &str) -> Option<HealthcheckTaskResult> {
+        result.tasks.iter().find(|task| task.name == name).map(|task| task.status.clone())
+    }
+
+    #[tokio::test]
+    async fn runs_tasks_in_parallel() {
+        let start = Instant::now();
+        let result = registry().run(&config(None, Duration::from_secs(10))).await;
+        assert!(start.elapsed() < 2 * TASK_DURATION, "took {:?}", start.elapsed());
+        assert!(result.healthy);
+        assert_eq!(status(&result, "First"), Some(HealthcheckTaskResult::Success));
+        assert_eq!(status(&result, "Second"), Some(HealthcheckTaskResult::Success));
+    }
+
+    #[tokio::test]
+    async fn times_out_each_task() {
+        let start = Instant::now();
+        let result = registry().run(&config(Some(&["First", "Slow"]), 2 * TASK_DURATION)).await;
+        assert!(start.elapsed() < 4 * TASK_DURATION, "took {:?}", start.elapsed());
+        assert!(!result.healthy);
+        assert_eq!(status(&result, "First"), Some(HealthcheckTaskResult::Success));
+        assert_eq!(status(&result, "Slow"), Some(HealthcheckTaskResult::TimedOut));
+    }
+
+    #[tokio::test]
+    async fn reports_panicking_task_as_failed() {
+        let result = registry().run(&config(Some(&["Panicking", "Second"]), Duration::from_secs(10))).await;
+        assert!(!result.healthy);
+        assert_eq!(status(&result, "Second"), Some(HealthcheckTaskResult::Success));
+        let panicked = result.tasks.iter().find(|task| task.name == "Panicking").unwrap();
+        assert_eq!(panicked.status, HealthcheckTaskResult::Failed);
+        assert!(matches!(&panicked.result, HealthcheckResult::NoResult(message) if message.starts_with("Task panicked")), "{:?}", panicked.result);
+    }
+
+    #[tokio::test]
+    async fn runs_configured_tasks_only() {
+        // names are matched case-insensitive like in TELOXIDE_HEALTHCHECK_TASKS
+        let result = registry().run(&config(Some(&["second"]), Duration::from_secs(10))).await;
+        assert_eq!(result.tasks.iter().map(|task| task.name.as_str()).collect::<Vec<_>>(), vec!["Second"]);
+
+        // without configured tasks only the ones enabled by default run
+        let result = registry().run(&config(None, Duration::from_secs(10))).await;
+        assert_eq!(result.tasks.iter().map(|task| task.name.as_str()).collect::<Vec<_>>(), vec!["First", "Second"]);
+    }
+}
@@ -1,19 +1,29 @@
+use std::future::Future;
+use std::pin::Pin;
+
 use serde::Serialize;
 
+use crate::bot::core::healthcheck::tasks::own_health_endpoint::OwnEndpointCheckResult;
 use crate::bot::core::healthcheck::webhook_info::WebhookCheckResult;
 
-pub trait HealthcheckTask {
+/// Boxed future of [`HealthcheckTask::execute`], keeps the trait object-safe.
+pub type TaskFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+pub trait HealthcheckTask: Send {
+    /// Name used in the result and to enable the task in the configuration
+    fn name(&self) -> &'static str;
     /// Actually execute the check
-    async fn execute(&mut self);
+    fn execute(&mut self) -> TaskFuture<'_>;
     /// Check if task execution succeeded
     fn check_result(&self) -> HealthcheckTaskResult;
     /// get result for documentation
-    fn get_result(&self) -> TaskResult;
+    fn get_result(&self) -> HealthcheckResult;
 }
 
 #[derive(Debug, Serialize, Clone)]
 pub struct HealthcheckCollectedResult {
     pub healthy: bool,
+    pub duration_ms: u128,
     pub tasks: Vec<TaskResult>,
 }
 impl HealthcheckCollectedResult {
@@ -25,21 +35,28 @@ impl HealthcheckCollectedResult {
 #[derive(Debug, Serialize, Clone)]
 pub struct TaskResult {
     pub name: String,
+    pub status: HealthcheckTaskResult,
+    pub duration_ms: u128,
     pub result: HealthcheckResult,
 }
 
 #[derive(Debug, Serialize, Clone)]
 pub enum HealthcheckResult {
     WebhookInfo(WebhookCheckResult),
+    OwnEndpoint(OwnEndpointCheckResult),
     NoResult(String),
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub enum HealthcheckTaskResult {
     /// Task failed
     Failed,
     /// Task succeeded
     Success,
+    /// Task succeeded, but reported problems that need attention
+    Warning,
     /// Task result is ignored, document result only
     Unchecked,
-}
\ No newline at end of file
+    /// Task did not finish within the configured timeout, counts as failed
+    TimedOut,
+}
@@ -1,2 +1,2 @@
 pub mod webhook;
-mod own_health_endpoint;
\ No newline at end of file
+pub mod own_health_endpoint;
\ No newline at end of file
@@ -1,31 +1,183 @@
+use std::time::Instant;
+
+use reqwest::Url;
+use serde::Serialize;
+
 use crate::bot::core::bot_config::webhook::BotConfigWebHook;
-use crate::bot::core::healthcheck::task::{HealthcheckTask, HealthcheckTaskResult, TaskResult};
+use crate::bot::core::healthcheck::endpoint::BotHealthResult;
+use crate::bot::core::healthcheck::task::{HealthcheckResult, HealthcheckTask, HealthcheckTaskResult, TaskFuture};
 
-pub struct HealthEndpointCheck {
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct OwnEndpointCheckResult {
+    pub healthy: bool,
+    pub url: Option<String>,
+    pub status_code: Option<u16>,
+    /// Time until the response was read completely
+    pub duration_ms: Option<u128>,
+    pub message: Option<String>,
+    pub reply: Option<BotHealthResult>,
+}
 
+impl OwnEndpointCheckResult {
+    fn failed(url: Option<String>, message: String) -> Self {
+        Self {
+            healthy: false,
+            url,
+            status_code: None,
+            duration_ms: None,
+            message: Some(message),
+            reply: None,
+        }
+    }
 }
 
-impl HealthcheckTask for HealthEndpointCheck {
-    async fn execute(&mut self) {
-        let bot_config = BotConfigWebHook::new();
-        match bot_config {
-            Ok(bot_config) => {
-                let _bot_health_reply = reqwest::get(bot_config.public_healthcheck_url)
-                    .await;
-                todo!()
+/// Calls the public `/healthcheck` url of the bot, this covers TLS termination and proxy in front of the bot.
+pub struct HealthEndpointCheck {
+    result: Option<OwnEndpointCheckResult>,
+}
+
+impl HealthEndpointCheck {
+    pub fn new() -> Self {
+        Self {
+            result: None,
+        }
+    }
+
+    async fn call_own_endpoint() -> OwnEndpointCheckResult {
+        let webhook_config = match BotConfigWebHook::new() {
+            Ok(webhook_config) => webhook_config,
+            Err(error) => {
+                return OwnEndpointCheckResult::failed(None, format!("ConfigError: {}", error));
+            }
+        };
+        Self::check_endpoint(webhook_config.public_healthcheck_url).await
+    }
+
+    /// Healthy with a successful status and `ok` in the reply.
+    async fn check_endpoint(url: Url) -> OwnEndpointCheckResult {
+        // the registry limits the duration of the task
+        let start = Instant::now();
+        let response = match reqwest::get(url.clone()).await {
+            Ok(response) => response,
+            Err(error) => {
+                return OwnEndpointCheckResult::failed(Some(url.to_string()), format!("Bot did not respond: {}", error));
             }
-            Err(_error) => {
-                // webhook configuration error
+        };
+        let status_code = response.status();
+        let body = response.text().await;
+        let duration_ms = start.elapsed().as_millis();
 
+        let mut result = OwnEndpointCheckResult {
+            healthy: false,
+            url: Some(url.to_string()),
+            status_code: Some(status_code.as_u16()),
+            duration_ms: Some(duration_ms),
+            message: None,
+            reply: None,
+        };
+        match body {
+            Ok(body) => {
+                match serde_json::from_str::<BotHealthResult>(&body) {
+                    Ok(reply) => {
+                        result.healthy = status_code.is_success() && reply.ok;
+                        if !result.healthy {
+                            result.message = Some(format!("Bot replied with status {} and ok={}", status_code, reply.ok));
+                        }
+                        result.reply = Some(reply);
+                    }
+                    Err(error) => {
+                        result.message = Some(format!("Could not parse response of bot with status {}: {}", status_code, error));
+                    }
+                }
+            }
+            Err(error) => {
+                result.message = Some(format!("Could not fetch response of bot: {}", error));
             }
         }
+        result
+    }
+}
+
+impl HealthcheckTask for HealthEndpointCheck {
+    fn name(&self) -> &'static str {
+        "OwnEndpoint"
+    }
+
+    fn execute(&mut self) -> TaskFuture<'_> {
+        Box::pin(async move {
+            self.result = Some(HealthEndpointCheck::call_own_endpoint().await);
+        })
     }
 
     fn check_result(&self) -> HealthcheckTaskResult {
-        todo!()
+        match &self.result {
+            None => {
+                HealthcheckTaskResult::Unchecked
+            }
+            Some(result) => {
+                if result.healthy {
+                    HealthcheckTaskResult::Success
+                } else {
+                    HealthcheckTaskResult::Failed
+                }
+            }
+        }
+    }
+
+    fn get_result(&self) -> HealthcheckResult {
+        match self.result.clone() {
+            None => HealthcheckResult::NoResult("No result".to_string()),
+            Some(result) => HealthcheckResult::OwnEndpoint(result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use axum::routing::get;
+
+    use crate::bot::core::healthcheck::task::{HealthcheckTask, HealthcheckTaskResult};
+    use crate::bot::core::healthcheck::tasks::own_health_endpoint::{HealthEndpointCheck, OwnEndpointCheckResult};
+    use crate::bot::core::mock_api::serve_local;
+
+    async fn check(status: StatusCode, body: &'static str) -> (HealthcheckTaskResult, OwnEndpointCheckResult) {
+        let router = axum::Router::new().route("/healthcheck", get(move || async move { (status, body) }));
+        let url = serve_local(router).await.join("healthcheck").unwrap();
+        let result = HealthEndpointCheck::check_endpoint(url).await;
+        let task = HealthEndpointCheck { result: Some(result.clone()) };
+        (task.check_result(), result)
+    }
+
+    #[tokio::test]
+    async fn succeeds_with_healthy_reply() {
+        let (status, result) = check(StatusCode::OK, r#"{"ok":true}"#).await;
+        assert_eq!(status, HealthcheckTaskResult::Success);
+        assert_eq!(result.status_code, Some(200));
+        assert!(result.reply.is_some_and(|reply| reply.ok));
+        assert_eq!(result.message, None);
     }
 
-    fn get_result(&self) -> TaskResult {
-        todo!()
+    #[tokio::test]
+    async fn fails_with_error_status() {
+        let (status, result) = check(StatusCode::SERVICE_UNAVAILABLE, r#"{"ok":true}"#).await;
+        assert_eq!(status, HealthcheckTaskResult::Failed);
+        assert_eq!(result.status_code, Some(503));
+        assert_eq!(result.message.as_deref(), Some("Bot replied with status 503 Service Unavailable and ok=true"));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn fails_with_unhealthy_reply() {
+        let (status, result) = check(StatusCode::OK, r#"{"ok":false}"#).await;
+        assert_eq!(status, HealthcheckTaskResult::Failed);
+        assert_eq!(result.message.as_deref(), Some("Bot replied with status 200 OK and ok=false"));
+    }
+
+    #[tokio::test]
+    async fn fails_with_invalid_reply() {
+        let (status, result) = check(StatusCode::OK, "<html>proxy error</html>").await;
+        assert_eq!(status, HealthcheckTaskResult::Failed);
+        assert!(result.reply.is_none());
+        assert!(result.message.as_ref().is_some_and(|message| message.starts_with("Could not parse response of bot with status 200 OK: ")), "{:?}", result.message);
+    }
+}
@@ -1,7 +1,9 @@
+use crate::bot::core::bot_config::BotConfig;
+use crate::bot::core::bot_config::healthcheck::WebhookInfoLimits;
 use crate::bot::core::bot_config::webhook::BotConfigWebHook;
-use crate::bot::core::healthcheck::task::{HealthcheckResult, HealthcheckTask, HealthcheckTaskResult, TaskResult};
+use crate::bot::core::healthcheck::task::{HealthcheckResult, HealthcheckTask, HealthcheckTaskResult, TaskFuture};
 use crate::bot::core::healthcheck::webhook_info;
-use crate::bot::core::healthcheck::webhook_info::{TelegramUpdateMode, WebhookCheckResult};
+use crate::bot::core::healthcheck::webhook_info::WebhookCheckResult;
 
 pub struct WebhookCheckTask {
     result: Option<WebhookCheckResult>,
@@ -13,34 +15,49 @@ impl WebhookCheckTask {
             result: None,
         }
     }
-    async fn telegram_healthcheck_api() -> WebhookCheckResult {
-        let webhook_response = webhook_info::telegram_check_webhook_info().await;
+    pub(crate) async fn telegram_healthcheck_api() -> WebhookCheckResult {
+        let (bot_config, limits) = match (BotConfig::new(), WebhookInfoLimits::new()) {
+            (Ok(bot_config), Ok(limits)) => (bot_config, limits),
+            (Err(error), _) | (_, Err(error)) => {
+                return WebhookCheckResult::error(format!("ConfigError: {}", error));
+            }
+        };
+        let webhook_response = webhook_info::telegram_check_webhook_info(&bot_config).await;
         let bot_webhook_config = BotConfigWebHook::new();
 
-        match (webhook_response, bot_webhook_config) {
+        let mut result = match (webhook_response, bot_webhook_config) {
             (Ok(webhook_response), Ok(webhook_config)) => {
                 // webhook configured
-                webhook_response.check_webhook_mode(webhook_config.public_bot_url.as_ref())
+                let mut result = webhook_response.check_webhook_mode(webhook_config.public_bot_url.as_ref(), &limits);
+                if webhook_config.secret_token.is_none() {
+                    // a random secret token is generated on every start
+                    result.warn("Webhook secret token is not configured, set TELOXIDE_WEBHOOK_SECRET.".to_string());
+                }
+                result
             }
             (Ok(webhook_response), Err(_webhook_error)) => {
                 // either using getUpdates or missing environment variables for webhook
                 webhook_response.check_get_updates_mode()
             }
             (Err(webhook_error), _) => {
-                WebhookCheckResult {
-                    healthy: false,
-                    mode: TelegramUpdateMode::Error,
-                    message: Option::from(format!("WebhookInfoError: {}", webhook_error)),
-                    result: None,
-                }
+                WebhookCheckResult::error(format!("WebhookInfoError: {}", webhook_error))
             }
-        }
+        };
+        // never report the url including the bot token
+        result.api_url = Some(bot_config.api_url);
+        result
     }
 }
 
 impl HealthcheckTask for WebhookCheckTask {
-    async fn execute(&mut self) {
-        self.result = Some(WebhookCheckTask::telegram_healthcheck_api().await);
+    fn name(&self) -> &'static str {
+        "WebhookInfo"
+    }
+
+    fn execute(&mut self) -> TaskFuture<'_> {
+        Box::pin(async move {
+            self.result = Some(WebhookCheckTask::telegram_healthcheck_api().await);
+        })
     }
 
     fn check_result(&self) -> HealthcheckTaskResult {
@@ -49,8 +66,10 @@ impl HealthcheckTask for WebhookCheckTask {
                 HealthcheckTaskResult::Unchecked
             }
             Some(result) => {
-                if result.healthy {
+                if result.healthy && result.warnings.is_empty() {
                     HealthcheckTaskResult::Success
+                } else if result.healthy {
+                    HealthcheckTaskResult::Warning
                 } else {
                     HealthcheckTaskResult::Failed
                 }
@@ -58,21 +77,10 @@ impl HealthcheckTask for WebhookCheckTask {
         }
     }
 
-    fn get_result(&self) -> TaskResult {
+    fn get_result(&self) -> HealthcheckResult {
         match self.result.clone() {
-            None => {
-                TaskResult {
-                    name: "WebhookInfo".to_string(),
-                    result: HealthcheckResult::NoResult("No result".to_string()),
-                }
-            }
-            Some(result) => {
-                TaskResult {
-                    name: "WebhookInfo".to_string(),
-                    result: HealthcheckResult::WebhookInfo(result),
-                }
-            }
+            None => HealthcheckResult::NoResult("No result".to_string()),
+            Some(result) => HealthcheckResult::WebhookInfo(result),
         }
-
     }
 }
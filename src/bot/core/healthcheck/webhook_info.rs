@@ -1,7 +1,13 @@
 use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
+use teloxide::types::AllowedUpdate;
 
 use crate::bot::core::bot_config::BotConfig;
+use crate::bot::core::bot_config::healthcheck::WebhookInfoLimits;
+use crate::bot::schema::HANDLED_UPDATES;
+
+/// Update types telegram does not deliver if `allowed_updates` is empty
+const NOT_DELIVERED_BY_DEFAULT: &[AllowedUpdate] = &[AllowedUpdate::ChatMember, AllowedUpdate::MessageReaction, AllowedUpdate::MessageReactionCount];
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum TelegramUpdateMode {
@@ -15,7 +21,37 @@ pub(crate) struct WebhookCheckResult {
     pub healthy: bool,
     pub mode: TelegramUpdateMode,
     pub message: Option<String>,
+    /// Problems of the webhook that make the check fail
+    pub failures: Vec<String>,
+    /// Problems of the webhook that are reported without failing the check
+    pub warnings: Vec<String>,
     pub result: Option<TelegramWebhookInfoResult>,
+    /// Telegram Bot API server that was checked
+    pub api_url: Option<String>,
+}
+
+impl WebhookCheckResult {
+    /// getWebhookInfo could not be requested or evaluated.
+    pub(crate) fn error(message: String) -> Self {
+        Self {
+            healthy: false,
+            mode: TelegramUpdateMode::Error,
+            message: Some(message),
+            failures: vec![],
+            warnings: vec![],
+            result: None,
+            api_url: None,
+        }
+    }
+
+    pub(crate) fn fail(&mut self, failure: String) {
+        self.healthy = false;
+        self.failures.push(failure);
+    }
+
+    pub(crate) fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
 }
 
 /// See https://core.telegram.org/bots/api#getwebhookinfo
@@ -29,21 +65,26 @@ pub(crate) struct TelegramWebhookInfoResult {
     _has_custom_certificate: bool,
     /// Number of updates awaiting delivery
     #[serde(rename = "pending_update_count")]
-    _pending_update_count: u64,
+    pub pending_update_count: u64,
     /// Optional. Currently used webhook IP address
     #[serde(rename = "ip_address")]
     _ip_address: Option<String>,
     /// Optional. Error message in human-readable format for the most recent error that happened when trying to deliver an update via webhook
     #[serde(rename = "last_error_message")]
-    _last_error_message: Option<String>,
+    pub last_error_message: Option<String>,
     /// Optional. Unix time for the most recent error that happened when trying to deliver an update via webhook
     #[serde(rename = "last_error_date")]
-    _last_error_date: Option<String>,
+    pub last_error_date: Option<i64>,
+    /// Optional. Unix time of the most recent error that happened when trying to synchronize available updates with Telegram datacenters
+    #[serde(rename = "last_synchronization_error_date")]
+    pub last_synchronization_error_date: Option<i64>,
     /// The maximum allowed number of simultaneous HTTPS connections to the webhook for update delivery, 1-100. Defaults to 40.
     /// Use lower values to limit the load on your bot's server, and higher values to increase your bot's throughput.
     #[serde(rename = "max_connections")]
-    _max_connections: Option<u64>,
-
+    pub max_connections: Option<u64>,
+    /// Optional. A list of update types the bot is subscribed to. Defaults to all update types except chat_member
+    #[serde(rename = "allowed_updates")]
+    pub allowed_updates: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -56,61 +97,164 @@ pub(crate) struct TelegramWebhookInfoResponse {
 
 impl TelegramWebhookInfoResponse {
     fn healthy(&self, mode: TelegramUpdateMode) -> WebhookCheckResult {
+        let mut result = WebhookCheckResult {
+            healthy: true,
+            mode,
+            message: None,
+            failures: vec![],
+            warnings: vec![],
+            result: Some(self.result.clone()),
+            api_url: None,
+        };
         if self.ok.is_none() || self.error_code.is_some() {
             if let Some(404) = self.error_code {
-                WebhookCheckResult {
-                    healthy: false,
-                    mode,
-                    message: Some("Invalid/missing authentication.".to_string()),
-                    result: Some(self.result.clone()),
-                }
+                result.fail("Invalid/missing authentication.".to_string());
             } else {
-                WebhookCheckResult {
-                    healthy: false,
-                    mode,
-                    message: Some("Unknown error".to_string()),
-                    result: Some(self.result.clone()),
-                }
-            }
-        } else {
-            WebhookCheckResult {
-                healthy: true,
-                mode,
-                message: None,
-                result: Some(self.result.clone()),
+                result.fail("Unknown error".to_string());
             }
         }
+        result
     }
 
     pub(crate) fn check_get_updates_mode(&self) -> WebhookCheckResult {
         self.healthy(TelegramUpdateMode::GetUpdates)
     }
 
-    pub(crate) fn check_webhook_mode(&self, public_bot_url: &str) -> WebhookCheckResult {
-        let assessment = self.healthy(TelegramUpdateMode::Webhook);
-        if self.result.url.ne(public_bot_url) {
-            WebhookCheckResult {
-                healthy: false,
-                mode: TelegramUpdateMode::Webhook,
-                message: Some(format!("Public url in telegram api does not match public url configure in bot! Found api public_url={} configured={:?}",
-                                      self.result.url.clone(), public_bot_url)),
-                result: Some(self.result.clone()),
+    pub(crate) fn check_webhook_mode(&self, public_bot_url: &str, limits: &WebhookInfoLimits) -> WebhookCheckResult {
+        let mut assessment = self.healthy(TelegramUpdateMode::Webhook);
+        let info = &self.result;
+        if info.url.ne(public_bot_url) {
+            assessment.fail(format!("Public url in telegram api does not match public url configure in bot! Found api public_url={} configured={:?}",
+                                    info.url, public_bot_url));
+        }
+
+        if info.pending_update_count > limits.max_pending_updates {
+            assessment.fail(format!("{} updates are pending, more than {}", info.pending_update_count, limits.max_pending_updates));
+        } else if let Some(max_connections) = info.max_connections.filter(|max_connections| info.pending_update_count > *max_connections) {
+            assessment.warn(format!("{} updates are pending, more than max_connections={}", info.pending_update_count, max_connections));
+        }
+
+        let now = chrono::offset::Utc::now().timestamp();
+        if let Some(last_error_date) = info.last_error_date {
+            let seconds_ago = now.saturating_sub(last_error_date);
+            let description = format!("Last delivery error {}s ago: {}", seconds_ago, info.last_error_message.as_deref().unwrap_or("unknown"));
+            if seconds_ago < limits.error_window.as_secs() as i64 {
+                assessment.fail(description);
+            } else if seconds_ago < limits.warn_window.as_secs() as i64 {
+                assessment.warn(description);
             }
-        } else {
-            assessment
+        }
+        if let Some(synchronization_error_date) = info.last_synchronization_error_date {
+            let seconds_ago = now.saturating_sub(synchronization_error_date);
+            if seconds_ago < limits.error_window.as_secs() as i64 {
+                assessment.warn(format!("Telegram datacenter synchronization error {}s ago", seconds_ago));
+            }
+        }
+
+        self.check_allowed_updates(&mut assessment);
+        assessment
+    }
+
+    /// Compare the subscribed update types with the ones [`crate::bot::schema::schema`] handles.
+    fn check_allowed_updates(&self, assessment: &mut WebhookCheckResult) {
+        let subscribed = self.result.allowed_updates.clone().unwrap_or_default();
+        let missing = HANDLED_UPDATES.iter()
+            .filter(|handled| {
+                let name = update_type_name(handled);
+                if subscribed.is_empty() {
+                    NOT_DELIVERED_BY_DEFAULT.contains(handled)
+                } else {
+                    !subscribed.contains(&name)
+                }
+            })
+            .map(update_type_name)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            assessment.fail(format!("Telegram does not deliver handled update types: {}", missing.join(", ")));
+        }
+
+        let unhandled = subscribed.iter()
+            .filter(|name| !HANDLED_UPDATES.iter().any(|handled| update_type_name(handled).eq(*name)))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !unhandled.is_empty() {
+            assessment.warn(format!("Telegram delivers unhandled update types: {}", unhandled.join(", ")));
         }
     }
 }
 
-pub(crate) async fn telegram_check_webhook_info() -> Result<TelegramWebhookInfoResponse, anyhow::Error> {
-    let bot_config = BotConfig::new()?;
+/// Name of the update type as used by the bot api, e.g. `callback_query`.
+fn update_type_name(update: &AllowedUpdate) -> String {
+    serde_json::to_value(update).ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("{:?}", update))
+}
 
-    let webhook_info_url = format!("https://api.telegram.org/bot{}/getWebhookInfo", bot_config.bot_token);
+pub(crate) async fn telegram_check_webhook_info(bot_config: &BotConfig) -> Result<TelegramWebhookInfoResponse, anyhow::Error> {
+    let webhook_info_url = bot_config.telegram_method_url("getWebhookInfo")?;
+    // the request url contains the bot token
     let body = reqwest::get(webhook_info_url)
-        .await?
+        .await
+        .map_err(|error| error.without_url())?
         .json::<serde_json::Value>()
-        .await?;
+        .await
+        .map_err(|error| error.without_url())?;
 
     serde_json::from_value::<TelegramWebhookInfoResponse>(body.clone())
         .map_err(|error| anyhow!("Could not parse webhook info: {}. Error: {}", body, error))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::bot::core::bot_config::healthcheck::WebhookInfoLimits;
+    use crate::bot::core::healthcheck::webhook_info::TelegramWebhookInfoResponse;
+
+    const PUBLIC_URL: &str = "https://bot.example.com/bot";
+
+    fn check_last_error(seconds_ago: i64) -> (Vec<String>, Vec<String>) {
+        let response = serde_json::from_value::<TelegramWebhookInfoResponse>(json!({
+            "ok": true,
+            "result": {
+                "url": PUBLIC_URL,
+                "has_custom_certificate": false,
+                "pending_update_count": 0,
+                "last_error_date": chrono::Utc::now().timestamp() - seconds_ago,
+                "last_error_message": "Connection refused",
+                "allowed_updates": ["message", "callback_query", "my_chat_member"],
+            },
+        })).unwrap();
+        let limits = WebhookInfoLimits {
+            max_pending_updates: 100,
+            error_window: Duration::from_secs(10 * 60),
+            warn_window: Duration::from_secs(24 * 60 * 60),
+        };
+        let result = response.check_webhook_mode(PUBLIC_URL, &limits);
+        (result.failures, result.warnings)
+    }
+
+    #[test]
+    fn recent_delivery_error_fails() {
+        let (failures, warnings) = check_last_error(60);
+        assert_eq!(failures.len(), 1, "{:?}", failures);
+        assert!(failures[0].contains("Connection refused"));
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn older_delivery_error_warns() {
+        let (failures, warnings) = check_last_error(2 * 60 * 60);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    }
+
+    #[test]
+    fn delivery_error_outside_warn_window_is_ignored() {
+        let (failures, warnings) = check_last_error(3 * 24 * 60 * 60);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+}
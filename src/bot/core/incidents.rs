@@ -0,0 +1,272 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::dispatching::{DpHandlerDescription, UpdateHandler};
+use teloxide::dptree;
+use teloxide::dptree::di::{DependencyMap, DependencySupplier};
+use teloxide::dptree::HandlerDescription;
+use teloxide::prelude::Requester;
+use teloxide::types::{ChatId, Update};
+use teloxide::Bot;
+
+use crate::bot::core::db::model::Incident;
+use crate::bot::core::metrics::{metrics, MeasuredRequest};
+use crate::bot::core::redaction::{redact_secrets, TelegramId};
+use crate::bot::core::repository::{BotRepository, IncidentRepository, UserRepository};
+use crate::bot::core::telemetry::dialogue_state_name;
+use crate::bot::core::util::random_incident_id;
+use crate::bot::State;
+
+type HandlerError = Box<dyn Error + Send + Sync + 'static>;
+
+/// The same error is notified to the admins once within this time
+const DEDUPLICATION_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// At most [`MAX_NOTIFICATIONS`] admin notifications within this time
+const THROTTLE_WINDOW: Duration = Duration::from_secs(10 * 60);
+const MAX_NOTIFICATIONS: usize = 5;
+/// Keeps admin notifications well below the message length limit
+const MAX_ERROR_LENGTH: usize = 1000;
+
+static NOTIFICATION_THROTTLE: OnceLock<Mutex<NotificationThrottle>> = OnceLock::new();
+
+/// Turns errors of the following handlers into incidents, the affected user gets a reply and the admins a notification.
+pub(crate) fn report_incidents<R: BotRepository>() -> UpdateHandler<HandlerError> {
+    dptree::from_fn_with_description(DpHandlerDescription::entry(), |dependencies: DependencyMap, cont| async move {
+        let update: Arc<Update> = dependencies.get();
+        let bot: Arc<Bot> = dependencies.get();
+        let repository: Arc<R> = dependencies.get();
+        let storage: Arc<Arc<InMemStorage<State>>> = dependencies.get();
+        // the handlers may change the dialogue state before failing
+        let dialogue_state = dialogue_state_name(&update, Arc::clone(&storage)).await;
+        let result: ControlFlow<Result<(), HandlerError>, DependencyMap> = cont(dependencies).await;
+        match result {
+            ControlFlow::Break(Err(error)) => {
+                report_incident(&bot, repository.as_ref(), &update, dialogue_state, error.as_ref()).await;
+                // reported, the error handler of the dispatcher would only log it again
+                ControlFlow::Break(Ok(()))
+            }
+            result => result,
+        }
+    })
+}
+
+async fn report_incident<R: IncidentRepository + UserRepository>(bot: &Bot, repository: &R, update: &Update, dialogue_state: Option<&str>, error: &(dyn Error + Send + Sync + 'static)) {
+    metrics().record_handler_error(error);
+    let chat_id = update.chat().map(|chat| chat.id);
+    let incident = Incident {
+        id: random_incident_id(),
+        created_at: chrono::Utc::now().timestamp(),
+        update_id: update.id.0 as i64,
+        chat_id: chat_id.map(|chat_id| chat_id.0),
+        command: metrics().command_label(update).map(str::to_string),
+        dialogue_state: dialogue_state.map(str::to_string),
+        error: redact_secrets(&error.to_string()),
+    };
+    tracing::error!("Incident {} while handling an update: {}", incident.id, incident.error);
+
+    if let Err(error) = repository.record_incident(incident.clone()).await {
+        tracing::error!("Could not record incident {}: {}", incident.id, error);
+    }
+    if let Some(chat_id) = chat_id {
+        let text = format!("Sorry, something went wrong. Please try again later. If the problem persists, contact an administrator and mention incident {}.", incident.id);
+        if let Err(error) = bot.send_message(chat_id, text).send_measured().await {
+            tracing::warn!("Could not inform the user about incident {}: {}", incident.id, error);
+        }
+    }
+    notify_admins(bot, repository, &incident).await;
+}
+
+async fn notify_admins<R: UserRepository>(bot: &Bot, repository: &R, incident: &Incident) {
+    let admitted = notification_throttle().lock()
+        .expect("Notification throttle lock poisoned")
+        .admit(&fingerprint(incident), Instant::now());
+    let Some(suppressed) = admitted else {
+        tracing::debug!("Admin notification of incident {} suppressed", incident.id);
+        return;
+    };
+    let admins = match repository.list_registered_users().await {
+        Ok(users) => users.into_iter()
+            .filter(|user| user.is_admin())
+            .filter_map(|user| user.telegram_id)
+            .collect::<Vec<_>>(),
+        Err(error) => {
+            tracing::error!("Could not load admins to notify about incident {}: {}", incident.id, error);
+            return;
+        }
+    };
+
+    let text = admin_notification(incident, suppressed);
+    for admin in admins {
+        if let Err(error) = bot.send_message(ChatId(admin), &text).send_measured().await {
+            tracing::warn!("Could not notify admin {} about incident {}: {}", TelegramId(admin), incident.id, error);
+        }
+    }
+}
+
+fn admin_notification(incident: &Incident, suppressed: u32) -> String {
+    let mut text = format!("Incident {}", incident.id);
+    if let Some(command) = &incident.command {
+        text.push_str(&format!("\nCommand: /{}", command));
+    }
+    if let Some(dialogue_state) = &incident.dialogue_state {
+        text.push_str(&format!("\nDialogue state: {}", dialogue_state));
+    }
+    if let Some(chat_id) = incident.chat_id {
+        // the hash as in the logs, `admin incidents` shows the chat id
+        text.push_str(&format!("\nChat: {}", TelegramId(chat_id)));
+    }
+    let error = match incident.error.char_indices().nth(MAX_ERROR_LENGTH) {
+        Some((end, _)) => format!("{}...", &incident.error[..end]),
+        None => incident.error.clone(),
+    };
+    text.push_str(&format!("\nError: {}", error));
+    if suppressed > 0 {
+        text.push_str(&format!("\n\n{} more incidents like this were not notified.", suppressed));
+    }
+    text
+}
+
+/// Errors differing only in numbers, e.g. ids or durations, are the same.
+fn fingerprint(incident: &Incident) -> String {
+    let error = incident.error.chars()
+        .map(|char| if char.is_ascii_digit() { '#' } else { char })
+        .collect::<String>();
+    format!("{}|{}", incident.command.as_deref().unwrap_or_default(), error)
+}
+
+fn notification_throttle() -> &'static Mutex<NotificationThrottle> {
+    NOTIFICATION_THROTTLE.get_or_init(Default::default)
+}
+
+/// Keeps a failing dependency from flooding the admins with notifications.
+#[derive(Default)]
+struct NotificationThrottle {
+    fingerprints: HashMap<String, FingerprintState>,
+    /// Times of the notifications within the throttle window
+    sent: VecDeque<Instant>,
+}
+
+#[derive(Default)]
+struct FingerprintState {
+    last_notified: Option<Instant>,
+    suppressed: u32,
+}
+
+impl NotificationThrottle {
+    /// The number of suppressed incidents to mention, None if this incident is suppressed as well.
+    fn admit(&mut self, fingerprint: &str, now: Instant) -> Option<u32> {
+        let recent = |instant: Instant, window: Duration| now.duration_since(instant) < window;
+        while self.sent.front().is_some_and(|sent| !recent(*sent, THROTTLE_WINDOW)) {
+            self.sent.pop_front();
+        }
+        self.fingerprints.retain(|_, state| {
+            state.suppressed > 0 || state.last_notified.is_some_and(|last| recent(last, DEDUPLICATION_WINDOW))
+        });
+
+        let state = self.fingerprints.entry(fingerprint.to_string()).or_default();
+        let duplicate = state.last_notified.is_some_and(|last| recent(last, DEDUPLICATION_WINDOW));
+        if duplicate || self.sent.len() >= MAX_NOTIFICATIONS {
+            state.suppressed += 1;
+            return None;
+        }
+        state.last_notified = Some(now);
+        self.sent.push_back(now);
+        Some(std::mem::take(&mut state.suppressed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use teloxide::dptree;
+
+    use crate::bot::core::db::model::Incident;
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::incidents::{admin_notification, fingerprint, report_incidents, HandlerError, NotificationThrottle, MAX_ERROR_LENGTH, MAX_NOTIFICATIONS};
+    use crate::bot::core::mock_api::{ADMIN, ALICE};
+    use crate::bot::core::repository::in_memory::InMemoryRepository;
+    use crate::bot::core::repository::{AccountRepository, IncidentRepository};
+    use crate::bot::test_bot::TestBot;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn incident(command: &str, error: &str) -> Incident {
+        Incident {
+            id: "ABC123".to_string(),
+            created_at: 1,
+            update_id: 2,
+            chat_id: None,
+            command: Some(command.to_string()),
+            dialogue_state: None,
+            error: error.to_string(),
+        }
+    }
+
+    #[test]
+    fn notifies_same_error_once_within_deduplication_window() {
+        let mut throttle = NotificationThrottle::default();
+        let start = Instant::now();
+        assert_eq!(throttle.admit("timeout", start), Some(0));
+        assert_eq!(throttle.admit("timeout", start + MINUTE), None);
+        assert_eq!(throttle.admit("timeout", start + 14 * MINUTE), None);
+        assert_eq!(throttle.admit("other", start + 14 * MINUTE), Some(0));
+        // the next notification mentions the suppressed incidents
+        assert_eq!(throttle.admit("timeout", start + 16 * MINUTE), Some(2));
+        assert_eq!(throttle.admit("timeout", start + 17 * MINUTE), None);
+    }
+
+    #[test]
+    fn throttles_notifications_of_different_errors() {
+        let mut throttle = NotificationThrottle::default();
+        let start = Instant::now();
+        for index in 0..MAX_NOTIFICATIONS {
+            assert_eq!(throttle.admit(&format!("error {}", index), start), Some(0));
+        }
+        assert_eq!(throttle.admit("one more", start + MINUTE), None);
+        assert_eq!(throttle.admit("one more", start + 9 * MINUTE), None);
+        assert_eq!(throttle.admit("one more", start + 11 * MINUTE), Some(2));
+    }
+
+    #[test]
+    fn fingerprint_ignores_digits() {
+        let timeout = fingerprint(&incident("search", "timed out after 30s for chat 1234"));
+        assert_eq!(timeout, fingerprint(&incident("search", "timed out after 45s for chat 9876")));
+        assert_ne!(timeout, fingerprint(&incident("purchase", "timed out after 30s for chat 1234")));
+        assert_ne!(timeout, fingerprint(&incident("search", "connection refused")));
+    }
+
+    #[test]
+    fn truncates_long_errors_in_notification() {
+        let text = admin_notification(&incident("search", &"x".repeat(MAX_ERROR_LENGTH + 500)), 3);
+        assert!(text.starts_with("Incident ABC123\nCommand: /search\nError: "), "{}", text);
+        assert!(text.contains(&format!("{}...", "x".repeat(MAX_ERROR_LENGTH))));
+        assert!(!text.contains(&"x".repeat(MAX_ERROR_LENGTH + 1)));
+        assert!(text.ends_with("3 more incidents like this were not notified."), "{}", text);
+
+        let text = admin_notification(&incident("search", "short"), 0);
+        assert!(text.ends_with("\nError: short"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn failing_handler_reports_incident() {
+        let failing = dptree::endpoint(|| async { Err::<(), HandlerError>("database is locked".into()) });
+        let mut bot = TestBot::start_with_handler(report_incidents::<InMemoryRepository>().chain(failing)).await;
+        let admin = bot.repository.create_user("admin", UserRole::Admin).unwrap();
+        bot.repository.register_telegram_account_of_user(&admin.start_token, ADMIN.id).await.unwrap();
+
+        let replies = bot.send_text(&ALICE, "/search", 2).await;
+        let incidents = bot.repository.list_incidents(10).await.unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!((incidents[0].chat_id, incidents[0].command.as_deref(), incidents[0].error.as_str()), (Some(ALICE.id), Some("search"), "database is locked"));
+        assert_eq!(replies[0].chat_id, ALICE.id);
+        assert!(replies[0].text.ends_with(&format!("mention incident {}.", incidents[0].id)), "{}", replies[0].text);
+        assert_eq!(replies[1].chat_id, ADMIN.id);
+        assert!(replies[1].text.starts_with(&format!("Incident {}\nCommand: /search", incidents[0].id)), "{}", replies[1].text);
+        bot.stop().await;
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::anyhow;
+use tracing::level_filters::LevelFilter;
+use tracing::span::Record;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::{DefaultFields, Writer};
+use tracing_subscriber::fmt::{FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+use crate::bot::core::bot_config::logging::{BotLoggingConfig, LogFormat, LogRotation};
+#[cfg(feature = "otel")]
+use crate::bot::core::bot_config::logging::DEFAULT_LOG_FILTER;
+use crate::bot::core::bot_config::storage::BotStorageConfig;
+use crate::bot::core::bot_config::telemetry::BotTelemetryConfig;
+use crate::bot::core::redaction::{init_redaction, RedactingEvents, RedactingFields, RedactingJsonFields, RedactingPrettyFields, RedactingWriter};
+#[cfg(feature = "otel")]
+use crate::bot::core::telemetry;
+use crate::MyResult;
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+static LOG_FILTERS: OnceLock<LogFilters> = OnceLock::new();
+
+/// Output of log lines with its own filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LogOutput {
+    Stdout,
+    File,
+}
+
+impl FromStr for LogOutput {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "stdout" => Ok(LogOutput::Stdout),
+            "file" => Ok(LogOutput::File),
+            _ => Err(anyhow!("expected stdout or file")),
+        }
+    }
+}
+
+impl Display for LogOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogOutput::Stdout => write!(f, "stdout"),
+            LogOutput::File => write!(f, "file"),
+        }
+    }
+}
+
+/// Filters of the log outputs, changed at runtime with the `/loglevel` admin command.
+pub(crate) struct LogFilters {
+    stdout: reload::Handle<EnvFilter, Registry>,
+    file: reload::Handle<EnvFilter, Registry>,
+    /// Directives per output, the configured ones and the ones in use
+    configured: [String; 2],
+    current: Mutex<[String; 2]>,
+}
+
+/// None if logging is not initialized, e.g. in the healthcheck.
+pub(crate) fn log_filters() -> Option<&'static LogFilters> {
+    LOG_FILTERS.get()
+}
+
+impl LogFilters {
+    fn index(output: LogOutput) -> usize {
+        match output {
+            LogOutput::Stdout => 0,
+            LogOutput::File => 1,
+        }
+    }
+
+    pub(crate) fn current(&self, output: LogOutput) -> String {
+        self.current.lock().expect("Log filter lock poisoned")[Self::index(output)].clone()
+    }
+
+    /// Replace the filter of an output, the directives are validated before.
+    pub(crate) fn set(&self, output: LogOutput, directives: &str) -> MyResult {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|error| anyhow!("Invalid filter '{}': {}", directives, error))?;
+        let handle = match output {
+            LogOutput::Stdout => &self.stdout,
+            LogOutput::File => &self.file,
+        };
+        handle.reload(filter)?;
+        self.current.lock().expect("Log filter lock poisoned")[Self::index(output)] = directives.to_string();
+        // the `log` crate skips records above the max level it was initialized with
+        log::set_max_level(log_level(LevelFilter::current()));
+        tracing::info!("Log filter of {} changed to '{}'", output, directives);
+        Ok(())
+    }
+
+    /// Restore the filter of the configuration.
+    pub(crate) fn reset(&self, output: LogOutput) -> MyResult {
+        let configured = self.configured[Self::index(output)].clone();
+        self.set(output, &configured)
+    }
+}
+
+fn log_level(level: LevelFilter) -> log::LevelFilter {
+    match level {
+        LevelFilter::OFF => log::LevelFilter::Off,
+        LevelFilter::ERROR => log::LevelFilter::Error,
+        LevelFilter::WARN => log::LevelFilter::Warn,
+        LevelFilter::INFO => log::LevelFilter::Info,
+        LevelFilter::DEBUG => log::LevelFilter::Debug,
+        LevelFilter::TRACE => log::LevelFilter::Trace,
+    }
+}
+
+/// Span fields are stored in the span per field formatter type, with the same type both outputs would append recorded fields.
+struct FileFields<N>(N);
+
+impl<'writer, N> FormatFields<'writer> for FileFields<N>
+where
+    N: for<'any> FormatFields<'any>,
+{
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> std::fmt::Result {
+        self.0.format_fields(writer, fields)
+    }
+
+    fn add_fields(&self, current: &'writer mut FormattedFields<Self>, fields: &Record<'_>) -> std::fmt::Result {
+        let mut inner = FormattedFields::<N>::new(std::mem::take(&mut current.fields));
+        let result = self.0.add_fields(&mut inner, fields);
+        current.fields = inner.fields;
+        result
+    }
+}
+
+fn fmt_layer<W>(format: LogFormat, output: LogOutput, writer: W) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    // fields of the `update` span, including the trace id, are part of each log line within it,
+    // telegram ids and message texts are masked by the field formatters
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        // Could be nice to use ansi coloring in stdout but not in file output for persistence, disabling for now
+        .with_ansi(false);
+    match (format, output) {
+        (LogFormat::Compact, LogOutput::Stdout) => layer.compact().fmt_fields(RedactingFields(DefaultFields::new())).boxed(),
+        (LogFormat::Compact, LogOutput::File) => layer.compact().fmt_fields(FileFields(RedactingFields(DefaultFields::new()))).boxed(),
+        (LogFormat::Pretty, LogOutput::Stdout) => layer.pretty().fmt_fields(RedactingPrettyFields).map_event_format(RedactingEvents).boxed(),
+        (LogFormat::Pretty, LogOutput::File) => layer.pretty().fmt_fields(FileFields(RedactingPrettyFields)).map_event_format(RedactingEvents).boxed(),
+        (LogFormat::Json, LogOutput::Stdout) => layer.json().fmt_fields(RedactingJsonFields).map_event_format(RedactingEvents).boxed(),
+        (LogFormat::Json, LogOutput::File) => layer.json().fmt_fields(FileFields(RedactingJsonFields)).map_event_format(RedactingEvents).boxed(),
+    }
+}
+
+pub fn init_tracing() -> MyResult {
+    let storage_config = BotStorageConfig::new()?;
+    let logging_config = BotLoggingConfig::new()?;
+    // fails if an endpoint is configured but the `otel` feature is missing
+    let telemetry_config = BotTelemetryConfig::new()?;
+
+    // before the first log line
+    let salt_configured = init_redaction(&logging_config.redaction);
+
+    let rotation = match logging_config.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let file_appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .max_log_files(logging_config.max_files)
+        .filename_prefix("telegrambot")
+        .filename_suffix("log")
+        .build(storage_config.log_directory)
+        .expect("initializing rolling file appender failed");
+
+    // validated when loading the configuration
+    let (stdout_filter, stdout_handle) = reload::Layer::new(EnvFilter::try_new(&logging_config.stdout_filter)?);
+    let (file_filter, file_handle) = reload::Layer::new(EnvFilter::try_new(&logging_config.file_filter)?);
+
+    let subscriber = tracing_subscriber::registry().with(vec![
+        fmt_layer(logging_config.format, LogOutput::Stdout, RedactingWriter(std::io::stdout)).with_filter(stdout_filter).boxed(),
+        fmt_layer(logging_config.format, LogOutput::File, RedactingWriter(file_appender)).with_filter(file_filter).boxed(),
+    ]);
+    #[cfg(feature = "otel")]
+    let subscriber = {
+        // spans are exported independent of the log filters
+        let otel_filter = EnvFilter::try_new(DEFAULT_LOG_FILTER)?;
+        subscriber.with(telemetry::otel::layer(&telemetry_config)?.map(|layer| layer.with_filter(otel_filter)))
+    };
+    subscriber.init();
+
+    let filters = [logging_config.stdout_filter, logging_config.file_filter];
+    let _ = LOG_FILTERS.set(LogFilters {
+        stdout: stdout_handle,
+        file: file_handle,
+        configured: filters.clone(),
+        current: Mutex::new(filters),
+    });
+
+    if !salt_configured {
+        tracing::warn!("No redaction salt configured, hashes of telegram ids in the logs change on restart. Configure TELOXIDE_LOG_REDACTION_SALT to keep them.");
+    }
+    #[cfg(feature = "otel")]
+    if let Some(traces_url) = telemetry_config.otlp_traces_url {
+        tracing::info!("Exporting traces to {}", traces_url);
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = telemetry_config;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::Empty;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::bot::core::bot_config::logging::LogFormat;
+    use crate::bot::core::logging::{fmt_layer, LogOutput};
+    use crate::bot::core::redaction::TelegramId;
+
+    /// Long ids, shorter ones could be part of a hash or the timestamp.
+    const CHAT_ID: i64 = -1009876543210;
+    const USER_ID: u64 = 5678901234;
+    const TELEGRAM_ID: i64 = 4321098765;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_update(format: LogFormat, output: LogOutput) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(fmt_layer(format, output, move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("update", update_id = 7, chat_id = Empty, user_id = Empty, text = Empty);
+            span.record("chat_id", CHAT_ID);
+            span.record("user_id", USER_ID);
+            span.record("text", "hello bot");
+            let _entered = span.enter();
+            tracing::info!(telegram_id = TELEGRAM_ID, text = "hello again", "Handled update");
+        });
+        let logs = buffer.0.lock().unwrap().clone();
+        String::from_utf8(logs).unwrap()
+    }
+
+    #[test]
+    fn masks_telegram_ids_and_message_texts_in_every_format() {
+        for format in [LogFormat::Compact, LogFormat::Pretty, LogFormat::Json] {
+            for output in [LogOutput::Stdout, LogOutput::File] {
+                let logs = log_update(format, output);
+                assert!(logs.contains("Handled update"), "{:?} {}: {}", format, output, logs);
+                for id in [CHAT_ID, USER_ID as i64, TELEGRAM_ID] {
+                    assert!(logs.contains(&TelegramId(id).to_string()), "{:?} {}: {}", format, output, logs);
+                    assert!(!logs.contains(id.abs().to_string().as_str()), "{:?} {}: {}", format, output, logs);
+                }
+                assert!(logs.contains("<9 chars>") && logs.contains("<11 chars>"), "{:?} {}: {}", format, output, logs);
+                assert!(!logs.contains("hello"), "{:?} {}: {}", format, output, logs);
+            }
+        }
+    }
+}
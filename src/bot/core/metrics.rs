@@ -0,0 +1,258 @@
+use std::error::Error;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+use teloxide::requests::{HasPayload, Output, Payload, Request};
+use teloxide::types::{Update, UpdateKind};
+use teloxide::RequestError;
+use tracing::Instrument;
+
+use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_METRICS;
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::user_representation::UserRole;
+use crate::bot::core::repository::UserRepository;
+use crate::bot::core::shutdown::{shutdown_requested, spawn_background_task};
+use crate::bot::schema::command_names;
+
+const METRIC_NAMESPACE: &str = "telegrambot";
+
+static METRICS: OnceLock<BotMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the running bot, exported at `/metrics`.
+pub(crate) struct BotMetrics {
+    registry: Registry,
+    updates: IntCounterVec,
+    commands: IntCounterVec,
+    handler_errors: IntCounterVec,
+    telegram_api_duration: HistogramVec,
+    telegram_api_failures: IntCounterVec,
+    broadcast_deliveries: IntCounterVec,
+    registered_users: IntGaugeVec,
+    database_query_duration: HistogramVec,
+    known_commands: Vec<String>,
+}
+
+pub(crate) fn metrics() -> &'static BotMetrics {
+    METRICS.get_or_init(|| BotMetrics::new().expect("Metric definitions are valid."))
+}
+
+impl BotMetrics {
+    fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some(METRIC_NAMESPACE.to_string()), None)?;
+        let updates = IntCounterVec::new(Opts::new("updates_total", "Received updates by kind"), &["kind"])?;
+        let commands = IntCounterVec::new(Opts::new("commands_total", "Received commands by name"), &["command"])?;
+        let handler_errors = IntCounterVec::new(Opts::new("handler_errors_total", "Errors returned by update handlers by source"), &["source"])?;
+        let telegram_api_duration = HistogramVec::new(HistogramOpts::new("telegram_api_duration_seconds", "Duration of Bot API calls by method"), &["method"])?;
+        let telegram_api_failures = IntCounterVec::new(Opts::new("telegram_api_failures_total", "Failed Bot API calls by method"), &["method"])?;
+        let broadcast_deliveries = IntCounterVec::new(Opts::new("broadcast_deliveries_total", "Broadcast messages by delivery result"), &["result"])?;
+        let registered_users = IntGaugeVec::new(Opts::new("registered_users", "Users with a linked telegram account by role"), &["role"])?;
+        let database_query_duration = HistogramVec::new(HistogramOpts::new("database_query_duration_seconds", "Duration of database work including waiting for a connection"), &["pool"])?;
+
+        registry.register(Box::new(updates.clone()))?;
+        registry.register(Box::new(commands.clone()))?;
+        registry.register(Box::new(handler_errors.clone()))?;
+        registry.register(Box::new(telegram_api_duration.clone()))?;
+        registry.register(Box::new(telegram_api_failures.clone()))?;
+        registry.register(Box::new(broadcast_deliveries.clone()))?;
+        registry.register(Box::new(registered_users.clone()))?;
+        registry.register(Box::new(database_query_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            updates,
+            commands,
+            handler_errors,
+            telegram_api_duration,
+            telegram_api_failures,
+            broadcast_deliveries,
+            registered_users,
+            database_query_duration,
+            known_commands: command_names(),
+        })
+    }
+
+    pub(crate) fn record_update(&self, update: &Update) {
+        let kind = match &update.kind {
+            UpdateKind::Message(_) => "message",
+            UpdateKind::EditedMessage(_) => "edited_message",
+            UpdateKind::ChannelPost(_) => "channel_post",
+            UpdateKind::EditedChannelPost(_) => "edited_channel_post",
+            UpdateKind::InlineQuery(_) => "inline_query",
+            UpdateKind::ChosenInlineResult(_) => "chosen_inline_result",
+            UpdateKind::CallbackQuery(_) => "callback_query",
+            UpdateKind::MyChatMember(_) => "my_chat_member",
+            _ => "other",
+        };
+        self.updates.with_label_values(&[kind]).inc();
+
+        if let Some(command) = self.command_label(update) {
+            self.commands.with_label_values(&[command]).inc();
+        }
+    }
+
+    /// Command of a message, unknown commands share a label to bound the cardinality.
+    pub(crate) fn command_label(&self, update: &Update) -> Option<&str> {
+        let UpdateKind::Message(message) = &update.kind else {
+            return None;
+        };
+        let command = message.text()?.strip_prefix('/')?;
+        // `/start@mybot token` -> `start`
+        let name = command.split_whitespace().next().unwrap_or_default()
+            .split('@').next().unwrap_or_default()
+            .to_lowercase();
+        let label = self.known_commands.iter()
+            .find(|known| **known == name)
+            .map(|known| known.as_str())
+            .unwrap_or("unknown");
+        Some(label)
+    }
+
+    pub(crate) fn record_handler_error(&self, error: &(dyn Error + Send + Sync + 'static)) {
+        let source = if error.is::<RequestError>() {
+            "telegram_api"
+        } else if error.is::<DatabaseError>() {
+            "database"
+        } else {
+            "other"
+        };
+        self.handler_errors.with_label_values(&[source]).inc();
+    }
+
+    pub(crate) fn record_broadcast_delivery(&self, delivered: bool) {
+        let result = if delivered { "delivered" } else { "failed" };
+        self.broadcast_deliveries.with_label_values(&[result]).inc();
+    }
+
+    pub(crate) fn record_database_query(&self, pool: &str, duration: Duration) {
+        self.database_query_duration.with_label_values(&[pool]).observe(duration.as_secs_f64());
+    }
+
+    /// `method` is the payload name like `SendMessage`, recorded as the Bot API method `sendMessage`.
+    fn record_telegram_api_call(&self, method: &str, duration: Duration, success: bool) {
+        let mut chars = method.chars();
+        let method = chars.next().map(|first| first.to_ascii_lowercase().to_string() + chars.as_str()).unwrap_or_default();
+        let method = method.as_str();
+        self.telegram_api_duration.with_label_values(&[method]).observe(duration.as_secs_f64());
+        if !success {
+            self.telegram_api_failures.with_label_values(&[method]).inc();
+        }
+    }
+
+    fn set_registered_users(&self, roles: &[UserRole]) {
+        for role in [UserRole::User, UserRole::Admin] {
+            let count = roles.iter().filter(|user_role| user_role.eq(&&role)).count();
+            self.registered_users.with_label_values(&[&role.to_string()]).set(count as i64);
+        }
+    }
+
+    fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|error| prometheus::Error::Msg(error.to_string()))
+    }
+}
+
+/// Send a Bot API request within a `telegram_api` span and record its duration and failure by method name.
+/// The update listeners in [`crate::bot::core::dispatch`] send their requests with it as well.
+pub(crate) trait MeasuredRequest: Request {
+    fn send_measured(self) -> impl Future<Output=Result<Output<Self>, Self::Err>> + Send;
+}
+
+impl<R: Request + Send> MeasuredRequest for R {
+    async fn send_measured(self) -> Result<Output<Self>, Self::Err> {
+        let method = <<Self as HasPayload>::Payload as Payload>::NAME;
+        let span = tracing::info_span!("telegram_api", method, otel.status_code = tracing::field::Empty);
+        let start = Instant::now();
+        let result = self.send().instrument(span.clone()).await;
+        if result.is_err() {
+            span.record("otel.status_code", "ERROR");
+        }
+        metrics().record_telegram_api_call(method, start.elapsed(), result.is_ok());
+        result
+    }
+}
+
+/// Count handler errors before logging them like the default error handler of the dispatcher.
+pub(crate) async fn handle_error(error: Box<dyn Error + Send + Sync>) {
+    metrics().record_handler_error(error.as_ref());
+    tracing::error!("An error has occurred in the dispatcher: {:?}", error);
+}
+
+/// Route for `/metrics`, the user gauge is refreshed from the repository on each scrape.
+pub(crate) fn metrics_router<R: UserRepository>(repository: R) -> axum::Router {
+    axum::Router::new()
+        .route(TELEGRAM_BOT_ENDPOINT_METRICS, axum::routing::get(metrics_endpoint::<R>))
+        .with_state(repository)
+}
+
+async fn metrics_endpoint<R: UserRepository>(State(repository): State<R>) -> Response {
+    match repository.list_registered_users().await {
+        Ok(users) => {
+            let roles = users.into_iter().map(|user| user.role).collect::<Vec<_>>();
+            metrics().set_registered_users(&roles);
+        }
+        Err(error) => {
+            tracing::warn!("Could not count registered users for metrics: {}", error);
+        }
+    }
+    match metrics().encode() {
+        Ok(text) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text).into_response(),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response(),
+    }
+}
+
+/// Serve `/metrics` on its own address, e.g. in polling mode without the webhook listener.
+/// Stops accepting requests on shutdown, the shutdown waits for the running ones.
+pub(crate) async fn spawn_metrics_listener(address: SocketAddr, router: axum::Router) -> Result<(), anyhow::Error> {
+    let tcp_listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|error| anyhow::anyhow!("Could not bind metrics listener to {}: {}", address, error))?;
+    tracing::info!("Serving metrics on http://{}{}", address, TELEGRAM_BOT_ENDPOINT_METRICS);
+    spawn_background_task(async move {
+        if let Err(error) = axum::serve(tcp_listener, router).with_graceful_shutdown(shutdown_requested()).await {
+            tracing::error!("Metrics listener failed: {}", error);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use teloxide::Bot;
+    use teloxide::update_listeners::{AsUpdateStream, UpdateListener};
+
+    use crate::bot::core::dispatch::polling_update_listener;
+    use crate::bot::core::metrics::metrics;
+    use crate::bot::core::mock_api::{MockApi, ALICE};
+
+    #[tokio::test]
+    async fn polling_measures_its_requests_and_labels_chat_member_updates() {
+        let api = MockApi::new("testbot");
+        let bot = Bot::new("123456:test-token").set_api_url(api.serve().await);
+        let get_updates = || metrics().telegram_api_duration.with_label_values(&["getUpdates"]).get_sample_count();
+        let my_chat_member = || metrics().updates.with_label_values(&["my_chat_member"]).get();
+        let (get_updates_before, my_chat_member_before) = (get_updates(), my_chat_member());
+
+        let mut listener = polling_update_listener(bot).await;
+        let stop_token = listener.stop_token();
+        api.block_bot(&ALICE);
+        let mut stream = listener.as_stream();
+        let update = stream.next().await.unwrap().unwrap();
+        metrics().record_update(&update);
+        stop_token.stop();
+        assert!(stream.next().await.is_none());
+        drop(stream);
+
+        // the first call and the confirmation when stopping, other tests may poll as well
+        assert!(get_updates() >= get_updates_before + 2);
+        assert!(metrics().telegram_api_duration.with_label_values(&["getWebhookInfo"]).get_sample_count() >= 1);
+        assert!(my_chat_member() > my_chat_member_before);
+    }
+}
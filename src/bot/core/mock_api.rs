@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use axum::body::Bytes;
+use reqwest::Url;
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+
+/// Upper bound for long polling, keeps the shutdown of the dispatcher fast
+const MAX_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+pub(crate) const MOCK_BOT_ID: i64 = 1;
+
+/// Users of the tests, the admin and alice are registered by most of them
+pub(crate) const ADMIN: MockUser = MockUser { id: 1001, first_name: "Admin" };
+pub(crate) const ALICE: MockUser = MockUser { id: 1002, first_name: "Alice" };
+pub(crate) const BOB: MockUser = MockUser { id: 1003, first_name: "Bob" };
+/// Never registered
+pub(crate) const STRANGER: MockUser = MockUser { id: 1004, first_name: "Stranger" };
+
+/// Message sent by the bot to the mock API.
+#[derive(Debug, Clone)]
+pub(crate) struct SentMessage {
+    pub chat_id: i64,
+    pub text: String,
+}
+
+/// Telegram user that sends scripted updates.
+#[derive(Debug, Clone)]
+pub(crate) struct MockUser {
+    pub id: i64,
+    pub first_name: &'static str,
+}
+
+impl MockUser {
+    fn to_json(&self) -> Value {
+        json!({"id": self.id, "is_bot": false, "first_name": self.first_name})
+    }
+
+    fn chat_json(&self) -> Value {
+        json!({"id": self.id, "type": "private", "first_name": self.first_name})
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockApiData {
+    /// Updates not yet confirmed by the bot via the `offset` of getUpdates
+    pending_updates: Vec<Value>,
+    next_update_id: i64,
+    next_message_id: i64,
+    sent_messages: Vec<SentMessage>,
+    /// Chats of users that blocked the bot, messages to them are rejected
+    blocked_chats: HashSet<i64>,
+    webhook_url: String,
+}
+
+/// Local emulation of the Telegram Bot API methods used by this bot.
+#[derive(Debug)]
+pub(crate) struct MockApi {
+    bot_name: String,
+    data: Mutex<MockApiData>,
+    updates_available: Notify,
+    message_sent: Notify,
+}
+
+impl MockApi {
+    pub(crate) fn new(bot_name: &str) -> Arc<Self> {
+        Arc::new(Self {
+            bot_name: bot_name.to_string(),
+            data: Default::default(),
+            updates_available: Notify::new(),
+            message_sent: Notify::new(),
+        })
+    }
+
+    /// Serve the mock API on a random local port and return its base url.
+    pub(crate) async fn serve(self: &Arc<Self>) -> Url {
+        let router = axum::Router::new()
+            .route("/:bot_token/:method", axum::routing::post(handle_method).get(handle_method))
+            .with_state(self.clone());
+        serve_local(router).await
+    }
+
+    /// Queue a text message from the given user.
+    pub(crate) fn send_text(&self, from: &MockUser, text: &str) {
+        self.push_update(|update_id, message_id| json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": message_id,
+                "date": chrono::offset::Utc::now().timestamp(),
+                "chat": from.chat_json(),
+                "from": from.to_json(),
+                "text": text,
+            }
+        }));
+    }
+
+    /// Queue a press on an inline keyboard button by the given user.
+    pub(crate) fn press_button(&self, from: &MockUser, data: &str) {
+        self.push_update(|update_id, message_id| json!({
+            "update_id": update_id,
+            "callback_query": {
+                "id": format!("callback-{}", update_id),
+                "from": from.to_json(),
+                "chat_instance": from.id.to_string(),
+                "data": data,
+                "message": {
+                    "message_id": message_id,
+                    "date": chrono::offset::Utc::now().timestamp(),
+                    "chat": from.chat_json(),
+                    "from": self.bot_user_json(),
+                    "text": "",
+                }
+            }
+        }));
+    }
+
+    /// Answer messages to the given user with `403` like telegram does after the user blocked the bot.
+    pub(crate) fn reject_messages_to(&self, user: &MockUser) {
+        self.data.lock().expect("Mock API lock poisoned").blocked_chats.insert(user.id);
+    }
+
+    /// Queue the update telegram sends when the given user blocks the bot.
+    pub(crate) fn block_bot(&self, from: &MockUser) {
+        self.push_update(|update_id, _message_id| json!({
+            "update_id": update_id,
+            "my_chat_member": {
+                "chat": from.chat_json(),
+                "from": from.to_json(),
+                "date": chrono::offset::Utc::now().timestamp(),
+                "old_chat_member": {"user": self.bot_user_json(), "status": "member"},
+                "new_chat_member": {"user": self.bot_user_json(), "status": "kicked", "until_date": 0},
+            }
+        }));
+    }
+
+    fn push_update(&self, update: impl FnOnce(i64, i64) -> Value) {
+        {
+            let mut data = self.data.lock().expect("Mock API lock poisoned");
+            data.next_update_id += 1;
+            data.next_message_id += 1;
+            let update = update(data.next_update_id, data.next_message_id);
+            data.pending_updates.push(update);
+        }
+        self.updates_available.notify_waiters();
+    }
+
+    pub(crate) fn sent_messages(&self) -> Vec<SentMessage> {
+        self.data.lock().expect("Mock API lock poisoned").sent_messages.clone()
+    }
+
+    /// Wait until a message was sent or the timeout elapsed.
+    pub(crate) async fn wait_for_message(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.message_sent.notified()).await;
+    }
+
+    fn bot_user_json(&self) -> Value {
+        json!({"id": MOCK_BOT_ID, "is_bot": true, "first_name": self.bot_name, "username": self.bot_name})
+    }
+
+    async fn get_updates(&self, parameters: &Value) -> Value {
+        let offset = parameters["offset"].as_i64().unwrap_or(0);
+        let timeout = parameters["timeout"].as_u64()
+            .map(Duration::from_secs)
+            .unwrap_or_default()
+            .min(MAX_POLL_TIMEOUT);
+
+        let notified = self.updates_available.notified();
+        if self.confirm_and_list_updates(offset).is_empty() {
+            let _ = tokio::time::timeout(timeout, notified).await;
+        }
+        Value::Array(self.confirm_and_list_updates(offset))
+    }
+
+    fn confirm_and_list_updates(&self, offset: i64) -> Vec<Value> {
+        let mut data = self.data.lock().expect("Mock API lock poisoned");
+        data.pending_updates.retain(|update| update["update_id"].as_i64().unwrap_or(0) >= offset);
+        data.pending_updates.clone()
+    }
+
+    fn send_message(&self, parameters: &Value) -> Result<Value, Json<Value>> {
+        let chat_id = parameters["chat_id"].as_i64()
+            .or_else(|| parameters["chat_id"].as_str().and_then(|id| id.parse().ok()))
+            .unwrap_or_default();
+        let text = parameters["text"].as_str().unwrap_or_default().to_string();
+        let message_id = {
+            let mut data = self.data.lock().expect("Mock API lock poisoned");
+            if data.blocked_chats.contains(&chat_id) {
+                return Err(error_response(403, "Forbidden: bot was blocked by the user"));
+            }
+            data.next_message_id += 1;
+            data.sent_messages.push(SentMessage { chat_id, text: text.clone() });
+            data.next_message_id
+        };
+        self.message_sent.notify_waiters();
+        Ok(json!({
+            "message_id": message_id,
+            "date": chrono::offset::Utc::now().timestamp(),
+            "chat": {"id": chat_id, "type": "private"},
+            "from": self.bot_user_json(),
+            "text": text,
+        }))
+    }
+
+    fn set_webhook(&self, parameters: &Value) -> Value {
+        let url = parameters["url"].as_str().unwrap_or_default().to_string();
+        self.data.lock().expect("Mock API lock poisoned").webhook_url = url;
+        Value::Bool(true)
+    }
+
+    fn get_webhook_info(&self) -> Value {
+        let data = self.data.lock().expect("Mock API lock poisoned");
+        json!({
+            "url": data.webhook_url,
+            "has_custom_certificate": false,
+            "pending_update_count": data.pending_updates.len(),
+        })
+    }
+
+    fn get_me(&self) -> Value {
+        let mut me = self.bot_user_json();
+        for capability in ["can_join_groups", "can_read_all_group_messages", "supports_inline_queries", "can_connect_to_business", "has_main_web_app"] {
+            me[capability] = Value::Bool(false);
+        }
+        me
+    }
+}
+
+/// Serve the router on a random local port, returns its base url.
+pub(crate) async fn serve_local(router: axum::Router) -> Url {
+    let tcp_listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await
+        .expect("Could not bind a local port");
+    let url = Url::parse(&format!("http://{}/", tcp_listener.local_addr().expect("Bound listener has an address")))
+        .expect("Local url is valid");
+    tokio::spawn(async move { axum::serve(tcp_listener, router).await });
+    url
+}
+
+/// Dispatch a Bot API call, telegram treats method names case-insensitive.
+async fn handle_method(
+    State(api): State<Arc<MockApi>>,
+    Path((_bot_token, method)): Path<(String, String)>,
+    body: Bytes,
+) -> Json<Value> {
+    let parameters = serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null);
+    tracing::debug!("Mock Bot API call {} with {}", method, parameters);
+    let result = match method.to_lowercase().as_str() {
+        "getme" => api.get_me(),
+        "getupdates" => api.get_updates(&parameters).await,
+        "sendmessage" => match api.send_message(&parameters) {
+            Ok(message) => message,
+            Err(error) => return error,
+        },
+        "answercallbackquery" => Value::Bool(true),
+        "setwebhook" => api.set_webhook(&parameters),
+        "deletewebhook" => api.set_webhook(&Value::Null),
+        "getwebhookinfo" => api.get_webhook_info(),
+        _ => {
+            tracing::warn!("Mock Bot API does not emulate method {}", method);
+            return error_response(404, &format!("Not Found: method {} is not emulated", method));
+        }
+    };
+    Json(json!({"ok": true, "result": result}))
+}
+
+fn error_response(error_code: u16, description: &str) -> Json<Value> {
+    Json(json!({"ok": false, "error_code": error_code, "description": description}))
+}
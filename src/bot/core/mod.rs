@@ -3,3 +3,18 @@ pub(crate) mod healthcheck;
 pub(crate) mod util;
 pub(crate) mod bot_config;
 pub(crate) mod db;
+pub(crate) mod repository;
+#[cfg(test)]
+pub(crate) mod mock_api;
+pub(crate) mod metrics;
+pub(crate) mod telemetry;
+pub(crate) mod logging;
+pub(crate) mod redaction;
+pub(crate) mod incidents;
+pub(crate) mod shutdown;
+pub(crate) mod admin_api;
+pub(crate) mod audit;
+pub(crate) mod dashboard;
+pub(crate) mod signature;
+pub(crate) mod webapp;
+pub(crate) mod events;
@@ -0,0 +1,507 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::sync::OnceLock;
+
+use sha2::{Digest, Sha256};
+use tracing::field::{display, Field, Value, Visit};
+use tracing::span::Record;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::{MakeVisitor, RecordFields, VisitFmt, VisitOutput};
+use tracing_subscriber::fmt::format::{JsonVisitor, PrettyVisitor, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::bot::core::bot_config::logging::BotRedactionConfig;
+use crate::bot::core::util::random_start_token;
+
+const REDACTED: &str = "<redacted>";
+/// Hex digits of the hash, enough to tell the users of a bot apart
+const HASH_LENGTH: usize = 12;
+/// Start tokens have 32 characters
+const TOKEN_PREFIX_LENGTH: usize = 4;
+const MIN_SECRET_LENGTH: usize = 8;
+const HASH_PREFIX: &str = "tg-";
+/// Fields with a telegram user or chat id, logged as salted hash
+const TELEGRAM_ID_FIELDS: [&str; 3] = ["telegram_id", "chat_id", "user_id"];
+/// Field with text written by a user
+const MESSAGE_TEXT_FIELD: &str = "text";
+/// Most fields a callsite can have
+const MAX_FIELDS: usize = 32;
+
+static REDACTION: OnceLock<Redaction> = OnceLock::new();
+
+/// Applied to log lines, so log files can be shared with support.
+struct Redaction {
+    salt: String,
+    log_message_text: bool,
+    secrets: Vec<String>,
+}
+
+impl Redaction {
+    /// Masked value of a field with a telegram id or message text, None if the field is logged as is.
+    fn field_value(&self, name: &str, value: impl FnOnce() -> String) -> Option<String> {
+        if TELEGRAM_ID_FIELDS.contains(&name) {
+            let value = value();
+            if value.starts_with(HASH_PREFIX) {
+                // already hashed with `TelegramId`
+                return Some(value);
+            }
+            // ids are also recorded as `ChatId(..)` or `UserId(..)`
+            let id = value.chars().filter(|c| c.is_ascii_digit() || *c == '-').collect::<String>();
+            Some(id.parse().map_or_else(|_| REDACTED.to_string(), |id| hash_telegram_id(&self.salt, id)))
+        } else if name == MESSAGE_TEXT_FIELD && !self.log_message_text {
+            Some(format!("<{} chars>", value().chars().count()))
+        } else {
+            None
+        }
+    }
+}
+
+fn redaction() -> &'static Redaction {
+    // a random salt if logging is not initialized, e.g. in the admin cli
+    REDACTION.get_or_init(|| Redaction {
+        salt: random_start_token(),
+        log_message_text: false,
+        secrets: vec![],
+    })
+}
+
+/// Returns false if no salt is configured and a random salt is used.
+pub(crate) fn init_redaction(config: &BotRedactionConfig) -> bool {
+    let salt = config.salt.as_ref().map(|salt| salt.expose().to_string());
+    let salt_configured = salt.is_some();
+    let _ = REDACTION.set(Redaction {
+        salt: salt.unwrap_or_else(random_start_token),
+        log_message_text: config.log_message_text,
+        secrets: config.secrets.iter()
+            .map(|secret| secret.expose().to_string())
+            // short values like test tokens would mask unrelated text
+            .filter(|secret| secret.len() >= MIN_SECRET_LENGTH)
+            .collect(),
+    });
+    salt_configured
+}
+
+/// Salted hash of a telegram id, the same id gets the same hash with the same salt.
+pub(crate) fn hash_telegram_id(salt: &str, telegram_id: i64) -> String {
+    let digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update(b":")
+        .chain_update(telegram_id.to_string().as_bytes())
+        .finalize();
+    let hex = digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    format!("{}{}", HASH_PREFIX, &hex[..HASH_LENGTH])
+}
+
+/// Telegram user or chat id, logged as salted hash.
+pub(crate) struct TelegramId(pub i64);
+
+impl Display for TelegramId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hash_telegram_id(&redaction().salt, self.0))
+    }
+}
+
+/// Start token or other credential, only a prefix is logged to tell tokens apart.
+pub(crate) struct Token<'a>(pub &'a str);
+
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0.get(..TOKEN_PREFIX_LENGTH) {
+            Some(prefix) if self.0.len() >= 4 * TOKEN_PREFIX_LENGTH => write!(f, "{}...", prefix),
+            _ => f.write_str(REDACTED),
+        }
+    }
+}
+
+fn contains_secret(text: &str) -> bool {
+    redaction().secrets.iter().any(|secret| text.contains(secret.as_str())) || url_credentials(text).is_some()
+}
+
+/// Text with the configured secrets masked, e.g. error messages shown outside the logs.
+pub(crate) fn redact_secrets(text: &str) -> String {
+    let text = redaction().secrets.iter().fold(text.to_string(), |text, secret| text.replace(secret.as_str(), REDACTED));
+    redact_url_credentials(&text)
+}
+
+/// Range of the first `user:password` in front of the host of a url, e.g. of a database url in a library error.
+fn url_credentials(text: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    while let Some(index) = text[offset..].find("://") {
+        let start = offset + index + "://".len();
+        let authority_end = text[start..]
+            .find(|c: char| matches!(c, '/' | '?' | '#' | '"' | '\'' | '<' | '>') || c.is_whitespace())
+            .map_or(text.len(), |end| start + end);
+        if let Some(at) = text[start..authority_end].rfind('@') {
+            return Some((start, start + at));
+        }
+        offset = authority_end;
+    }
+    None
+}
+
+fn redact_url_credentials(text: &str) -> String {
+    let mut text = text.to_string();
+    let mut offset = 0;
+    while let Some((start, end)) = url_credentials(&text[offset..]) {
+        text.replace_range(offset + start..offset + end, REDACTED);
+        offset += start + REDACTED.len() + 1;
+    }
+    text
+}
+
+/// Masks the configured secrets, e.g. the bot token, wherever they appear in a log line.
+pub(crate) struct RedactingWriter<M>(pub M);
+
+pub(crate) struct RedactingLineWriter<W>(W);
+
+impl<'writer, M: MakeWriter<'writer>> MakeWriter<'writer> for RedactingWriter<M> {
+    type Writer = RedactingLineWriter<M::Writer>;
+
+    fn make_writer(&'writer self) -> Self::Writer {
+        RedactingLineWriter(self.0.make_writer())
+    }
+
+    fn make_writer_for(&'writer self, meta: &tracing::Metadata<'_>) -> Self::Writer {
+        RedactingLineWriter(self.0.make_writer_for(meta))
+    }
+}
+
+impl<W: io::Write> io::Write for RedactingLineWriter<W> {
+    /// The fmt layer writes each formatted event at once.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf);
+        if contains_secret(&line) {
+            self.0.write_all(redact_secrets(&line).as_bytes())?;
+        } else {
+            self.0.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+fn is_redacted_field(field: &Field) -> bool {
+    TELEGRAM_ID_FIELDS.contains(&field.name()) || field.name() == MESSAGE_TEXT_FIELD
+}
+
+/// Records telegram ids as salted hash and message texts only if enabled, other fields are passed to the wrapped visitor.
+pub(crate) struct RedactingVisitor<V>(V);
+
+impl<V: Visit> RedactingVisitor<V> {
+    /// False if the field is logged as is.
+    fn record_redacted(&mut self, field: &Field, value: impl FnOnce() -> String) -> bool {
+        match redaction().field_value(field.name(), value) {
+            Some(redacted) => {
+                self.0.record_debug(field, &format_args!("{}", redacted));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<V: Visit> Visit for RedactingVisitor<V> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.record_f64(field, value)
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if !self.record_redacted(field, || value.to_string()) {
+            self.0.record_i64(field, value)
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if !self.record_redacted(field, || value.to_string()) {
+            self.0.record_u64(field, value)
+        }
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        if !self.record_redacted(field, || value.to_string()) {
+            self.0.record_i128(field, value)
+        }
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        if !self.record_redacted(field, || value.to_string()) {
+            self.0.record_u128(field, value)
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.record_bool(field, value)
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if !self.record_redacted(field, || value.to_string()) {
+            self.0.record_str(field, value)
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        if !self.record_redacted(field, || value.to_string()) {
+            self.0.record_error(field, value)
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.record_redacted(field, || format!("{:?}", value)) {
+            self.0.record_debug(field, value)
+        }
+    }
+}
+
+impl<V: VisitOutput<O>, O> VisitOutput<O> for RedactingVisitor<V> {
+    fn finish(self) -> O {
+        self.0.finish()
+    }
+}
+
+impl<V: VisitFmt> VisitFmt for RedactingVisitor<V> {
+    fn writer(&mut self) -> &mut dyn std::fmt::Write {
+        self.0.writer()
+    }
+}
+
+/// Field formatter of the compact format that masks telegram ids and message texts, e.g. of the `update` span.
+pub(crate) struct RedactingFields<M>(pub M);
+
+impl<T, M: MakeVisitor<T>> MakeVisitor<T> for RedactingFields<M> {
+    type Visitor = RedactingVisitor<M::Visitor>;
+
+    fn make_visitor(&self, target: T) -> Self::Visitor {
+        RedactingVisitor(self.0.make_visitor(target))
+    }
+}
+
+/// Field formatter of the pretty format that masks telegram ids and message texts.
+pub(crate) struct RedactingPrettyFields;
+
+impl<'writer> FormatFields<'writer> for RedactingPrettyFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> std::fmt::Result {
+        let mut visitor = RedactingVisitor(PrettyVisitor::new(writer, true));
+        fields.record(&mut visitor);
+        visitor.finish()
+    }
+
+    /// Fields recorded later are separated like the ones of `Pretty`.
+    fn add_fields(&self, current: &'writer mut FormattedFields<Self>, fields: &Record<'_>) -> std::fmt::Result {
+        let empty = current.is_empty();
+        let mut visitor = RedactingVisitor(PrettyVisitor::new(current.as_writer(), empty));
+        fields.record(&mut visitor);
+        visitor.finish()
+    }
+}
+
+/// Field formatter of the json format that masks telegram ids and message texts.
+pub(crate) struct RedactingJsonFields;
+
+impl<'writer> FormatFields<'writer> for RedactingJsonFields {
+    fn format_fields<R: RecordFields>(&self, mut writer: Writer<'writer>, fields: R) -> std::fmt::Result {
+        let mut visitor = RedactingVisitor(JsonVisitor::new(&mut writer));
+        fields.record(&mut visitor);
+        visitor.finish()
+    }
+
+    /// Fields recorded later are merged into the json object of the span, like `JsonFields` does.
+    fn add_fields(&self, current: &'writer mut FormattedFields<Self>, fields: &Record<'_>) -> std::fmt::Result {
+        if current.is_empty() {
+            return self.format_fields(current.as_writer(), fields);
+        }
+        let mut added = String::new();
+        self.format_fields(Writer::new(&mut added), fields)?;
+        let mut object = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&current.fields).map_err(|_| std::fmt::Error)?;
+        object.extend(serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&added).map_err(|_| std::fmt::Error)?);
+        current.fields = serde_json::to_string(&object).map_err(|_| std::fmt::Error)?;
+        Ok(())
+    }
+}
+
+/// The pretty and json formats record the fields of events without the field formatter,
+/// events with telegram ids or message texts are passed on with masked values.
+pub(crate) struct RedactingEvents<F>(pub F);
+
+/// Masked values of the fields of an event.
+#[derive(Default)]
+struct RedactedValues(Vec<(Field, Box<dyn Value>)>);
+
+impl RedactedValues {
+    fn push(&mut self, field: &Field, value: Box<dyn Value>, recorded: impl FnOnce() -> String) {
+        let value = match redaction().field_value(field.name(), recorded) {
+            Some(redacted) => Box::new(display(redacted)),
+            None => value,
+        };
+        self.0.push((field.clone(), value));
+    }
+}
+
+impl Visit for RedactedValues {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.push((field.clone(), Box::new(value)))
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, Box::new(value), || value.to_string())
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, Box::new(value), || value.to_string())
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.push((field.clone(), Box::new(value)))
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, Box::new(value.to_string()), || value.to_string())
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        let value = format!("{:?}", value);
+        self.push(field, Box::new(display(value.clone())), || value)
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for RedactingEvents<F>
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, writer: Writer<'_>, event: &Event<'_>) -> std::fmt::Result {
+        if !event.fields().any(|field| is_redacted_field(&field)) {
+            return self.0.format_event(ctx, writer, event);
+        }
+        let mut values = RedactedValues::default();
+        event.record(&mut values);
+        let Some((first_field, _)) = values.0.first() else {
+            return self.0.format_event(ctx, writer, event);
+        };
+        // the remaining entries have no value and are skipped
+        let entries: [(&Field, Option<&dyn Value>); MAX_FIELDS] = std::array::from_fn(|index| match values.0.get(index) {
+            Some((field, value)) => (field, Some(value.as_ref())),
+            None => (first_field, None),
+        });
+        let metadata = event.metadata();
+        let value_set = metadata.fields().value_set(&entries);
+        let redacted = if event.is_contextual() {
+            Event::new(metadata, &value_set)
+        } else {
+            Event::new_child_of(event.parent().cloned(), metadata, &value_set)
+        };
+        self.0.format_event(ctx, writer, &redacted)
+    }
+}
+
+/// Masks secrets, telegram ids and message texts in the attributes and events of exported spans, the formatters of the log outputs do not see them.
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+pub(crate) struct RedactingSpanProcessor<P>(pub P);
+
+#[cfg(feature = "otel")]
+impl<P: opentelemetry_sdk::trace::SpanProcessor> opentelemetry_sdk::trace::SpanProcessor for RedactingSpanProcessor<P> {
+    fn on_start(&self, span: &mut opentelemetry_sdk::trace::Span, cx: &opentelemetry::Context) {
+        self.0.on_start(span, cx)
+    }
+
+    fn on_end(&self, mut span: opentelemetry_sdk::export::trace::SpanData) {
+        redact_attributes(&mut span.attributes);
+        for event in span.events.events.iter_mut() {
+            redact_attributes(&mut event.attributes);
+        }
+        self.0.on_end(span)
+    }
+
+    fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+        self.0.force_flush()
+    }
+
+    fn shutdown(&self) -> opentelemetry::trace::TraceResult<()> {
+        self.0.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.0.set_resource(resource)
+    }
+}
+
+#[cfg(feature = "otel")]
+fn redact_attributes(attributes: &mut [opentelemetry::KeyValue]) {
+    for attribute in attributes.iter_mut() {
+        if let Some(redacted) = redaction().field_value(attribute.key.as_str(), || attribute.value.as_str().into_owned()) {
+            attribute.value = opentelemetry::Value::from(redacted);
+        } else if let opentelemetry::Value::String(value) = &attribute.value {
+            if contains_secret(value.as_str()) {
+                attribute.value = opentelemetry::Value::from(redact_secrets(value.as_str()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::redaction::{hash_telegram_id, redact_url_credentials, Redaction, Token};
+
+    #[test]
+    fn hashes_telegram_ids_per_salt() {
+        let hash = hash_telegram_id("salt", 1002);
+        assert_eq!(hash, hash_telegram_id("salt", 1002));
+        assert_ne!(hash, hash_telegram_id("other salt", 1002));
+        assert_ne!(hash, hash_telegram_id("salt", 1003));
+        assert!(hash.starts_with("tg-"));
+        assert_eq!(hash.len(), "tg-".len() + 12);
+        assert!(!hash.contains("1002"));
+    }
+
+    #[test]
+    fn logs_only_the_prefix_of_tokens() {
+        assert_eq!(Token("abcdEFGHijklMNOPqrstUVWXyz012345").to_string(), "abcd...");
+        assert_eq!(Token("abcdEFGHijklMNOP").to_string(), "abcd...");
+        assert_eq!(Token("abcdEFGHijklMNO").to_string(), "<redacted>");
+        assert_eq!(Token("abc").to_string(), "<redacted>");
+        assert_eq!(Token("").to_string(), "<redacted>");
+    }
+
+    #[test]
+    fn logs_message_text_only_if_enabled() {
+        let redaction = |log_message_text| Redaction { salt: "salt".to_string(), log_message_text, secrets: vec![] };
+        assert_eq!(redaction(false).field_value("text", || "Hello \"bot\"".to_string()), Some("<11 chars>".to_string()));
+        assert_eq!(redaction(false).field_value("text", || "Grüße".to_string()), Some("<5 chars>".to_string()));
+        assert_eq!(redaction(true).field_value("text", || "Hello \"bot\"".to_string()), None);
+    }
+
+    #[test]
+    fn hashes_telegram_id_fields() {
+        let redaction = Redaction { salt: "salt".to_string(), log_message_text: true, secrets: vec![] };
+        let hash = Some(hash_telegram_id("salt", -1002));
+        assert_eq!(redaction.field_value("chat_id", || "-1002".to_string()), hash);
+        assert_eq!(redaction.field_value("telegram_id", || "ChatId(-1002)".to_string()), hash);
+        assert_eq!(redaction.field_value("user_id", || "1002".to_string()), Some(hash_telegram_id("salt", 1002)));
+        assert_eq!(redaction.field_value("user_id", || "tg-0123456789ab".to_string()), Some("tg-0123456789ab".to_string()));
+        assert_eq!(redaction.field_value("user_id", || "None".to_string()), Some("<redacted>".to_string()));
+        assert_eq!(redaction.field_value("update_id", || "1002".to_string()), None);
+    }
+
+    #[test]
+    fn masks_credentials_of_urls() {
+        assert_eq!(
+            redact_url_credentials("could not connect to postgres://telegrambot:s3cret@db:5432/telegrambot, retrying"),
+            "could not connect to postgres://<redacted>@db:5432/telegrambot, retrying",
+        );
+        assert_eq!(
+            redact_url_credentials("\"https://user@example.com/a\" and https://token:x@example.com"),
+            "\"https://<redacted>@example.com/a\" and https://<redacted>@example.com",
+        );
+    }
+
+    #[test]
+    fn keeps_urls_without_credentials() {
+        let text = "sending to https://example.com/events?to=a@b.c and mailto:user@example.com";
+        assert_eq!(redact_url_credentials(text), text);
+    }
+}
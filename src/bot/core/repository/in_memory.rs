@@ -0,0 +1,265 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::model::{AuditEvent, Broadcast, Incident, NewAuditEvent, NewBroadcast, NewOrder, NewOutboxEntry, Order, OutboxAttempt, OutboxEntry};
+use crate::bot::core::db::user_representation::{UserRepresentation, UserRole};
+use crate::bot::core::redaction::{TelegramId, Token};
+use crate::bot::core::repository::{AccountRepository, AuditRepository, IncidentRepository, OrderRepository, OutboxRepository, UserRepository};
+use crate::bot::core::util::random_start_token;
+
+/// Repository without persistence for the handler tests.
+#[derive(Debug, Clone)]
+pub(crate) struct InMemoryRepository {
+    bot_name: String,
+    users: Arc<RwLock<Vec<UserRepresentation>>>,
+    incidents: Arc<RwLock<Vec<Incident>>>,
+    audit_events: Arc<RwLock<Vec<AuditEvent>>>,
+    broadcasts: Arc<RwLock<Vec<Broadcast>>>,
+    orders: Arc<RwLock<Vec<Order>>>,
+    outbox: Arc<RwLock<Vec<OutboxEntry>>>,
+    /// Last id of an outbox entry, not reused after pruning
+    last_outbox_id: Arc<AtomicI64>,
+}
+
+impl InMemoryRepository {
+    pub fn new(bot_name: &str) -> Self {
+        Self {
+            bot_name: bot_name.to_string(),
+            users: Default::default(),
+            incidents: Default::default(),
+            audit_events: Default::default(),
+            broadcasts: Default::default(),
+            orders: Default::default(),
+            outbox: Default::default(),
+            last_outbox_id: Default::default(),
+        }
+    }
+
+    pub fn create_user(&self, user_name: &str, role: UserRole) -> Result<UserRepresentation, DatabaseError> {
+        let mut users = self.users.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock users. {}", error)))?;
+        if users.iter().any(|user| user.name.eq(user_name)) {
+            return Err(DatabaseError::CreateError(format!("Could not create user '{}'. User exists.", user_name)));
+        }
+
+        let start_token = random_start_token();
+        let user = UserRepresentation {
+            id: users.len() as i64 + 1,
+            name: user_name.to_string(),
+            bot_start_url: format!("https://t.me/{}?start={}", self.bot_name, start_token),
+            start_token,
+            role,
+            telegram_id: None,
+        };
+        users.push(user.clone());
+        Ok(user)
+    }
+}
+
+impl UserRepository for InMemoryRepository {
+    async fn known_user(&self, telegram_user_id: i64) -> Result<Option<UserRepresentation>, DatabaseError> {
+        let users = self.users.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to check if user is known: {}. Error: {}", TelegramId(telegram_user_id), error)))?;
+        Ok(users.iter()
+            .find(|user| user.telegram_id.eq(&Some(telegram_user_id)))
+            .cloned())
+    }
+
+    async fn list_registered_users(&self) -> Result<Vec<UserRepresentation>, DatabaseError> {
+        let users = self.users.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock users. {}", error)))?;
+        Ok(users.iter()
+            .filter(|user| user.telegram_id.is_some())
+            .cloned()
+            .collect())
+    }
+}
+
+impl AccountRepository for InMemoryRepository {
+    async fn register_telegram_account_of_user(&mut self, start_token: &str, telegram_id: i64) -> Result<UserRepresentation, DatabaseError> {
+        let mut users = self.users.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock users. {}", error)))?;
+        if let Some(user) = users.iter().find(|user| user.telegram_id.eq(&Some(telegram_id))) {
+            return Ok(user.clone());
+        }
+
+        let user = users.iter_mut()
+            .find(|user| user.start_token.eq(start_token))
+            .ok_or_else(|| DatabaseError::UnknownUser(format!("Could not find token '{}' for telegram id '{}'.", Token(start_token), TelegramId(telegram_id))))?;
+        match user.telegram_id {
+            Some(present_telegram_id) => {
+                Err(DatabaseError::Other(format!("Telegram id={} does not match the found telegram id={}", TelegramId(telegram_id), TelegramId(present_telegram_id))))
+            }
+            None => {
+                user.telegram_id = Some(telegram_id);
+                Ok(user.clone())
+            }
+        }
+    }
+}
+
+impl IncidentRepository for InMemoryRepository {
+    async fn record_incident(&self, incident: Incident) -> Result<(), DatabaseError> {
+        self.incidents.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock incidents. {}", error)))?
+            .push(incident);
+        Ok(())
+    }
+
+    async fn list_incidents(&self, limit: i64) -> Result<Vec<Incident>, DatabaseError> {
+        let incidents = self.incidents.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock incidents. {}", error)))?;
+        Ok(incidents.iter()
+            .rev()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+impl AuditRepository for InMemoryRepository {
+    async fn record_audit_event(&self, event: NewAuditEvent) -> Result<(), DatabaseError> {
+        let mut audit_events = self.audit_events.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock audit log. {}", error)))?;
+        let id = audit_events.len() as i64 + 1;
+        audit_events.push(AuditEvent {
+            id,
+            created_at: event.created_at,
+            actor: event.actor,
+            action: event.action,
+            target: event.target,
+            detail: event.detail,
+        });
+        Ok(())
+    }
+
+    async fn list_audit_events(&self, limit: i64) -> Result<Vec<AuditEvent>, DatabaseError> {
+        let audit_events = self.audit_events.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock audit log. {}", error)))?;
+        Ok(audit_events.iter()
+            .rev()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn record_broadcast(&self, broadcast: NewBroadcast) -> Result<(), DatabaseError> {
+        let mut broadcasts = self.broadcasts.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock broadcasts. {}", error)))?;
+        let id = broadcasts.len() as i64 + 1;
+        broadcasts.push(Broadcast {
+            id,
+            created_at: broadcast.created_at,
+            sender: broadcast.sender,
+            message: broadcast.message,
+            recipients: broadcast.recipients,
+            delivered: broadcast.delivered,
+        });
+        Ok(())
+    }
+
+    async fn list_broadcasts(&self, limit: i64) -> Result<Vec<Broadcast>, DatabaseError> {
+        let broadcasts = self.broadcasts.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock broadcasts. {}", error)))?;
+        Ok(broadcasts.iter()
+            .rev()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+impl OrderRepository for InMemoryRepository {
+    async fn record_order(&self, order: NewOrder) -> Result<(), DatabaseError> {
+        let mut orders = self.orders.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock orders. {}", error)))?;
+        let id = orders.len() as i64 + 1;
+        orders.push(Order {
+            id,
+            created_at: order.created_at,
+            telegram_id: order.telegram_id,
+            full_name: order.full_name,
+            product: order.product,
+        });
+        Ok(())
+    }
+
+    async fn list_orders(&self, telegram_id: i64, limit: i64) -> Result<Vec<Order>, DatabaseError> {
+        let orders = self.orders.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock orders. {}", error)))?;
+        Ok(orders.iter()
+            .rev()
+            .filter(|order| order.telegram_id == telegram_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+impl OutboxRepository for InMemoryRepository {
+    async fn enqueue_outbox_entries(&self, entries: Vec<NewOutboxEntry>) -> Result<(), DatabaseError> {
+        let mut outbox = self.outbox.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock event outbox. {}", error)))?;
+        for entry in entries {
+            let id = self.last_outbox_id.fetch_add(1, Ordering::Relaxed) + 1;
+            outbox.push(OutboxEntry {
+                id,
+                created_at: entry.created_at,
+                event_id: entry.event_id,
+                event_type: entry.event_type,
+                url: entry.url,
+                payload: entry.payload,
+                attempts: 0,
+                next_attempt_at: entry.next_attempt_at,
+                delivered_at: None,
+                last_error: None,
+            });
+        }
+        Ok(())
+    }
+
+    async fn pending_outbox_urls(&self) -> Result<Vec<String>, DatabaseError> {
+        let outbox = self.outbox.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock event outbox. {}", error)))?;
+        let mut urls = outbox.iter()
+            .filter(|entry| entry.next_attempt_at.is_some())
+            .map(|entry| entry.url.clone())
+            .collect::<Vec<_>>();
+        urls.sort();
+        urls.dedup();
+        Ok(urls)
+    }
+
+    async fn due_outbox_entries(&self, url: &str, now: i64, limit: i64) -> Result<Vec<OutboxEntry>, DatabaseError> {
+        let outbox = self.outbox.read()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock event outbox. {}", error)))?;
+        Ok(outbox.iter()
+            .filter(|entry| entry.url == url && entry.next_attempt_at.is_some())
+            .take(limit.max(0) as usize)
+            .take_while(|entry| entry.next_attempt_at.is_some_and(|next_attempt_at| next_attempt_at <= now))
+            .cloned()
+            .collect())
+    }
+
+    async fn record_outbox_attempt(&self, id: i64, attempt: OutboxAttempt) -> Result<(), DatabaseError> {
+        let mut outbox = self.outbox.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock event outbox. {}", error)))?;
+        let entry = outbox.iter_mut()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| DatabaseError::Other(format!("Could not find outbox entry {}.", id)))?;
+        entry.attempts = attempt.attempts;
+        entry.next_attempt_at = attempt.next_attempt_at;
+        entry.delivered_at = attempt.delivered_at;
+        entry.last_error = attempt.last_error;
+        Ok(())
+    }
+
+    async fn prune_outbox(&self, created_before: i64) -> Result<usize, DatabaseError> {
+        let mut outbox = self.outbox.write()
+            .map_err(|error| DatabaseError::Other(format!("Failed to lock event outbox. {}", error)))?;
+        let size = outbox.len();
+        outbox.retain(|entry| entry.next_attempt_at.is_some() || entry.created_at >= created_before);
+        Ok(size - outbox.len())
+    }
+}
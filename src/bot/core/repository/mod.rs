@@ -0,0 +1,88 @@
+use std::future::Future;
+
+use crate::bot::core::db::DatabaseError;
+use crate::bot::core::db::model::{AuditEvent, Broadcast, Incident, NewAuditEvent, NewBroadcast, NewOrder, NewOutboxEntry, Order, OutboxAttempt, OutboxEntry};
+use crate::bot::core::db::user_representation::UserRepresentation;
+
+#[cfg(test)]
+pub(crate) mod in_memory;
+
+/// Read access to known users, injected into handlers as dptree dependency.
+pub(crate) trait UserRepository: Clone + Send + Sync + 'static {
+    /// Look up a registered user by telegram id, always reads the current state
+    /// as other replicas and the admin cli change users of the same database.
+    fn known_user(&self, telegram_user_id: i64) -> impl Future<Output=Result<Option<UserRepresentation>, DatabaseError>> + Send;
+
+    fn known_user_exists(&self, telegram_user_id: i64) -> impl Future<Output=Result<bool, DatabaseError>> + Send {
+        async move {
+            Ok(self.known_user(telegram_user_id).await?.is_some())
+        }
+    }
+
+    fn known_admin_user_exists(&self, telegram_user_id: i64) -> impl Future<Output=Result<bool, DatabaseError>> + Send {
+        async move {
+            Ok(self.known_user(telegram_user_id).await?
+                .map(|user| user.is_admin())
+                .unwrap_or(false))
+        }
+    }
+
+    /// All users with a linked telegram account
+    fn list_registered_users(&self) -> impl Future<Output=Result<Vec<UserRepresentation>, DatabaseError>> + Send;
+}
+
+/// Linking telegram accounts to users.
+pub(crate) trait AccountRepository: Clone + Send + Sync + 'static {
+    fn register_telegram_account_of_user(&mut self, start_token: &str, telegram_id: i64) -> impl Future<Output=Result<UserRepresentation, DatabaseError>> + Send;
+}
+
+/// Incidents of failed updates, listed with `admin incidents`.
+pub(crate) trait IncidentRepository: Clone + Send + Sync + 'static {
+    fn record_incident(&self, incident: Incident) -> impl Future<Output=Result<(), DatabaseError>> + Send;
+
+    /// Most recent incidents first
+    fn list_incidents(&self, limit: i64) -> impl Future<Output=Result<Vec<Incident>, DatabaseError>> + Send;
+}
+
+/// Audit log of administrative actions and the broadcast history, shown in the dashboard.
+pub(crate) trait AuditRepository: Clone + Send + Sync + 'static {
+    fn record_audit_event(&self, event: NewAuditEvent) -> impl Future<Output=Result<(), DatabaseError>> + Send;
+
+    /// Most recent events first
+    fn list_audit_events(&self, limit: i64) -> impl Future<Output=Result<Vec<AuditEvent>, DatabaseError>> + Send;
+
+    fn record_broadcast(&self, broadcast: NewBroadcast) -> impl Future<Output=Result<(), DatabaseError>> + Send;
+
+    /// Most recent broadcasts first
+    fn list_broadcasts(&self, limit: i64) -> impl Future<Output=Result<Vec<Broadcast>, DatabaseError>> + Send;
+}
+
+/// Orders of the purchase dialogue, shown in the mini app.
+pub(crate) trait OrderRepository: Clone + Send + Sync + 'static {
+    fn record_order(&self, order: NewOrder) -> impl Future<Output=Result<(), DatabaseError>> + Send;
+
+    /// Most recent orders of the telegram account first
+    fn list_orders(&self, telegram_id: i64, limit: i64) -> impl Future<Output=Result<Vec<Order>, DatabaseError>> + Send;
+}
+
+/// Outbox of the event webhooks, entries are kept until they are delivered or given up.
+pub(crate) trait OutboxRepository: Clone + Send + Sync + 'static {
+    fn enqueue_outbox_entries(&self, entries: Vec<NewOutboxEntry>) -> impl Future<Output=Result<(), DatabaseError>> + Send;
+
+    /// Urls with pending entries
+    fn pending_outbox_urls(&self) -> impl Future<Output=Result<Vec<String>, DatabaseError>> + Send;
+
+    /// Pending entries of the url oldest first, up to the first one with a next attempt after `now`.
+    /// Later entries wait for a retry of an earlier one, so the receiver gets the events in order.
+    fn due_outbox_entries(&self, url: &str, now: i64, limit: i64) -> impl Future<Output=Result<Vec<OutboxEntry>, DatabaseError>> + Send;
+
+    fn record_outbox_attempt(&self, id: i64, attempt: OutboxAttempt) -> impl Future<Output=Result<(), DatabaseError>> + Send;
+
+    /// Remove delivered and given up entries created before the time, returns the number of removed entries
+    fn prune_outbox(&self, created_before: i64) -> impl Future<Output=Result<usize, DatabaseError>> + Send;
+}
+
+/// Everything the handlers need, implemented by the database client and the in-memory repository of the tests.
+pub(crate) trait BotRepository: UserRepository + AccountRepository + IncidentRepository + AuditRepository + OrderRepository + OutboxRepository {}
+
+impl<T: UserRepository + AccountRepository + IncidentRepository + AuditRepository + OrderRepository + OutboxRepository> BotRepository for T {}
@@ -0,0 +1,157 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use teloxide::dispatching::ShutdownToken;
+use tokio::sync::Notify;
+use tokio::task::JoinSet;
+
+use crate::bot::core::healthcheck::readiness::BotReadiness;
+
+/// Like `timeout(1)`, the process was stopped while handlers were still running
+const EXIT_CODE_DEADLINE_EXCEEDED: i32 = 124;
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_NOTIFY: Notify = Notify::const_new();
+static BACKGROUND_TASKS: Mutex<Option<JoinSet<()>>> = Mutex::new(None);
+
+/// Long running handlers like a broadcast stop early and report their progress.
+pub(crate) fn is_shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Resolves with the shutdown signal, e.g. to stop a listener from accepting requests.
+pub(crate) async fn shutdown_requested() {
+    // registered before the check, so a signal in between is not missed
+    let notified = SHUTDOWN_NOTIFY.notified();
+    if !is_shutdown_requested() {
+        notified.await;
+    }
+}
+
+fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    SHUTDOWN_NOTIFY.notify_waiters();
+}
+
+/// Run a task that outlives the request that started it, e.g. a broadcast of the admin api or a separate listener.
+/// The shutdown waits for it within its deadline, before the database is closed.
+pub(crate) fn spawn_background_task<F>(task: F)
+where
+    F: Future<Output=()> + Send + 'static,
+{
+    let mut tasks = BACKGROUND_TASKS.lock().expect("Background task lock poisoned");
+    let tasks = tasks.get_or_insert_with(JoinSet::new);
+    // the results of finished tasks are kept until they are joined
+    while tasks.try_join_next().is_some() {}
+    tasks.spawn(task);
+}
+
+/// Wait for the background tasks, including the ones spawned meanwhile.
+pub(crate) async fn join_background_tasks() {
+    loop {
+        let Some(mut tasks) = BACKGROUND_TASKS.lock().expect("Background task lock poisoned").take() else {
+            return;
+        };
+        while let Some(result) = tasks.join_next().await {
+            if let Err(error) = result {
+                tracing::error!("A background task failed: {}", error);
+            }
+        }
+    }
+}
+
+/// How the dispatcher stopped after a shutdown signal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ShutdownOutcome {
+    /// All received updates were handled
+    Drained,
+    /// Handlers were still running at the deadline
+    DeadlineExceeded,
+}
+
+impl ShutdownOutcome {
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            ShutdownOutcome::Drained => 0,
+            ShutdownOutcome::DeadlineExceeded => EXIT_CODE_DEADLINE_EXCEEDED,
+        }
+    }
+}
+
+/// SIGTERM and SIGINT, installed before dispatching so a failure is a startup error.
+pub(crate) struct ShutdownSignal {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignal {
+    pub(crate) fn install() -> Result<Self, anyhow::Error> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                terminate: signal(SignalKind::terminate())?,
+                interrupt: signal(SignalKind::interrupt())?,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// Name of the received signal
+    async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.terminate.recv() => "SIGTERM",
+                _ = self.interrupt.recv() => "SIGINT",
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            "Ctrl-C"
+        }
+    }
+}
+
+/// Runs the dispatcher until a shutdown signal, then stops the update listener and waits for the running handlers and background tasks.
+/// The webhook server stops accepting requests with the update listener, separate listeners with [`shutdown_requested`].
+pub(crate) async fn dispatch_until_signal<F>(dispatch: F, shutdown_token: ShutdownToken, mut signal: ShutdownSignal, readiness: &BotReadiness, timeout: Duration) -> Result<ShutdownOutcome, anyhow::Error>
+where
+    F: Future<Output=()>,
+{
+    tokio::pin!(dispatch);
+    let received = tokio::select! {
+        () = &mut dispatch => {
+            return Err(anyhow!("The dispatcher stopped without a shutdown signal, the update listener failed."));
+        }
+        received = signal.recv() => received,
+    };
+    tracing::info!("Received {}, stopping the update listener and waiting up to {:?} for running handlers.", received, timeout);
+    request_shutdown();
+    readiness.set_dispatcher_running(false);
+    if let Err(error) = shutdown_token.shutdown() {
+        tracing::warn!("Could not stop the dispatcher: {}", error);
+    }
+
+    let drain = async {
+        dispatch.await;
+        join_background_tasks().await;
+    };
+    match tokio::time::timeout(timeout, drain).await {
+        Ok(()) => {
+            tracing::info!("All received updates were handled.");
+            Ok(ShutdownOutcome::Drained)
+        }
+        Err(_) => {
+            tracing::warn!("Handlers were still running after {:?}, stopping anyway.", timeout);
+            Ok(ShutdownOutcome::DeadlineExceeded)
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Field with the signature of data signed by telegram
+pub(crate) const HASH_FIELD: &str = "hash";
+
+/// Sorted `key=value` lines of all fields except the hash, the signed payload of login widget and mini app data.
+pub(crate) fn data_check_string(fields: &BTreeMap<String, String>) -> String {
+    fields.iter()
+        .filter(|(key, _)| key.as_str() != HASH_FIELD)
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length.");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Compares in constant time, an invalid hex string never matches.
+pub(crate) fn verify_hmac_sha256(key: &[u8], data: &[u8], expected_hex: &str) -> bool {
+    let Some(expected) = from_hex(expected_hex) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length.");
+    mac.update(data);
+    mac.verify_slice(&expected).is_ok()
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use teloxide::dispatching::dialogue::{InMemStorage, Storage};
+use teloxide::dispatching::{DpHandlerDescription, UpdateHandler};
+use teloxide::dptree;
+use teloxide::dptree::di::{DependencyMap, DependencySupplier};
+use teloxide::dptree::HandlerDescription;
+use teloxide::types::{Update, UpdateKind};
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+use crate::bot::core::metrics::metrics;
+use crate::bot::State;
+
+/// Runs the following handlers within an `update` span, database and Bot API calls become its children.
+pub(crate) fn update_span<E: Send + Sync + 'static>() -> UpdateHandler<E> {
+    // an entry description keeps the allowed updates of the following handlers
+    dptree::from_fn_with_description(DpHandlerDescription::entry(), |dependencies: DependencyMap, cont| async move {
+        let update: Arc<Update> = dependencies.get();
+        // the storage is provided as `Arc<InMemStorage<_>>` like teloxide's dialogue handlers expect it
+        let storage: Arc<Arc<InMemStorage<State>>> = dependencies.get();
+        let span = new_update_span(&update, Arc::clone(&storage)).await;
+        cont(dependencies).instrument(span).await
+    })
+}
+
+async fn new_update_span(update: &Update, storage: Arc<InMemStorage<State>>) -> Span {
+    let span = tracing::info_span!("update",
+        update_id = update.id.0,
+        chat_id = Empty,
+        user_id = Empty,
+        command = Empty,
+        dialogue_state = Empty,
+        text = Empty,
+        trace_id = Empty,
+    );
+    if let Some(user) = update.from() {
+        span.record("user_id", user.id.0);
+    }
+    if let Some(command) = metrics().command_label(update) {
+        span.record("command", command);
+    }
+    if let UpdateKind::Message(message) = &update.kind {
+        if let Some(text) = message.text() {
+            span.record("text", text);
+        }
+    }
+    if let Some(chat) = update.chat() {
+        span.record("chat_id", chat.id.0);
+    }
+    // state before handling the update
+    if let Some(state) = dialogue_state_name(update, storage).await {
+        span.record("dialogue_state", state);
+    }
+    #[cfg(feature = "otel")]
+    otel::record_trace_id(&span);
+    span
+}
+
+/// Dialogue state of the chat of an update, a missing dialogue starts with the default state.
+pub(crate) async fn dialogue_state_name(update: &Update, storage: Arc<InMemStorage<State>>) -> Option<&'static str> {
+    let chat = update.chat()?;
+    match storage.get_dialogue(chat.id).await {
+        Ok(state) => Some(state.unwrap_or_default().name()),
+        Err(error) => {
+            tracing::warn!("Could not read dialogue state: {}", error);
+            None
+        }
+    }
+}
+
+/// Exports the remaining spans when dropped, also if `main` returns early with an error.
+pub(crate) struct TelemetryGuard;
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        otel::shutdown();
+    }
+}
+
+/// Export of spans via OTLP/HTTP, enabled with the `otel` feature and a configured endpoint.
+#[cfg(feature = "otel")]
+pub(crate) mod otel {
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::trace::{BatchSpanProcessor, TracerProvider};
+    use opentelemetry_sdk::{runtime, Resource};
+    use tracing::Span;
+    use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    use crate::bot::core::bot_config::telemetry::BotTelemetryConfig;
+    use crate::bot::core::redaction::RedactingSpanProcessor;
+
+    /// Layer exporting spans in batches, None if no endpoint is configured.
+    pub(crate) fn layer<S>(config: &BotTelemetryConfig) -> Result<Option<OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>>, anyhow::Error>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let Some(traces_url) = &config.otlp_traces_url else {
+            return Ok(None);
+        };
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(traces_url.as_str())
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_span_processor(RedactingSpanProcessor(BatchSpanProcessor::builder(exporter, runtime::Tokio).build()))
+            .with_resource(Resource::new([KeyValue::new("service.name", config.service_name.clone())]))
+            .build();
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+        opentelemetry::global::set_tracer_provider(provider);
+        Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+    }
+
+    /// Log lines within the span carry the trace id, to find the trace of a user complaint.
+    pub(super) fn record_trace_id(span: &Span) {
+        let trace_id = span.context().span().span_context().trace_id();
+        if trace_id != opentelemetry::trace::TraceId::INVALID {
+            span.record("trace_id", trace_id.to_string());
+        }
+    }
+
+    /// Export the remaining spans before the process exits.
+    pub(crate) fn shutdown() {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::body::Bytes;
+    use opentelemetry::trace::TraceContextExt;
+    use reqwest::Url;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::bot::core::bot_config::telemetry::BotTelemetryConfig;
+    use crate::bot::core::mock_api::serve_local;
+    use crate::bot::core::telemetry::{otel, TelemetryGuard};
+
+    const SERVICE_NAME: &str = "telegrambot-test";
+    const DATABASE_URL: &str = "postgres://telegrambot:collector-secret@db:5432/telegrambot";
+
+    /// Stand-in of an OTLP/HTTP collector, keeps the protobuf bodies sent to /v1/traces.
+    #[derive(Default)]
+    struct Collector {
+        requests: Mutex<Vec<(String, Bytes)>>,
+    }
+
+    impl Collector {
+        async fn serve(self: &Arc<Self>) -> Url {
+            let router = axum::Router::new()
+                .route("/v1/traces", axum::routing::post(receive_traces))
+                .with_state(self.clone());
+            serve_local(router).await.join("v1/traces").unwrap()
+        }
+    }
+
+    async fn receive_traces(State(collector): State<Arc<Collector>>, headers: HeaderMap, body: Bytes) -> StatusCode {
+        let content_type = headers.get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        collector.requests.lock().unwrap().push((content_type, body));
+        StatusCode::OK
+    }
+
+    fn contains(body: &[u8], expected: &[u8]) -> bool {
+        body.windows(expected.len()).any(|window| window == expected)
+    }
+
+    #[test]
+    fn no_layer_without_endpoint() {
+        let config = BotTelemetryConfig { otlp_traces_url: None, service_name: SERVICE_NAME.to_string() };
+        let layer = otel::layer::<tracing_subscriber::Registry>(&config).unwrap();
+        assert!(layer.is_none());
+    }
+
+    /// The batch exporter runs on the tokio runtime, the shutdown of the guard waits for it.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn guard_exports_redacted_spans_to_collector() {
+        let collector = Arc::new(Collector::default());
+        let config = BotTelemetryConfig { otlp_traces_url: Some(collector.serve().await), service_name: SERVICE_NAME.to_string() };
+        let layer = otel::layer(&config).unwrap().expect("an endpoint is configured");
+        let guard = TelemetryGuard;
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let trace_id = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("update", trace_id = tracing::field::Empty, database_url = DATABASE_URL);
+            otel::record_trace_id(&span);
+            span.in_scope(|| tracing::info_span!("database").in_scope(|| {}));
+            span.context().span().span_context().trace_id()
+        });
+        tokio::task::spawn_blocking(move || drop(guard)).await.unwrap();
+
+        // without the shutdown the batch exporter would send the spans after its delay of seconds
+        let requests = collector.requests.lock().unwrap().clone();
+        let (content_type, body) = requests.first().expect("the guard should export the spans");
+        assert_eq!(content_type, "application/x-protobuf");
+        assert!(contains(body, SERVICE_NAME.as_bytes()));
+        assert!(contains(body, b"update"));
+        assert!(contains(body, b"database"));
+        assert!(contains(body, &trace_id.to_bytes()));
+        assert!(!contains(body, b"collector-secret"), "credentials of urls are redacted");
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::core::signature::{data_check_string, hmac_sha256, verify_hmac_sha256, HASH_FIELD};
+
+/// Telegram signs the init data when the app is opened, older data is rejected
+const INIT_DATA_MAX_AGE_SECONDS: i64 = 24 * 60 * 60;
+/// Clocks of telegram and the bot may differ a little
+const INIT_DATA_MAX_CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// The `user` field of the init data, only the fields used by the bot.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct WebAppUser {
+    pub id: i64,
+    pub first_name: String,
+}
+
+/// Mini apps use the HMAC of the bot token with the constant `WebAppData` as key,
+/// see https://core.telegram.org/bots/webapps#validating-data-received-via-the-mini-app
+fn init_data_key(bot_token: &str) -> Vec<u8> {
+    hmac_sha256(b"WebAppData", bot_token.as_bytes())
+}
+
+/// Checks the hash and age of `Telegram.WebApp.initData`, returns the user that opened the app.
+pub(crate) fn verify_init_data(init_data: &str, bot_token: &str, now: i64) -> Result<WebAppUser, anyhow::Error> {
+    let mut url = Url::parse("http://localhost/").expect("Static url is valid.");
+    url.set_query(Some(init_data));
+    let fields = url.query_pairs()
+        .into_owned()
+        .collect::<BTreeMap<String, String>>();
+    let hash = fields.get(HASH_FIELD).ok_or_else(|| anyhow!("The init data is not signed."))?;
+    if !verify_hmac_sha256(&init_data_key(bot_token), data_check_string(&fields).as_bytes(), hash) {
+        return Err(anyhow!("The signature of the init data is invalid."));
+    }
+    let auth_date = fields.get("auth_date")
+        .and_then(|auth_date| auth_date.parse::<i64>().ok())
+        .ok_or_else(|| anyhow!("The init data has no valid auth_date."))?;
+    if auth_date > now + INIT_DATA_MAX_CLOCK_SKEW_SECONDS || now - auth_date > INIT_DATA_MAX_AGE_SECONDS {
+        return Err(anyhow!("The init data has expired, please reopen the app."));
+    }
+    let user = fields.get("user").ok_or_else(|| anyhow!("The init data has no user."))?;
+    serde_json::from_str(user).map_err(|error| anyhow!("The user of the init data is invalid. {}", error))
+}
+
+/// Init data signed like telegram does it, to call the api without a telegram client.
+#[cfg(test)]
+pub(crate) fn signed_init_data(user: &WebAppUser, bot_token: &str, now: i64) -> String {
+    let mut fields = BTreeMap::new();
+    fields.insert("auth_date".to_string(), now.to_string());
+    fields.insert("user".to_string(), serde_json::to_string(user).expect("User is serializable."));
+    let hash = crate::bot::core::signature::to_hex(&hmac_sha256(&init_data_key(bot_token), data_check_string(&fields).as_bytes()));
+    fields.insert(HASH_FIELD.to_string(), hash);
+    Url::parse_with_params("http://localhost/", &fields)
+        .expect("Static url is valid.")
+        .query()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use crate::bot::core::signature::HASH_FIELD;
+    use crate::bot::core::webapp::init_data::{signed_init_data, verify_init_data, WebAppUser};
+
+    const BOT_TOKEN: &str = "123456:test-token";
+    const NOW: i64 = 1_700_000_000;
+
+    fn alice() -> WebAppUser {
+        WebAppUser { id: 1002, first_name: "Alice".to_string() }
+    }
+
+    /// Init data with one field replaced or removed, the hash stays the same.
+    fn with_field(init_data: &str, key: &str, value: Option<&str>) -> String {
+        let mut url = Url::parse("http://localhost/").unwrap();
+        url.set_query(Some(init_data));
+        let fields = url.query_pairs().into_owned().filter(|(name, _)| name != key).collect::<Vec<_>>();
+        let mut changed = Url::parse("http://localhost/").unwrap();
+        changed.query_pairs_mut()
+            .extend_pairs(fields)
+            .extend_pairs(value.map(|value| (key, value)));
+        changed.query().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn accepts_signed_init_data() {
+        let user = verify_init_data(&signed_init_data(&alice(), BOT_TOKEN, NOW), BOT_TOKEN, NOW + 60).unwrap();
+        assert_eq!(user.id, 1002);
+        assert_eq!(user.first_name, "Alice");
+    }
+
+    #[test]
+    fn rejects_tampered_field() {
+        let init_data = signed_init_data(&alice(), BOT_TOKEN, NOW);
+        let tampered = with_field(&init_data, "user", Some(r#"{"id":1001,"first_name":"Alice"}"#));
+        let error = verify_init_data(&tampered, BOT_TOKEN, NOW).unwrap_err();
+        assert!(error.to_string().contains("signature"), "{}", error);
+        assert!(verify_init_data(&init_data, "654321:other-token", NOW).is_err());
+    }
+
+    #[test]
+    fn rejects_expired_auth_date() {
+        let init_data = signed_init_data(&alice(), BOT_TOKEN, NOW - 2 * 24 * 60 * 60);
+        let error = verify_init_data(&init_data, BOT_TOKEN, NOW).unwrap_err();
+        assert!(error.to_string().contains("expired"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_future_auth_date() {
+        let init_data = signed_init_data(&alice(), BOT_TOKEN, NOW + 10 * 60);
+        let error = verify_init_data(&init_data, BOT_TOKEN, NOW).unwrap_err();
+        assert!(error.to_string().contains("expired"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_missing_hash() {
+        let unsigned = with_field(&signed_init_data(&alice(), BOT_TOKEN, NOW), HASH_FIELD, None);
+        let error = verify_init_data(&unsigned, BOT_TOKEN, NOW).unwrap_err();
+        assert!(error.to_string().contains("not signed"), "{}", error);
+    }
+}
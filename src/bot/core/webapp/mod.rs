@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use reqwest::Url;
+use teloxide::Bot;
+use teloxide::prelude::Requester;
+use teloxide::payloads::SetChatMenuButtonSetters;
+use teloxide::types::{MenuButton, WebAppInfo};
+use tower_http::services::ServeDir;
+
+use crate::bot::core::admin_api::ApiError;
+use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_WEBAPP;
+use crate::bot::core::db::user_representation::UserRepresentation;
+use crate::bot::core::metrics::MeasuredRequest;
+use crate::bot::core::redaction::TelegramId;
+use crate::bot::core::repository::{BotRepository, UserRepository};
+use crate::bot::core::webapp::init_data::verify_init_data;
+
+pub(crate) mod init_data;
+mod routes;
+
+/// Scheme of the `Authorization` header, followed by `Telegram.WebApp.initData`
+const INIT_DATA_SCHEME: &str = "tma ";
+
+#[derive(Clone)]
+struct WebAppState<R> {
+    repository: R,
+    /// Key of the init data signature
+    bot_token: String,
+    /// Own `index.html`, the built-in page otherwise
+    assets_dir: Option<PathBuf>,
+}
+
+/// Registered user that opened the mini app.
+#[derive(Clone)]
+struct WebAppSession(UserRepresentation);
+
+/// Telegram Mini App below [`TELEGRAM_BOT_ENDPOINT_WEBAPP`]: the page at `/webapp/`, its assets below `/webapp/assets`
+/// and the json api below `/webapp/api`, which requires the init data of a registered user.
+pub(crate) fn webapp_router<R: BotRepository>(repository: R, bot_token: &str, assets_dir: Option<PathBuf>) -> axum::Router {
+    let state = WebAppState {
+        repository,
+        bot_token: bot_token.to_string(),
+        assets_dir: assets_dir.clone(),
+    };
+    let api = axum::Router::new()
+        .route("/me", get(routes::me))
+        .route("/products", get(routes::products))
+        .route("/orders", get(routes::orders::<R>))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), require_init_data::<R>))
+        .with_state(state.clone());
+    // relative links of the page resolve below the trailing slash
+    let page_url = format!("{}/", TELEGRAM_BOT_ENDPOINT_WEBAPP);
+    let router = axum::Router::new()
+        .route(TELEGRAM_BOT_ENDPOINT_WEBAPP, get(move || async move { Redirect::permanent(&page_url) }))
+        .route(&format!("{}/", TELEGRAM_BOT_ENDPOINT_WEBAPP), get(routes::index::<R>))
+        .nest(&format!("{}/api", TELEGRAM_BOT_ENDPOINT_WEBAPP), api)
+        .with_state(state);
+    match assets_dir {
+        Some(assets_dir) => router.nest_service(&format!("{}/assets", TELEGRAM_BOT_ENDPOINT_WEBAPP), ServeDir::new(assets_dir)),
+        None => router,
+    }
+}
+
+/// Let the menu button of private chats open the mini app, telegram only accepts https urls.
+pub(crate) async fn configure_menu_button(bot: &Bot, text: &str, public_url: &Url) -> Result<(), anyhow::Error> {
+    let url = public_url.join(&format!("{}/", TELEGRAM_BOT_ENDPOINT_WEBAPP))?;
+    tracing::info!("Menu button opens the mini app at {}", url);
+    let menu_button = MenuButton::WebApp { text: text.to_string(), web_app: WebAppInfo { url } };
+    bot.set_chat_menu_button().menu_button(menu_button).send_measured().await?;
+    Ok(())
+}
+
+/// Requires `Authorization: tma <init data>` signed for a registered user.
+async fn require_init_data<R: UserRepository>(State(state): State<WebAppState<R>>, mut request: Request, next: Next) -> Response {
+    let init_data = request.headers()
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix(INIT_DATA_SCHEME));
+    let Some(init_data) = init_data else {
+        return ApiError::new(StatusCode::UNAUTHORIZED, "Missing init data").into_response();
+    };
+    let now = chrono::Utc::now().timestamp();
+    let web_app_user = match verify_init_data(init_data, &state.bot_token, now) {
+        Ok(web_app_user) => web_app_user,
+        Err(error) => {
+            tracing::warn!("Rejected mini app request to {}: {}", request.uri().path(), error);
+            return ApiError::new(StatusCode::UNAUTHORIZED, error.to_string()).into_response();
+        }
+    };
+    match state.repository.known_user(web_app_user.id).await {
+        Ok(Some(user)) => {
+            request.extensions_mut().insert(WebAppSession(user));
+            next.run(request).await
+        }
+        Err(error) => ApiError::from(error).into_response(),
+        Ok(None) => {
+            tracing::info!("Mini app opened by unregistered {}", TelegramId(web_app_user.id));
+            ApiError::new(StatusCode::FORBIDDEN, "Register with the bot first").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::AUTHORIZATION;
+
+    use crate::bot::core::bot_config::TELEGRAM_BOT_ENDPOINT_WEBAPP;
+    use crate::bot::core::db::model::NewOrder;
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::mock_api::{serve_local, MockUser, ADMIN, ALICE, STRANGER};
+    use crate::bot::core::repository::in_memory::InMemoryRepository;
+    use crate::bot::core::repository::{AccountRepository, OrderRepository};
+    use crate::bot::core::webapp::init_data::{signed_init_data, WebAppUser};
+    use crate::bot::core::webapp::webapp_router;
+
+    const BOT_TOKEN: &str = "123456:test-token";
+
+    fn signed(user: MockUser, bot_token: &str, auth_date: i64) -> Option<String> {
+        Some(signed_init_data(&WebAppUser { id: user.id, first_name: user.first_name.to_string() }, bot_token, auth_date))
+    }
+
+    /// Serve the mini app of a repository with the registered users alice and admin, alice purchased a banana.
+    async fn serve_webapp() -> String {
+        let mut repository = InMemoryRepository::new("testbot");
+        let alice = repository.create_user("alice", UserRole::User).unwrap();
+        repository.register_telegram_account_of_user(&alice.start_token, ALICE.id).await.unwrap();
+        let admin = repository.create_user("admin", UserRole::Admin).unwrap();
+        repository.register_telegram_account_of_user(&admin.start_token, ADMIN.id).await.unwrap();
+        repository.record_order(NewOrder {
+            created_at: 1,
+            telegram_id: ALICE.id,
+            full_name: "Alice Example".to_string(),
+            product: "Banana".to_string(),
+        }).await.unwrap();
+
+        let router = webapp_router(repository, BOT_TOKEN, None);
+        serve_local(router).await.join(&format!("{}/api/", TELEGRAM_BOT_ENDPOINT_WEBAPP)).unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn api_requires_init_data_of_registered_user() {
+        let api_url = serve_webapp().await;
+        let now = chrono::Utc::now().timestamp();
+        // init data, path, expected status and body fragment
+        let checks = [
+            (None, "me", 401, "Missing init data"),
+            (signed(ALICE, "654321:other-token", now), "me", 401, "signature of the init data is invalid"),
+            (signed(ALICE, BOT_TOKEN, now - 2 * 24 * 60 * 60), "me", 401, "expired"),
+            (signed(STRANGER, BOT_TOKEN, now), "me", 403, "Register with the bot first"),
+            (signed(ALICE, BOT_TOKEN, now), "me", 200, "\"name\":\"alice\""),
+            (signed(ALICE, BOT_TOKEN, now), "products", 200, "\"Banana\""),
+            (signed(ALICE, BOT_TOKEN, now), "orders", 200, "\"product\":\"Banana\""),
+            (signed(ADMIN, BOT_TOKEN, now), "orders", 200, "[]"),
+        ];
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        for (init_data, path, status, fragment) in checks {
+            let mut request = client.get(format!("{}{}", api_url, path));
+            if let Some(init_data) = init_data {
+                request = request.header(AUTHORIZATION, format!("tma {}", init_data));
+            }
+            let response = request.send().await.unwrap();
+            let actual_status = response.status().as_u16();
+            let body = response.text().await.unwrap();
+            assert!(actual_status == status && body.contains(fragment), "GET {} expected {} containing {}, got {} {}", path, status, fragment, actual_status, body);
+        }
+    }
+}
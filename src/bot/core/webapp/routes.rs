@@ -0,0 +1,67 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Html;
+use axum::{Extension, Json};
+use serde::Serialize;
+
+use crate::bot::core::admin_api::ApiError;
+use crate::bot::core::repository::OrderRepository;
+use crate::bot::core::webapp::{WebAppSession, WebAppState};
+use crate::bot::handlers::product::PRODUCTS;
+
+/// Page served without an own assets directory
+const BUILT_IN_INDEX: &str = include_str!("../../../../resources/webapp/index.html");
+/// Orders shown in the app
+const ORDERS_LIMIT: i64 = 50;
+
+#[derive(Serialize)]
+pub(super) struct Profile {
+    name: String,
+    role: String,
+    telegram_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(super) struct OrderResponse {
+    id: i64,
+    /// Unix timestamp in seconds
+    created_at: i64,
+    full_name: String,
+    product: String,
+}
+
+pub(super) async fn index<R>(State(state): State<WebAppState<R>>) -> Result<Html<String>, ApiError> {
+    match &state.assets_dir {
+        Some(assets_dir) => tokio::fs::read_to_string(assets_dir.join("index.html"))
+            .await
+            .map(Html)
+            .map_err(|error| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Could not read index.html of the mini app. {}", error))),
+        None => Ok(Html(BUILT_IN_INDEX.to_string())),
+    }
+}
+
+pub(super) async fn me(Extension(WebAppSession(user)): Extension<WebAppSession>) -> Json<Profile> {
+    Json(Profile {
+        name: user.name,
+        role: user.role.to_string(),
+        telegram_id: user.telegram_id,
+    })
+}
+
+pub(super) async fn products() -> Json<Vec<&'static str>> {
+    Json(PRODUCTS.to_vec())
+}
+
+/// Most recent orders of the user first.
+pub(super) async fn orders<R: OrderRepository>(State(state): State<WebAppState<R>>, Extension(WebAppSession(user)): Extension<WebAppSession>) -> Result<Json<Vec<OrderResponse>>, ApiError> {
+    let telegram_id = user.telegram_id.expect("Known users have a telegram id.");
+    let orders = state.repository.list_orders(telegram_id, ORDERS_LIMIT).await?;
+    Ok(Json(orders.into_iter()
+        .map(|order| OrderResponse {
+            id: order.id,
+            created_at: order.created_at,
+            full_name: order.full_name,
+            product: order.product,
+        })
+        .collect()))
+}
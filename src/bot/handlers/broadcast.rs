@@ -1,35 +1,167 @@
-use teloxide::Bot;
+use teloxide::{ApiError, Bot, RequestError};
 use teloxide::prelude::{Message, Requester};
 use teloxide::types::ChatId;
 use tracing::debug;
 
 use crate::bot::{HandlerResult, MyDialogue, State};
-use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::audit::{record_broadcast, Actor};
+use crate::bot::core::db::user_representation::UserRepresentation;
+use crate::bot::core::events::{BotEvent, EventBus};
+use crate::bot::core::metrics::{metrics, MeasuredRequest};
+use crate::bot::core::redaction::TelegramId;
+use crate::bot::core::repository::{AuditRepository, OutboxRepository, UserRepository};
+use crate::bot::core::shutdown::is_shutdown_requested;
 
 pub(crate) async fn broadcast_start(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, "Send me the text to broadcast a message to all users.").await?;
+    bot.send_message(msg.chat.id, "Send me the text to broadcast a message to all users.").send_measured().await?;
     dialogue.update(State::Broadcast).await?;
     Ok(())
 }
 
-pub(crate) async fn receive_broadcast_message(bot: Bot, dialogue: MyDialogue, msg: Message, db_client: DatabaseClient) -> HandlerResult {
+pub(crate) async fn receive_broadcast_message<R: UserRepository + AuditRepository + OutboxRepository>(bot: Bot, dialogue: MyDialogue, msg: Message, repository: R, events: EventBus) -> HandlerResult {
     match msg.text().map(ToOwned::to_owned) {
         Some(broadcast_message) => {
+            // the broadcast is sent at most once, also if it fails
+            dialogue.update(State::Start).await?;
             let reply = format!("Sending broadcast to all users:\n{}", broadcast_message);
-            bot.send_message(msg.chat.id, reply).await?;
+            bot.send_message(msg.chat.id, reply).send_measured().await?;
 
-            let users = db_client.list_registered_users().await?;
-            for user in users {
-                let id = ChatId(user.telegram_id.unwrap());
-                debug!("Sending broadcast to {}", id);
-                bot.send_message(id, broadcast_message.clone()).await?;
+            let users = repository.list_registered_users().await?;
+            let admin = repository.known_user(msg.chat.id.0).await?.map(|user| user.name).unwrap_or_default();
+            let outcome = send_broadcast(&bot, &repository, &events, Actor::Telegram(&admin), &users, &broadcast_message).await;
+            if !outcome.failed.is_empty() {
+                let reply = format!("Could not send the broadcast to {} of {} users: {}", outcome.failed.len(), users.len(), outcome.failed.join(", "));
+                bot.send_message(msg.chat.id, reply).send_measured().await?;
+            }
+            if !outcome.not_sent.is_empty() {
+                let reply = format!("The bot is shutting down, the broadcast stopped after {} of {} users. Not sent to: {}", users.len() - outcome.not_sent.len(), users.len(), outcome.not_sent.join(", "));
+                bot.send_message(msg.chat.id, reply).send_measured().await?;
             }
-            dialogue.update(State::Start).await?;
         }
         None => {
-            bot.send_message(msg.chat.id, "Please, send me a proper broadcast message.").await?;
+            bot.send_message(msg.chat.id, "Please, send me a proper broadcast message.").send_measured().await?;
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Result of a broadcast by user names.
+#[derive(Debug, Default)]
+pub(crate) struct BroadcastOutcome {
+    pub delivered: usize,
+    /// Users the message could not be sent to, e.g. because they blocked the bot
+    pub failed: Vec<String>,
+    /// Users skipped because the bot is shutting down
+    pub not_sent: Vec<String>,
+}
+
+/// Send the message to the users in order, a failed delivery does not stop the broadcast, a shutdown does.
+/// The broadcast is added to the history in any case.
+pub(crate) async fn send_broadcast<R: AuditRepository + OutboxRepository>(bot: &Bot, repository: &R, events: &EventBus, sender: Actor<'_>, users: &[UserRepresentation], broadcast_message: &str) -> BroadcastOutcome {
+    let mut outcome = BroadcastOutcome::default();
+    for (index, user) in users.iter().enumerate() {
+        if is_shutdown_requested() {
+            tracing::warn!("Broadcast stopped by shutdown after {} of {} users", index, users.len());
+            outcome.not_sent = users[index..].iter().map(|user| user.name.clone()).collect();
+            break;
+        }
+        let id = ChatId(user.telegram_id.unwrap());
+        debug!("Sending broadcast to {}", TelegramId(id.0));
+        let delivery = bot.send_message(id, broadcast_message).send_measured().await;
+        metrics().record_broadcast_delivery(delivery.is_ok());
+        match delivery {
+            Ok(_) => outcome.delivered += 1,
+            Err(error) => {
+                tracing::warn!("Could not send broadcast to {}: {}", TelegramId(id.0), error);
+                if matches!(error, RequestError::Api(ApiError::BotBlocked)) {
+                    events.publish(repository, BotEvent::BotBlocked { telegram_id: id.0 }).await;
+                }
+                outcome.failed.push(user.name.clone());
+            }
+        }
+    }
+    record_broadcast(repository, sender, broadcast_message, users.len(), outcome.delivered).await;
+    events.publish(repository, BotEvent::BroadcastSent { sender: sender.to_string(), recipients: users.len(), delivered: outcome.delivered }).await;
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::mock_api::{ADMIN, ALICE, BOB};
+    use crate::bot::core::repository::AuditRepository;
+    use crate::bot::test_bot::TestBot;
+
+    #[tokio::test]
+    async fn sends_broadcast_to_registered_users() {
+        let bot = TestBot::start().await;
+        bot.register(&ADMIN, "admin", UserRole::Admin).await;
+        bot.register(&ALICE, "alice", UserRole::User).await;
+        bot.register(&BOB, "bob", UserRole::User).await;
+
+        let replies = bot.send_text(&ADMIN, "/broadcast", 1).await;
+        assert_eq!(replies[0].text, "Send me the text to broadcast a message to all users.");
+        let replies = bot.send_text(&ADMIN, "Hello everyone", 4).await;
+        assert_eq!(replies[0].chat_id, ADMIN.id);
+        assert_eq!(replies[0].text, "Sending broadcast to all users:\nHello everyone");
+        let mut recipients = replies[1..].iter()
+            .map(|reply| {
+                assert_eq!(reply.text, "Hello everyone");
+                reply.chat_id
+            })
+            .collect::<Vec<_>>();
+        recipients.sort();
+        assert_eq!(recipients, vec![ADMIN.id, ALICE.id, BOB.id]);
+
+        // the dialogue is finished, the next text is not broadcast.
+        // Updates of a chat are handled in order, the broadcast handler has returned afterwards.
+        let replies = bot.send_text(&ADMIN, "Hello again", 1).await;
+        assert_eq!(replies[0].chat_id, ADMIN.id);
+        assert!(replies[0].text.starts_with("Unable to handle the message."));
+
+        let broadcasts = bot.repository.list_broadcasts(10).await.unwrap();
+        assert_eq!(broadcasts.len(), 1);
+        assert_eq!((broadcasts[0].sender.as_str(), broadcasts[0].recipients, broadcasts[0].delivered), ("telegram:admin", 3, 3));
+        assert_eq!(bot.published_events().await.last().map(String::as_str), Some("broadcast_sent"));
+        bot.stop().await;
+    }
+
+    #[tokio::test]
+    async fn continues_broadcast_after_failed_delivery() {
+        let bot = TestBot::start().await;
+        bot.register(&ADMIN, "admin", UserRole::Admin).await;
+        bot.register(&ALICE, "alice", UserRole::User).await;
+        bot.register(&BOB, "bob", UserRole::User).await;
+        bot.api.reject_messages_to(&ALICE);
+
+        bot.send_text(&ADMIN, "/broadcast", 1).await;
+        let replies = bot.send_text(&ADMIN, "Hello everyone", 4).await;
+        let texts = replies.iter().map(|reply| (reply.chat_id, reply.text.as_str())).collect::<Vec<_>>();
+        assert_eq!(texts, vec![
+            (ADMIN.id, "Sending broadcast to all users:\nHello everyone"),
+            (ADMIN.id, "Hello everyone"),
+            (BOB.id, "Hello everyone"),
+            (ADMIN.id, "Could not send the broadcast to 1 of 3 users: alice"),
+        ]);
+
+        // the dialogue is finished despite the failed delivery
+        let replies = bot.send_text(&ADMIN, "Hello again", 1).await;
+        assert!(replies[0].text.starts_with("Unable to handle the message."));
+        let broadcasts = bot.repository.list_broadcasts(10).await.unwrap();
+        assert_eq!((broadcasts[0].recipients, broadcasts[0].delivered), (3, 2));
+        assert!(bot.published_events().await.contains(&"bot_blocked".to_string()));
+        bot.stop().await;
+    }
+
+    #[tokio::test]
+    async fn refuses_broadcast_of_users() {
+        let bot = TestBot::start().await;
+        bot.register(&ALICE, "alice", UserRole::User).await;
+
+        let replies = bot.send_text(&ALICE, "/broadcast", 1).await;
+        assert!(replies[0].text.starts_with("Unable to handle the message."));
+        assert!(bot.repository.list_broadcasts(10).await.unwrap().is_empty());
+        bot.stop().await;
+    }
+}
@@ -0,0 +1,34 @@
+use teloxide::types::ChatMemberUpdated;
+
+use crate::bot::HandlerResult;
+use crate::bot::core::events::{BotEvent, EventBus};
+use crate::bot::core::redaction::TelegramId;
+use crate::bot::core::repository::OutboxRepository;
+
+/// Telegram reports the status of the bot in a chat, in private chats `kicked` means the user blocked the bot.
+pub(crate) async fn my_chat_member<R: OutboxRepository>(update: ChatMemberUpdated, repository: R, events: EventBus) -> HandlerResult {
+    if update.chat.is_private() && update.new_chat_member.is_banned() {
+        tracing::info!("The bot was blocked by {}", TelegramId(update.chat.id.0));
+        events.publish(&repository, BotEvent::BotBlocked { telegram_id: update.chat.id.0 }).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::mock_api::{ALICE};
+    use crate::bot::test_bot::TestBot;
+
+    #[tokio::test]
+    async fn publishes_blocked_bot() {
+        let bot = TestBot::start().await;
+        bot.register(&ALICE, "alice", UserRole::User).await;
+
+        bot.api.block_bot(&ALICE);
+        // Updates of a chat are handled in order, the member update was handled before the reply.
+        bot.send_text(&ALICE, "/help", 1).await;
+        assert_eq!(bot.published_events().await, vec!["user_registered", "bot_blocked"]);
+        bot.stop().await;
+    }
+}
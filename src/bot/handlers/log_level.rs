@@ -0,0 +1,38 @@
+use teloxide::Bot;
+use teloxide::prelude::{Message, Requester};
+
+use crate::bot::HandlerResult;
+use crate::bot::core::logging::{log_filters, LogOutput};
+use crate::bot::core::metrics::MeasuredRequest;
+
+const OUTPUTS: [LogOutput; 2] = [LogOutput::Stdout, LogOutput::File];
+const RESET: &str = "reset";
+
+/// `/loglevel [stdout|file] [<filter>|reset]`, shows the filters without a new filter and changes both outputs without an output.
+pub(crate) async fn log_level(bot: Bot, msg: Message, arguments: String) -> HandlerResult {
+    let Some(filters) = log_filters() else {
+        bot.send_message(msg.chat.id, "Logging is not initialized.").send_measured().await?;
+        return Ok(());
+    };
+    let arguments = arguments.trim();
+    let (first, rest) = arguments.split_once(char::is_whitespace).unwrap_or((arguments, ""));
+    let (outputs, directives) = match first.parse::<LogOutput>() {
+        Ok(output) => (vec![output], rest.trim()),
+        Err(_) => (OUTPUTS.to_vec(), arguments),
+    };
+
+    let mut reply = vec![];
+    for output in outputs {
+        let result = match directives {
+            "" => Ok(()),
+            RESET => filters.reset(output),
+            directives => filters.set(output, directives),
+        };
+        match result {
+            Ok(()) => reply.push(format!("{}: {}", output, filters.current(output))),
+            Err(error) => reply.push(format!("{}: {}", output, error)),
+        }
+    }
+    bot.send_message(msg.chat.id, reply.join("\n")).send_measured().await?;
+    Ok(())
+}
@@ -2,3 +2,5 @@ pub(crate) mod search;
 pub(crate) mod product;
 pub(crate) mod register;
 pub(crate) mod broadcast;
+pub(crate) mod log_level;
+pub(crate) mod chat_member;
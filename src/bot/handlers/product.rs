@@ -3,9 +3,16 @@ use teloxide::payloads::SendMessageSetters;
 use teloxide::prelude::{CallbackQuery, Message, Requester};
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
 use crate::bot::{HandlerResult, MyDialogue, State};
+use crate::bot::core::db::model::NewOrder;
+use crate::bot::core::events::{BotEvent, EventBus};
+use crate::bot::core::metrics::MeasuredRequest;
+use crate::bot::core::repository::{OrderRepository, OutboxRepository};
+
+/// Products of the purchase dialogue and the mini app
+pub(crate) const PRODUCTS: [&str; 4] = ["Apple", "Banana", "Orange", "Potato"];
 
 pub(crate) async fn start_purchase(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, "Let's start! What's your full name?").await?;
+    bot.send_message(msg.chat.id, "Let's start! What's your full name?").send_measured().await?;
     dialogue.update(State::PurchaseReceiveFullName).await?;
     Ok(())
 }
@@ -13,36 +20,76 @@ pub(crate) async fn start_purchase(bot: Bot, dialogue: MyDialogue, msg: Message)
 pub(crate) async fn receive_full_name(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
     match msg.text().map(ToOwned::to_owned) {
         Some(full_name) => {
-            let products = ["Apple", "Banana", "Orange", "Potato"]
+            let products = PRODUCTS
                 .map(|product| InlineKeyboardButton::callback(product, product));
 
             bot.send_message(msg.chat.id, "Select a product:")
                 .reply_markup(InlineKeyboardMarkup::new([products]))
+                .send_measured()
                 .await?;
             dialogue.update(State::ReceiveProductChoice { full_name }).await?;
         }
         None => {
-            bot.send_message(msg.chat.id, "Please, send me your full name.").await?;
+            bot.send_message(msg.chat.id, "Please, send me your full name.").send_measured().await?;
         }
     }
 
     Ok(())
 }
 
-pub(crate) async fn receive_product_selection(
+pub(crate) async fn receive_product_selection<R: OrderRepository + OutboxRepository>(
     bot: Bot,
     dialogue: MyDialogue,
     full_name: String, // Available from `State::ReceiveProductChoice`.
     q: CallbackQuery,
+    repository: R,
+    events: EventBus,
 ) -> HandlerResult {
-    if let Some(product) = &q.data {
+    // callback data is sent by the client, only accept buttons of the keyboard
+    if let Some(product) = q.data.as_ref().filter(|product| PRODUCTS.contains(&product.as_str())) {
+        repository.record_order(NewOrder {
+            created_at: chrono::Utc::now().timestamp(),
+            telegram_id: dialogue.chat_id().0,
+            full_name: full_name.clone(),
+            product: product.clone(),
+        }).await?;
+        events.publish(&repository, BotEvent::OrderPlaced {
+            telegram_id: dialogue.chat_id().0,
+            full_name: full_name.clone(),
+            product: product.clone(),
+        }).await;
         bot.send_message(
             dialogue.chat_id(),
             format!("{full_name}, product '{product}' has been purchased successfully!"),
         )
+            .send_measured()
             .await?;
         dialogue.exit().await?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::mock_api::{ALICE};
+    use crate::bot::core::repository::OrderRepository;
+    use crate::bot::test_bot::TestBot;
+
+    #[tokio::test]
+    async fn records_order_of_selected_product() {
+        let bot = TestBot::start().await;
+        bot.register(&ALICE, "alice", UserRole::User).await;
+
+        assert_eq!(bot.send_text(&ALICE, "/purchase", 1).await[0].text, "Let's start! What's your full name?");
+        assert_eq!(bot.send_text(&ALICE, "Alice Example", 1).await[0].text, "Select a product:");
+        let replies = bot.press_button(&ALICE, "Banana", 1).await;
+        assert_eq!(replies[0].text, "Alice Example, product 'Banana' has been purchased successfully!");
+
+        let orders = bot.repository.list_orders(ALICE.id, 10).await.unwrap();
+        assert_eq!(orders.iter().map(|order| (order.full_name.as_str(), order.product.as_str())).collect::<Vec<_>>(), vec![("Alice Example", "Banana")]);
+        assert_eq!(bot.published_events().await, vec!["user_registered", "order_placed"]);
+        bot.stop().await;
+    }
+}
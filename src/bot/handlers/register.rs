@@ -2,35 +2,43 @@ use teloxide::Bot;
 use teloxide::prelude::{Message, Requester};
 use teloxide::types::Me;
 use teloxide::utils::command::BotCommands;
-use crate::bot::core::db::client::admin_client::DatabaseAdminClient;
-use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::audit::{audit, Actor, AuditAction};
 use crate::bot::core::db::DatabaseError;
+use crate::bot::core::events::{BotEvent, EventBus};
+use crate::bot::core::metrics::MeasuredRequest;
+use crate::bot::core::redaction::{TelegramId, Token};
+use crate::bot::core::repository::{AccountRepository, AuditRepository, OutboxRepository, UserRepository};
 use crate::bot::HandlerResult;
 
-pub(crate) async fn register(bot: Bot, msg: Message, mut database_client: DatabaseClient, me: Me) -> HandlerResult {
+pub(crate) async fn register<R: UserRepository + AccountRepository + AuditRepository + OutboxRepository>(bot: Bot, msg: Message, mut repository: R, events: EventBus, me: Me) -> HandlerResult {
     match msg.text().map(|data| crate::bot::schema::BasicCommands::parse(data, me.username())) {
         Some(Ok(crate::bot::schema::BasicCommands::Start(token))) => {
             if token.is_empty() {
-                bot.send_message(msg.chat.id, "Did not receive any data from you.").await?;
+                bot.send_message(msg.chat.id, "Did not receive any data from you.").send_measured().await?;
             } else {
-                tracing::debug!("Received start token {} from telegram account id={}", token, msg.chat.id.0);
+                tracing::debug!("Received start token {} from telegram account id={}", Token(&token), TelegramId(msg.chat.id.0));
                 let telegram_id = msg.chat.id.0;
-                let result = database_client.register_telegram_account_of_user(&token, telegram_id).await;
+                let newly_linked = !repository.known_user_exists(telegram_id).await?;
+                let result = repository.register_telegram_account_of_user(&token, telegram_id).await;
                 match result {
-                    Ok(_telegram_account) => {
-                        bot.send_message(msg.chat.id, "You were successfully registered.").await?;
+                    Ok(user) => {
+                        if newly_linked {
+                            audit(&repository, Actor::Telegram(&user.name), AuditAction::TelegramLinked, &user.name, None).await;
+                            events.publish(&repository, BotEvent::UserRegistered { user_name: user.name.clone(), telegram_id }).await;
+                        }
+                        bot.send_message(msg.chat.id, "You were successfully registered.").send_measured().await?;
                     }
                     Err(error) => {
-                        tracing::error!("Error adding user for telegram account id={} start={}: {}", msg.chat.id.0, token, error);
+                        tracing::error!("Error adding user for telegram account id={} start={}: {}", TelegramId(msg.chat.id.0), Token(&token), error);
                         match error {
                             DatabaseError::UnknownUser(_error) => {
-                                bot.send_message(msg.chat.id, "Could not find the user.").await?;
+                                bot.send_message(msg.chat.id, "Could not find the user.").send_measured().await?;
                             }
                             DatabaseError::CreateError(_error) => {
-                                bot.send_message(msg.chat.id, "Could not create the user.").await?;
+                                bot.send_message(msg.chat.id, "Could not create the user.").send_measured().await?;
                             }
                             _ => {
-                                bot.send_message(msg.chat.id, "An error occurred.").await?;
+                                bot.send_message(msg.chat.id, "An error occurred.").send_measured().await?;
                             }
                         }
                     }
@@ -38,13 +46,67 @@ pub(crate) async fn register(bot: Bot, msg: Message, mut database_client: Databa
             }
         }
         Some(Err(error)) => {
-            bot.send_message(msg.chat.id, "Could not parse data.").await?;
+            bot.send_message(msg.chat.id, "Could not parse data.").send_measured().await?;
 
             tracing::error!("Error parsing start command: {}", error);
         }
         _ => {
-            bot.send_message(msg.chat.id, "Did not receive the expected data from you.").await?;
+            bot.send_message(msg.chat.id, "Did not receive the expected data from you.").send_measured().await?;
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::mock_api::{ALICE, STRANGER};
+    use crate::bot::core::repository::{AuditRepository, UserRepository};
+    use crate::bot::test_bot::TestBot;
+
+    #[tokio::test]
+    async fn links_telegram_account_with_start_token() {
+        let bot = TestBot::start().await;
+        let alice = bot.repository.create_user("alice", UserRole::User).unwrap();
+
+        let replies = bot.send_text(&ALICE, &format!("/start {}", alice.start_token), 1).await;
+        assert_eq!(replies[0].chat_id, ALICE.id);
+        assert_eq!(replies[0].text, "You were successfully registered.");
+        assert_eq!(bot.repository.known_user(ALICE.id).await.unwrap().map(|user| user.name), Some("alice".to_string()));
+        let audit_events = bot.repository.list_audit_events(10).await.unwrap();
+        assert_eq!(audit_events.iter().map(|event| event.action.as_str()).collect::<Vec<_>>(), vec!["telegram_linked"]);
+        assert_eq!(bot.published_events().await, vec!["user_registered"]);
+
+        // using the start link again neither links nor publishes anything
+        let replies = bot.send_text(&ALICE, &format!("/start {}", alice.start_token), 1).await;
+        assert_eq!(replies[0].text, "You were successfully registered.");
+        assert_eq!(bot.published_events().await, vec!["user_registered"]);
+        bot.stop().await;
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_and_missing_start_token() {
+        let bot = TestBot::start().await;
+        bot.repository.create_user("alice", UserRole::User).unwrap();
+
+        let replies = bot.send_text(&STRANGER, "/start invalidtoken", 1).await;
+        assert_eq!(replies[0].text, "Could not find the user.");
+        let replies = bot.send_text(&STRANGER, "/start", 1).await;
+        assert_eq!(replies[0].text, "Did not receive any data from you.");
+        assert!(!bot.repository.known_user_exists(STRANGER.id).await.unwrap());
+        assert!(bot.published_events().await.is_empty());
+        bot.stop().await;
+    }
+
+    #[tokio::test]
+    async fn refuses_start_token_of_linked_user() {
+        let bot = TestBot::start().await;
+        let alice = bot.register(&ALICE, "alice", UserRole::User).await;
+
+        let replies = bot.send_text(&STRANGER, &format!("/start {}", alice.start_token), 1).await;
+        assert_eq!(replies[0].text, "An error occurred.");
+        assert!(!bot.repository.known_user_exists(STRANGER.id).await.unwrap());
+        assert_eq!(bot.repository.known_user(ALICE.id).await.unwrap().map(|user| user.name), Some("alice".to_string()));
+        bot.stop().await;
+    }
+}
@@ -2,11 +2,14 @@ use teloxide::Bot;
 use teloxide::prelude::{Message, Requester};
 
 use crate::bot::{HandlerResult, MyDialogue, State};
-use crate::bot::core::db::client::DatabaseClient;
+use crate::bot::core::metrics::MeasuredRequest;
+use crate::bot::core::redaction::TelegramId;
+use crate::bot::core::repository::UserRepository;
 
-pub(crate) async fn search_start(bot: Bot, dialogue: MyDialogue, msg: Message, db_client: DatabaseClient) -> HandlerResult {
-    bot.send_message(msg.chat.id, "Give me a search query.").await?;
-    tracing::info!("Initiating search for user id: {:?}", db_client.known_user_exists(msg.chat.id.0));
+pub(crate) async fn search_start<R: UserRepository>(bot: Bot, dialogue: MyDialogue, msg: Message, repository: R) -> HandlerResult {
+    bot.send_message(msg.chat.id, "Give me a search query.").send_measured().await?;
+    let known_user = repository.known_user_exists(msg.chat.id.0).await?;
+    tracing::info!("Initiating search for {}, known user: {}", TelegramId(msg.chat.id.0), known_user);
     dialogue.update(State::Search).await?;
     Ok(())
 }
@@ -15,13 +18,31 @@ pub(crate) async fn search_start(bot: Bot, dialogue: MyDialogue, msg: Message, d
 pub(crate) async fn receive_search_query(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
     match msg.text().map(ToOwned::to_owned) {
         Some(search_string) => {
-            bot.send_message(msg.chat.id, format!("Searching for {}", search_string)).await?;
+            bot.send_message(msg.chat.id, format!("Searching for {}", search_string)).send_measured().await?;
             dialogue.update(State::Start).await?;
         }
         None => {
-            bot.send_message(msg.chat.id, "Please, send me a proper search query.").await?;
+            bot.send_message(msg.chat.id, "Please, send me a proper search query.").send_measured().await?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::mock_api::{ALICE, STRANGER};
+    use crate::bot::test_bot::TestBot;
+
+    #[tokio::test]
+    async fn searches_for_query_of_registered_user() {
+        let bot = TestBot::start().await;
+        bot.register(&ALICE, "alice", UserRole::User).await;
+
+        assert!(bot.send_text(&STRANGER, "/search", 1).await[0].text.starts_with("Unable to handle the message."));
+        assert_eq!(bot.send_text(&ALICE, "/search", 1).await[0].text, "Give me a search query.");
+        assert_eq!(bot.send_text(&ALICE, "rust", 1).await[0].text, "Searching for rust");
+        bot.stop().await;
+    }
+}
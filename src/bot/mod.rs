@@ -8,6 +8,9 @@ pub(crate) mod start;
 pub(crate) mod handlers;
 pub(crate) mod schema;
 pub(crate) mod admin;
+pub(crate) mod config;
+#[cfg(test)]
+pub(crate) mod test_bot;
 
 type MyDialogue = Dialogue<State, InMemStorage<State>>;
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
@@ -23,3 +26,16 @@ pub enum State {
         full_name: String,
     },
 }
+
+impl State {
+    /// Name of the state without its data, which may contain user input.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            State::Start => "Start",
+            State::Search => "Search",
+            State::Broadcast => "Broadcast",
+            State::PurchaseReceiveFullName => "PurchaseReceiveFullName",
+            State::ReceiveProductChoice { .. } => "ReceiveProductChoice",
+        }
+    }
+}
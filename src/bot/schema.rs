@@ -2,13 +2,18 @@ use teloxide::{Bot, dptree};
 use teloxide::dispatching::{dialogue, HandlerExt, UpdateFilterExt, UpdateHandler};
 use teloxide::dispatching::dialogue::InMemStorage;
 use teloxide::prelude::{Message, Requester, Update};
+use teloxide::types::AllowedUpdate;
 use teloxide::utils::command::BotCommands;
 
 use crate::bot::{HandlerResult, MyDialogue, State};
-use crate::bot::core::db::client::DatabaseClient;
-use crate::bot::handlers::{product, broadcast, search};
+use crate::bot::core::metrics::MeasuredRequest;
+use crate::bot::core::repository::{BotRepository, UserRepository};
+use crate::bot::handlers::{product, broadcast, chat_member, log_level, search};
 use crate::bot::handlers::register::register;
 
+/// Update types filtered by [`schema`], checked against the subscription of the webhook.
+pub(crate) const HANDLED_UPDATES: &[AllowedUpdate] = &[AllowedUpdate::Message, AllowedUpdate::CallbackQuery, AllowedUpdate::MyChatMember];
+
 /// These commands are supported:
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase")]
@@ -35,16 +40,28 @@ enum UserCommands {
 enum AdminCommands {
     #[command(description = "Send a message to all registered users.")]
     Broadcast,
+    #[command(description = "Show or change the log filter: /loglevel [stdout|file] [<filter>|reset]")]
+    LogLevel(String),
+}
+
+/// Names of all commands without the leading slash, e.g. for metric labels.
+pub(crate) fn command_names() -> Vec<String> {
+    [BasicCommands::bot_commands(), UserCommands::bot_commands(), AdminCommands::bot_commands()]
+        .into_iter()
+        .flatten()
+        .map(|command| command.command.trim_start_matches('/').to_string())
+        .collect()
 }
 
-pub(crate) fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+/// Update handler tree, generic over the repository injected as dependency.
+pub(crate) fn schema<R: BotRepository>() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
     use dptree::case;
 
     let basic_command_handler = teloxide::filter_command::<BasicCommands, _>()
         .branch(
             case![State::Start]
-                .branch(case![BasicCommands::Help].endpoint(help))
-                .branch(case![BasicCommands::Start(token)].endpoint(register))
+                .branch(case![BasicCommands::Help].endpoint(help::<R>))
+                .branch(case![BasicCommands::Start(token)].endpoint(register::<R>))
         )
         .branch(case![BasicCommands::Cancel].endpoint(cancel));
 
@@ -52,11 +69,11 @@ pub(crate) fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync
         .branch(
             case![State::Start]
                 .branch(
-                    dptree::filter(|database_client: DatabaseClient, msg: Message| {
-                    msg.from.map(|user| database_client.known_user_exists(user.id.0 as i64)).unwrap_or(false)
+                    dptree::filter_async(|repository: R, msg: Message| async move {
+                    sender_has_role(&repository, &msg, false).await
                 })
                 .filter_command::<UserCommands>()
-                .branch(case![UserCommands::Search].endpoint(search::search_start))
+                .branch(case![UserCommands::Search].endpoint(search::search_start::<R>))
                 .branch(case![UserCommands::Purchase].endpoint(product::start_purchase)),
             )
         );
@@ -65,11 +82,12 @@ pub(crate) fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync
         .branch(
             case![State::Start]
                 .branch(
-                    dptree::filter(|database_client: DatabaseClient, msg: Message| {
-                        msg.from.map(|user| database_client.known_admin_user_exists(user.id.0 as i64)).unwrap_or(false)
+                    dptree::filter_async(|repository: R, msg: Message| async move {
+                        sender_has_role(&repository, &msg, true).await
                     })
                         .filter_command::<AdminCommands>()
-                        .branch(case![AdminCommands::Broadcast].endpoint(broadcast::broadcast_start)),
+                        .branch(case![AdminCommands::Broadcast].endpoint(broadcast::broadcast_start))
+                        .branch(case![AdminCommands::LogLevel(arguments)].endpoint(log_level::log_level)),
                 )
         );
 
@@ -80,7 +98,7 @@ pub(crate) fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync
 
     let second_stage_handlers = Update::filter_message()
         .branch(case![State::Search].endpoint(search::receive_search_query))
-        .branch(case![State::Broadcast].endpoint(broadcast::receive_broadcast_message))
+        .branch(case![State::Broadcast].endpoint(broadcast::receive_broadcast_message::<R>))
         .branch(case![State::PurchaseReceiveFullName].endpoint(product::receive_full_name));
 
     let message_handler = Update::filter_message()
@@ -90,42 +108,95 @@ pub(crate) fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync
         .branch(dptree::endpoint(invalid_state));
 
     let callback_query_handler = Update::filter_callback_query().branch(
-        case![State::ReceiveProductChoice { full_name }].endpoint(product::receive_product_selection),
+        case![State::ReceiveProductChoice { full_name }].endpoint(product::receive_product_selection::<R>),
     );
 
-    dialogue::enter::<Update, InMemStorage<State>, State, _>()
-        .branch(message_handler)
-        .branch(callback_query_handler)
+    // the status of the bot in a chat does not depend on the dialogue
+    let my_chat_member_handler = Update::filter_my_chat_member().endpoint(chat_member::my_chat_member::<R>);
+
+    dptree::entry()
+        .branch(my_chat_member_handler)
+        .branch(
+            dialogue::enter::<Update, InMemStorage<State>, State, _>()
+                .branch(message_handler)
+                .branch(callback_query_handler)
+        )
 }
 
 
-async fn help(bot: Bot, msg: Message, database_client: DatabaseClient) -> HandlerResult {
+/// Commands of registered users and admins, a failed lookup is logged and treated as unknown sender.
+async fn sender_has_role<R: UserRepository>(repository: &R, msg: &Message, admin: bool) -> bool {
+    let Some(sender) = &msg.from else {
+        return false;
+    };
+    let telegram_id = sender.id.0 as i64;
+    let known = if admin {
+        repository.known_admin_user_exists(telegram_id).await
+    } else {
+        repository.known_user_exists(telegram_id).await
+    };
+    known.unwrap_or_else(|error| {
+        tracing::error!("Could not look up the sender of a command: {}", error);
+        false
+    })
+}
+
+async fn help<R: UserRepository>(bot: Bot, msg: Message, repository: R) -> HandlerResult {
     let basic_commands = format!("Basic commands:\n{}", BasicCommands::descriptions());
     let user_commands = format!("User commands:\n{}", UserCommands::descriptions());
     let admin_commands = format!("Admin commands:\n{}", AdminCommands::descriptions());
-    if database_client.known_admin_user_exists(msg.chat.id.0) {
+    let user = repository.known_user(msg.chat.id.0).await?;
+    if user.as_ref().is_some_and(|user| user.is_admin()) {
         let response = format!("{}\n\n{}\n\n{}", basic_commands, user_commands, admin_commands);
-        bot.send_message(msg.chat.id, response).await?;
-    } else if database_client.known_user_exists(msg.chat.id.0) {
+        bot.send_message(msg.chat.id, response).send_measured().await?;
+    } else if user.is_some() {
         let response = format!("{}\n\n{}", basic_commands, user_commands);
-        bot.send_message(msg.chat.id, response).await?;
+        bot.send_message(msg.chat.id, response).send_measured().await?;
     } else {
         let response = format!("You are not registered. Please type /start <start_token> to register.\n\n{}", basic_commands);
-        bot.send_message(msg.chat.id, response).await?;
+        bot.send_message(msg.chat.id, response).send_measured().await?;
     }
     Ok(())
 }
 
 async fn cancel(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, "Cancelling the dialogue.").await?;
+    bot.send_message(msg.chat.id, "Cancelling the dialogue.").send_measured().await?;
     dialogue.exit().await?;
     Ok(())
 }
 
 async fn invalid_state(bot: Bot, msg: Message) -> HandlerResult {
     bot.send_message(msg.chat.id, "Unable to handle the message. Type /help to see the usage.")
+        .send_measured()
         .await?;
     Ok(())
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::core::db::user_representation::UserRole;
+    use crate::bot::core::mock_api::{ADMIN, ALICE, STRANGER};
+    use crate::bot::test_bot::TestBot;
+
+    #[tokio::test]
+    async fn help_lists_commands_of_role() {
+        let bot = TestBot::start().await;
+        bot.register(&ADMIN, "admin", UserRole::Admin).await;
+        bot.register(&ALICE, "alice", UserRole::User).await;
+
+        let stranger_help = &bot.send_text(&STRANGER, "/help", 1).await[0].text;
+        assert!(stranger_help.starts_with("You are not registered."), "{}", stranger_help);
+        assert!(stranger_help.contains("/start") && !stranger_help.contains("/search"), "{}", stranger_help);
+
+        let user_help = &bot.send_text(&ALICE, "/help", 1).await[0].text;
+        assert!(user_help.contains("User commands:") && user_help.contains("/purchase"), "{}", user_help);
+        assert!(!user_help.contains("Admin commands:"), "{}", user_help);
+
+        let admin_help = &bot.send_text(&ADMIN, "/help", 1).await[0].text;
+        assert!(admin_help.contains("User commands:") && admin_help.contains("Admin commands:"), "{}", admin_help);
+        assert!(admin_help.contains("/broadcast"), "{}", admin_help);
+        bot.stop().await;
+    }
+}
@@ -1,63 +1,176 @@
+use std::sync::Arc;
+
 use teloxide::{Bot, dptree};
 use teloxide::dispatching::dialogue::InMemStorage;
 use teloxide::dispatching::Dispatcher;
 use teloxide::error_handlers::LoggingErrorHandler;
-use teloxide::types::Me;
+use teloxide::types::{Me, Update};
 use teloxide::update_listeners::webhooks::Options;
 
+use crate::bot::core::admin_api::{admin_api_router, spawn_admin_api_listener};
+use crate::bot::core::bot_config::admin_api::BotAdminApiConfig;
+use crate::bot::core::bot_config::dashboard::BotDashboardConfig;
+use crate::bot::core::bot_config::events::BotEventsConfig;
 use crate::bot::core::bot_config::BotConfig;
+use crate::bot::core::bot_config::webapp::BotWebAppConfig;
 use crate::bot::core::bot_config::webhook::BotConfigWebHook;
+#[cfg(feature = "sqlite")]
+use crate::bot::core::db::backup;
+use crate::bot::core::dashboard::{dashboard_router, spawn_dashboard_listener};
 use crate::bot::core::db::client::DatabaseClient;
 use crate::bot::core::db::connection::MyDatabaseConnection;
-use crate::bot::core::dispatch::axum_update_listener;
+use crate::bot::core::dispatch::{axum_update_listener, polling_update_listener};
+use crate::bot::core::events::delivery::spawn_event_delivery;
+use crate::bot::core::events::EventBus;
 use crate::bot::core::healthcheck::bot_identity::ensure_configured_bot_name_is_valid;
+use crate::bot::core::healthcheck::readiness::BotReadiness;
+use crate::bot::core::incidents::report_incidents;
+use crate::bot::core::metrics::{handle_error, metrics, metrics_router, spawn_metrics_listener};
+use crate::bot::core::repository::BotRepository;
+use crate::bot::core::shutdown::{dispatch_until_signal, ShutdownOutcome, ShutdownSignal};
+use crate::bot::core::telemetry::update_span;
+use crate::bot::core::webapp::{configure_menu_button, webapp_router};
 use crate::bot::schema::schema;
 use crate::bot::State;
-use crate::{build, MyResult};
+use crate::build;
 
-pub(crate) async fn bot_start(use_webhook: bool) -> MyResult {
-    let bot = Bot::from_env();
-    let me = ensure_configured_bot_name_is_valid(&bot).await?;
-    log::info!("Bot started: {:?}", me);
-    print_banner(me);
+/// Runs until a shutdown signal, the outcome gives the exit code of the process.
+pub(crate) async fn bot_start(use_webhook: bool) -> Result<ShutdownOutcome, anyhow::Error> {
+    let bot_config = BotConfig::new()?;
+    // the token may come from a secret file, Bot::from_env only reads TELOXIDE_TOKEN
+    let bot = Bot::with_client(bot_config.bot_token.clone(), teloxide::net::client_from_env())
+        .set_api_url(bot_config.telegram_api_url());
+    tracing::info!("Using telegram api at {}", bot_config.api_url);
+    let me = ensure_configured_bot_name_is_valid(&bot, &bot_config.bot_name).await?;
+    tracing::info!("Bot started: {:?}", me);
+    print_banner(me.clone());
+    let events = EventBus::new(BotEventsConfig::new()?);
 
     let database_connection = MyDatabaseConnection::new().await?;
-    let database_client = DatabaseClient::load(database_connection.clone()).await?;
-    let bot_config = BotConfig::new()?;
+    let database_client = DatabaseClient::load(database_connection.clone(), &bot_config.bot_name).await?;
+    #[cfg(feature = "sqlite")]
+    let backup_task = backup::spawn_periodic_backup(&bot_config.storage);
+    let readiness = BotReadiness::new(Some(database_connection.clone()));
+    let admin_routes = admin_routes(&bot, &database_client, &readiness, &bot_config, use_webhook, &events).await?;
+    let outcome = dispatch(bot, database_client, readiness, bot_config, use_webhook, admin_routes, events).await;
+
+    // a running backup finishes on the blocking thread pool
+    #[cfg(feature = "sqlite")]
+    if let Some(backup_task) = backup_task {
+        backup_task.abort();
+    }
+    if let Err(error) = database_connection.close().await {
+        tracing::error!("Could not close the database: {}", error);
+    }
+    outcome
+}
 
+/// Routes of the admin api and the dashboard served by the webhook listener, separate listeners are started here.
+async fn admin_routes(bot: &Bot, database_client: &DatabaseClient, readiness: &Arc<BotReadiness>, bot_config: &BotConfig, use_webhook: bool, events: &EventBus) -> Result<Option<axum::Router>, anyhow::Error> {
+    let mut routes = None;
+    let admin_api_config = BotAdminApiConfig::new()?;
+    if admin_api_config.enabled {
+        let router = admin_api_router(database_client.clone(), bot.clone(), events.clone());
+        match admin_api_config.socket_address {
+            Some(socket_address) => spawn_admin_api_listener(socket_address, router).await?,
+            None => {
+                if !use_webhook {
+                    tracing::warn!("The admin api is only served by the webhook listener, configure TELOXIDE_ADMIN_API_BIND_ADDRESS to use it with polling.");
+                }
+                routes = Some(router);
+            }
+        }
+    }
+    let dashboard_config = BotDashboardConfig::new()?;
+    if dashboard_config.enabled {
+        let router = dashboard_router(database_client.clone(), readiness.clone(), &bot_config.bot_name, &bot_config.bot_token);
+        match dashboard_config.socket_address {
+            Some(socket_address) => spawn_dashboard_listener(socket_address, router).await?,
+            None => {
+                if !use_webhook {
+                    tracing::warn!("The dashboard is only served by the webhook listener, configure TELOXIDE_DASHBOARD_BIND_ADDRESS to use it with polling.");
+                }
+                routes = Some(routes.unwrap_or_default().merge(router));
+            }
+        }
+    }
+    Ok(routes)
+}
 
-    let dependency_map = dptree::deps![InMemStorage::<State>::new(), database_client, bot_config];
+async fn dispatch<R: BotRepository>(bot: Bot, repository: R, readiness: Arc<BotReadiness>, bot_config: BotConfig, use_webhook: bool, admin_routes: Option<axum::Router>, events: EventBus) -> Result<ShutdownOutcome, anyhow::Error> {
+    let metrics_router = metrics_router(repository.clone());
+    if let Some(metrics_socket_address) = bot_config.metrics_socket_address {
+        spawn_metrics_listener(metrics_socket_address, metrics_router.clone()).await?;
+    }
+    let webapp_config = BotWebAppConfig::new()?;
+    let webapp_routes = webapp_config.enabled
+        .then(|| webapp_router(repository.clone(), &bot_config.bot_token, webapp_config.assets_dir.clone()));
+    let delivery_task = spawn_event_delivery(repository.clone(), events.clone());
+    let shutdown_timeout = bot_config.shutdown_timeout;
+    let dependency_map = dptree::deps![InMemStorage::<State>::new(), repository, bot_config, readiness.clone(), events];
+    // remember the time of the last update for the readiness endpoint
+    let handler = update_span()
+        .inspect(|update: Update, readiness: Arc<BotReadiness>| {
+            readiness.record_update();
+            metrics().record_update(&update);
+        })
+        .chain(report_incidents::<R>())
+        .chain(schema::<R>());
+    let signal = ShutdownSignal::install()?;
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+        .dependencies(dependency_map)
+        .error_handler(Arc::new(handle_error))
+        .build();
+    let shutdown_token = dispatcher.shutdown_token();
 
-    if use_webhook {
-        log::info!("Starting bot using webhook listener...");
+    let outcome = if use_webhook {
+        tracing::info!("Starting bot using webhook listener...");
         let webhook_config = BotConfigWebHook::new()?;
-        log::info!("Webhook config: {:?}", webhook_config);
+        tracing::info!("Webhook config: {:?}", webhook_config);
 
-        let listener = axum_update_listener(
-            bot.clone(),
-            Options::new(webhook_config.socket_address, webhook_config.public_bot_url),
-        ).await
-            .expect("Couldn't setup webhook");
-        Dispatcher::builder(bot, schema())
-            .dependencies(dependency_map)
-            .enable_ctrlc_handler()
-            .build()
-            .dispatch_with_listener(
-                listener,
-                LoggingErrorHandler::with_custom_text("An error from the update listener"),
-            )
-            .await;
+        let mut options = Options::new(webhook_config.socket_address, webhook_config.public_bot_url);
+        match webhook_config.secret_token {
+            Some(secret_token) => {
+                options = options.secret_token(secret_token.expose().to_string());
+            }
+            None => {
+                tracing::warn!("No webhook secret token configured, generated one for this run. Configure TELOXIDE_WEBHOOK_SECRET to share it between restarts and replicas.");
+            }
+        }
+        if webapp_routes.is_some() {
+            // the app still works from links and inline buttons
+            if let Err(error) = configure_menu_button(&bot, &webapp_config.menu_button_text, &webhook_config.public_url).await {
+                tracing::warn!("Could not configure the menu button of the mini app: {}", error);
+            }
+        }
+        let routes = metrics_router
+            .merge(admin_routes.unwrap_or_default())
+            .merge(webapp_routes.unwrap_or_default());
+        let listener = axum_update_listener(bot, options, readiness.clone(), routes).await?;
+        readiness.set_dispatcher_running(true);
+        let dispatch = dispatcher.dispatch_with_listener(
+            listener,
+            LoggingErrorHandler::with_custom_text("An error from the update listener"),
+        );
+        dispatch_until_signal(dispatch, shutdown_token, signal, &readiness, shutdown_timeout).await?
     } else {
-        log::info!("Starting bot without webhook listener...");
-        Dispatcher::builder(bot, schema())
-            .dependencies(dependency_map)
-            .enable_ctrlc_handler()
-            .build()
-            .dispatch()
-            .await;
+        tracing::info!("Starting bot without webhook listener...");
+        if webapp_config.enabled {
+            tracing::warn!("The mini app is only served by the webhook listener, it is disabled with polling.");
+        }
+        let listener = polling_update_listener(bot).await;
+        readiness.set_dispatcher_running(true);
+        let dispatch = dispatcher.dispatch_with_listener(
+            listener,
+            LoggingErrorHandler::with_custom_text("An error from the update listener"),
+        );
+        dispatch_until_signal(dispatch, shutdown_token, signal, &readiness, shutdown_timeout).await?
+    };
+    // undelivered events stay in the outbox
+    if let Some(delivery_task) = delivery_task {
+        delivery_task.abort();
     }
-
-    Ok(())
+    Ok(outcome)
 }
 
 fn print_banner(me: Me) {
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use teloxide::{Bot, dptree};
+use teloxide::dispatching::{Dispatcher, ShutdownToken, UpdateHandler};
+use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::error_handlers::LoggingErrorHandler;
+use tokio::task::JoinHandle;
+
+use crate::bot::core::bot_config::events::BotEventsConfig;
+use crate::bot::core::db::user_representation::{UserRepresentation, UserRole};
+use crate::bot::core::dispatch::polling_update_listener;
+use crate::bot::core::events::EventBus;
+use crate::bot::core::mock_api::{MockApi, MockUser, SentMessage};
+use crate::bot::core::repository::in_memory::InMemoryRepository;
+use crate::bot::core::repository::OutboxRepository;
+use crate::bot::schema::schema;
+use crate::bot::State;
+
+pub(crate) const TEST_BOT_NAME: &str = "testbot";
+pub(crate) const TEST_BOT_TOKEN: &str = "123456:test-token";
+/// Events are only written to the outbox, nothing is delivered to this url
+const TEST_EVENTS_URL: &str = "http://127.0.0.1:9/events";
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn events_config() -> BotEventsConfig {
+    BotEventsConfig {
+        urls: vec![TEST_EVENTS_URL.parse().unwrap()],
+        secret: None,
+        max_attempts: 1,
+        retry_delay_seconds: 1,
+    }
+}
+
+/// Update handlers of [`schema`] with the in-memory repository, polling a mock Telegram Bot API.
+pub(crate) struct TestBot {
+    pub api: Arc<MockApi>,
+    pub repository: InMemoryRepository,
+    shutdown_token: ShutdownToken,
+    dispatch_task: JoinHandle<()>,
+}
+
+impl TestBot {
+    pub(crate) async fn start() -> Self {
+        Self::start_with_handler(schema::<InMemoryRepository>()).await
+    }
+
+    /// Dispatch the updates to another handler, it gets the dependencies of [`schema`].
+    pub(crate) async fn start_with_handler(handler: UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>) -> Self {
+        let api = MockApi::new(TEST_BOT_NAME);
+        let bot = Bot::new(TEST_BOT_TOKEN).set_api_url(api.serve().await);
+        let repository = InMemoryRepository::new(TEST_BOT_NAME);
+
+        let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+            .dependencies(dptree::deps![InMemStorage::<State>::new(), repository.clone(), EventBus::new(events_config())])
+            .build();
+        let shutdown_token = dispatcher.shutdown_token();
+        let listener = polling_update_listener(bot).await;
+        let dispatch_task = tokio::spawn(async move {
+            dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::with_custom_text("An error from the update listener")).await
+        });
+        Self { api, repository, shutdown_token, dispatch_task }
+    }
+
+    /// Create a user and link the telegram account with its start link.
+    pub(crate) async fn register(&self, from: &MockUser, user_name: &str, role: UserRole) -> UserRepresentation {
+        let user = self.repository.create_user(user_name, role).unwrap();
+        let replies = self.send_text(from, &format!("/start {}", user.start_token), 1).await;
+        assert_eq!(replies[0].text, "You were successfully registered.");
+        user
+    }
+
+    /// Send a text message and return the first `reply_count` messages sent by the bot afterwards.
+    pub(crate) async fn send_text(&self, from: &MockUser, text: &str, reply_count: usize) -> Vec<SentMessage> {
+        let already_sent = self.api.sent_messages().len();
+        self.api.send_text(from, text);
+        self.wait_for_replies(already_sent, reply_count).await
+    }
+
+    /// Press an inline keyboard button and return the first `reply_count` messages sent by the bot afterwards.
+    pub(crate) async fn press_button(&self, from: &MockUser, data: &str, reply_count: usize) -> Vec<SentMessage> {
+        let already_sent = self.api.sent_messages().len();
+        self.api.press_button(from, data);
+        self.wait_for_replies(already_sent, reply_count).await
+    }
+
+    async fn wait_for_replies(&self, already_sent: usize, reply_count: usize) -> Vec<SentMessage> {
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            let mut replies = self.api.sent_messages().split_off(already_sent);
+            if replies.len() >= reply_count {
+                replies.truncate(reply_count);
+                return replies;
+            }
+            assert!(Instant::now() < deadline, "expected {} replies, the bot sent {:?}", reply_count, replies);
+            self.api.wait_for_message(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Types of the published events in order.
+    pub(crate) async fn published_events(&self) -> Vec<String> {
+        self.repository.due_outbox_entries(TEST_EVENTS_URL, i64::MAX, 100).await.unwrap()
+            .into_iter()
+            .map(|entry| entry.event_type)
+            .collect()
+    }
+
+    pub(crate) async fn stop(self) {
+        if let Ok(shutdown) = self.shutdown_token.shutdown() {
+            shutdown.await;
+        }
+        self.dispatch_task.await.unwrap();
+    }
+}
@@ -1,11 +1,16 @@
-use std::ops::Not;
+use std::io::Write;
+use std::path::PathBuf;
 use std::process;
 use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
 
 use crate::bot::admin::AdminCli;
+use crate::bot::config::ConfigCli;
+use crate::bot::core::bot_config::source::ConfigSource;
+use crate::bot::core::healthcheck::output::{render, OutputFormat};
 use crate::bot::core::healthcheck::run_healthcheck;
-use crate::bot::core::util;
+use crate::bot::core::logging;
+use crate::bot::core::telemetry::TelemetryGuard;
 use crate::bot::start::bot_start;
 
 shadow_rs::shadow!(build);
@@ -19,6 +24,12 @@ pub type MyResult = anyhow::Result<()>;
 #[command(about = "a nice bot for telegram")]
 #[command(long_version = None)]
 struct Cli {
+    /// Configuration file in TOML format, environment variables take precedence
+    #[arg(long, global = true, env = "TELOXIDE_CONFIG_FILE")]
+    config: Option<PathBuf>,
+    /// Override a setting, e.g. `--set storage.data_dir=/tmp/bot`
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    settings: Vec<String>,
     #[command(subcommand)]
     command: TaskCli,
 }
@@ -30,9 +41,14 @@ enum TaskCli {
     /// Run telegram bot without web hook
     Dev,
     /// Check telegram API for health of this bot
-    Healthcheck,
+    Healthcheck {
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
     /// Admin cli
     Admin(AdminCli),
+    /// Configuration
+    Config(ConfigCli),
     /// Version
     Version,
 }
@@ -40,39 +56,53 @@ enum TaskCli {
 
 #[tokio::main]
 async fn main() -> MyResult {
-    dotenv().expect(".env file not found!");
+    // the .env file is optional, settings may also come from the environment or a configuration file
+    let _ = dotenv();
 
     let args = Cli::parse();
+    ConfigSource::load(args.config.as_deref(), &args.settings)?.install();
+    // created before the span exporter, its drop runs on every return
+    let telemetry = TelemetryGuard;
     match args.command {
-        TaskCli::Healthcheck => {
+        TaskCli::Healthcheck { .. } | TaskCli::Config(_) => {
             // do not enable logging here
         }
         _ => {
-            util::init_tracing()?;
+            logging::init_tracing()?;
         }
     }
 
+    let mut exit_code = 0;
     match args.command {
         TaskCli::Bot => {
-            bot_start(true).await?;
+            exit_code = bot_start(true).await?.exit_code();
         }
         TaskCli::Dev => {
-            bot_start(false).await?;
+            exit_code = bot_start(false).await?.exit_code();
         }
-        TaskCli::Healthcheck => {
+        TaskCli::Healthcheck { format } => {
             let result = run_healthcheck().await;
-            println!("{}", result.to_json());
-            if result.healthy.not() {
-                process::exit(1);
-            }
+            let (output, healthcheck_exit_code) = render(&result, format);
+            println!("{}", output);
+            exit_code = healthcheck_exit_code;
         }
         TaskCli::Admin(implementation) => {
             implementation.default_handling().await?;
         }
+        TaskCli::Config(implementation) => {
+            implementation.default_handling().await?;
+        }
         TaskCli::Version => {
             println!("Version: {}", build::VERSION)
         }
     }
 
+    // exports the remaining spans, log lines are written unbuffered
+    drop(telemetry);
+    if exit_code != 0 {
+        // skips destructors, flush what is left in the buffer of stdout
+        let _ = std::io::stdout().flush();
+        process::exit(exit_code);
+    }
     Ok(())
 }